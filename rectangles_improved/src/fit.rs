@@ -0,0 +1,260 @@
+// Operations for fitting one rectangle inside another while keeping its
+// aspect ratio, as needed by thumbnail and viewport code.
+//
+// This is the second implementation block for Rectangle mentioned in
+// main.rs. Keeping these methods in their own module stops the original
+// block from growing too large.
+//
+// All of the fitting is done with integer maths, so the results never pick up
+// floating point error. Only `scale` takes a floating point factor.
+
+use crate::{Placement, Rectangle};
+
+// How fractional pixels should be turned into whole ones
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    Down,
+    Up,
+    Nearest,
+}
+
+impl Rounding {
+    // numerator / denominator, rounded as requested
+    fn divide(&self, numerator: u64, denominator: u64) -> u64 {
+        match self {
+            Rounding::Down => numerator / denominator,
+            Rounding::Up => numerator.div_ceil(denominator),
+            Rounding::Nearest => (2 * numerator + denominator) / (2 * denominator),
+        }
+    }
+
+    fn apply(&self, value: f64) -> f64 {
+        match self {
+            Rounding::Down => value.floor(),
+            Rounding::Up => value.ceil(),
+            Rounding::Nearest => value.round(),
+        }
+    }
+}
+
+// The result of letterboxing: the content, centred in the target, along with
+// the bars which fill the space either side of it. There are no bars when the
+// aspect ratios already match.
+#[derive(Debug)]
+pub struct Letterbox {
+    pub content: Placement,
+    pub bars: Vec<Placement>,
+}
+
+// Whether scaling `source` to the width of `target` leaves its height no
+// taller than the target, i.e. the width is the limiting side.
+// Cross-multiplying avoids any division: tw / sw <= th / sh.
+fn width_limited(source: &Rectangle, target: &Rectangle) -> bool {
+    u64::from(target.width) * u64::from(source.height)
+        <= u64::from(target.height) * u64::from(source.width)
+}
+
+// The other side of `source` once one side has been scaled from `from` to
+// `to`, with the result converted back to u32
+fn scale_side(side: u32, to: u32, from: u32, rounding: Rounding) -> u32 {
+    let scaled = rounding.divide(u64::from(side) * u64::from(to), u64::from(from));
+    u32::try_from(scaled).unwrap_or(u32::MAX)
+}
+
+impl Rectangle {
+    fn has_aspect_ratio(&self) -> bool {
+        self.width > 0 && self.height > 0
+    }
+
+    // The largest rectangle with the same aspect ratio as this one which fits
+    // entirely inside `target`. Returns None if this rectangle has a side of
+    // zero, as it then has no aspect ratio to keep.
+    pub fn contain(&self, target: &Rectangle) -> Option<Rectangle> {
+        if !self.has_aspect_ratio() {
+            return None;
+        }
+        // Rounding to the nearest pixel can never push the scaled side past
+        // the target, as the exact value is already no bigger than it
+        Some(if width_limited(self, target) {
+            Rectangle {
+                width: target.width,
+                height: scale_side(self.height, target.width, self.width, Rounding::Nearest),
+            }
+        } else {
+            Rectangle {
+                width: scale_side(self.width, target.height, self.height, Rounding::Nearest),
+                height: target.height,
+            }
+        })
+    }
+
+    // The smallest rectangle with the same aspect ratio as this one which
+    // completely covers `target`. Anything outside of the target would be
+    // cropped off.
+    pub fn cover(&self, target: &Rectangle) -> Option<Rectangle> {
+        if !self.has_aspect_ratio() {
+            return None;
+        }
+        // Rounding up here makes sure no gap is left along the edge of the
+        // target
+        Some(if width_limited(self, target) {
+            Rectangle {
+                width: scale_side(self.width, target.height, self.height, Rounding::Up),
+                height: target.height,
+            }
+        } else {
+            Rectangle {
+                width: target.width,
+                height: scale_side(self.height, target.width, self.width, Rounding::Up),
+            }
+        })
+    }
+
+    // Contains this rectangle within `target`, centres it, and fills the
+    // leftover space with bars (letterboxing top and bottom, or pillarboxing
+    // on the left and right). Any odd pixel goes to the second bar.
+    pub fn letterbox(&self, target: &Rectangle) -> Option<Letterbox> {
+        let content = self.contain(target)?;
+        let spare_x = target.width - content.width;
+        let spare_y = target.height - content.height;
+        let (x, y) = (spare_x / 2, spare_y / 2);
+
+        let mut bars = Vec::new();
+        if spare_y > 0 {
+            bars.push(Placement::new(0, 0, Rectangle { width: target.width, height: y }));
+            bars.push(Placement::new(
+                0,
+                y + content.height,
+                Rectangle { width: target.width, height: spare_y - y },
+            ));
+        } else if spare_x > 0 {
+            bars.push(Placement::new(0, 0, Rectangle { width: x, height: target.height }));
+            bars.push(Placement::new(
+                x + content.width,
+                0,
+                Rectangle { width: spare_x - x, height: target.height },
+            ));
+        }
+        // Drop any zero sized bar, which happens when there is only one
+        // spare pixel to share out
+        bars.retain(|bar| bar.rect.has_aspect_ratio());
+
+        Some(Letterbox {
+            content: Placement::new(x, y, content),
+            bars,
+        })
+    }
+
+    // Multiplies both sides by `factor`, rounding each as requested. As with
+    // the Guess type from the error-handling exercise, a factor which isn't a
+    // finite, non-negative number is a bug in the caller, so we panic.
+    pub fn scale(&self, factor: f64, rounding: Rounding) -> Rectangle {
+        if !factor.is_finite() || factor < 0.0 {
+            panic!("Scale factor must be finite and non-negative, got {factor}.");
+        }
+        // `as` saturates when converting from f64, so very large results are
+        // clamped to u32::MAX rather than wrapping around
+        Rectangle {
+            width: rounding.apply(f64::from(self.width) * factor) as u32,
+            height: rounding.apply(f64::from(self.height) * factor) as u32,
+        }
+    }
+
+    // The counterpart to Rectangle::square: rather than building a square
+    // from a size, this returns the largest square which fits inside this
+    // rectangle
+    pub fn largest_square(&self) -> Rectangle {
+        Rectangle::square(self.width.min(self.height))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(width: u32, height: u32) -> Rectangle {
+        Rectangle { width, height }
+    }
+
+    fn size(rect: Rectangle) -> (u32, u32) {
+        (rect.width, rect.height)
+    }
+
+    fn spot(placement: &Placement) -> (u32, u32, u32, u32) {
+        (placement.x, placement.y, placement.rect.width, placement.rect.height)
+    }
+
+    #[test]
+    fn contain_fits_inside_the_target() {
+        assert_eq!(rect(1920, 1080).contain(&rect(400, 400)).map(size), Some((400, 225)));
+        assert_eq!(rect(1080, 1920).contain(&rect(400, 400)).map(size), Some((225, 400)));
+        // 66.67 rounds to the nearest pixel
+        assert_eq!(rect(300, 200).contain(&rect(100, 100)).map(size), Some((100, 67)));
+        assert_eq!(rect(16, 9).contain(&rect(32, 18)).map(size), Some((32, 18)));
+        assert!(rect(0, 9).contain(&rect(32, 18)).is_none());
+    }
+
+    #[test]
+    fn cover_leaves_no_gaps() {
+        assert_eq!(rect(300, 200).cover(&rect(100, 100)).map(size), Some((150, 100)));
+        assert_eq!(rect(200, 300).cover(&rect(100, 100)).map(size), Some((100, 150)));
+        // 23.33 rounds up, so the bottom edge is still covered
+        assert_eq!(rect(3, 7).cover(&rect(10, 10)).map(size), Some((10, 24)));
+        assert!(rect(3, 0).cover(&rect(10, 10)).is_none());
+    }
+
+    #[test]
+    fn letterbox_puts_bars_top_and_bottom() {
+        let boxed = rect(1920, 1080).letterbox(&rect(400, 400)).unwrap();
+        assert_eq!(spot(&boxed.content), (0, 87, 400, 225));
+        let bars: Vec<_> = boxed.bars.iter().map(spot).collect();
+        assert_eq!(bars, [(0, 0, 400, 87), (0, 312, 400, 88)]);
+    }
+
+    #[test]
+    fn pillarbox_puts_bars_either_side() {
+        let boxed = rect(300, 400).letterbox(&rect(400, 400)).unwrap();
+        assert_eq!(spot(&boxed.content), (50, 0, 300, 400));
+        let bars: Vec<_> = boxed.bars.iter().map(spot).collect();
+        assert_eq!(bars, [(0, 0, 50, 400), (350, 0, 50, 400)]);
+    }
+
+    #[test]
+    fn letterbox_only_adds_bars_that_are_needed() {
+        let boxed = rect(16, 9).letterbox(&rect(32, 18)).unwrap();
+        assert_eq!(spot(&boxed.content), (0, 0, 32, 18));
+        assert!(boxed.bars.is_empty());
+
+        // A single spare pixel makes one bar rather than two
+        let boxed = rect(3, 1).letterbox(&rect(3, 2)).unwrap();
+        assert_eq!(spot(&boxed.content), (0, 0, 3, 1));
+        let bars: Vec<_> = boxed.bars.iter().map(spot).collect();
+        assert_eq!(bars, [(0, 1, 3, 1)]);
+    }
+
+    #[test]
+    fn scale_rounds_as_asked() {
+        let r = rect(5, 3);
+        assert_eq!(size(r.scale(1.5, Rounding::Down)), (7, 4));
+        assert_eq!(size(r.scale(1.5, Rounding::Up)), (8, 5));
+        assert_eq!(size(r.scale(1.5, Rounding::Nearest)), (8, 5));
+        let r = rect(10, 10);
+        assert_eq!(size(r.scale(0.33, Rounding::Down)), (3, 3));
+        assert_eq!(size(r.scale(0.33, Rounding::Up)), (4, 4));
+        assert_eq!(size(r.scale(0.33, Rounding::Nearest)), (3, 3));
+        assert_eq!(size(r.scale(0.0, Rounding::Up)), (0, 0));
+    }
+
+    #[test]
+    #[should_panic(expected = "Scale factor must be finite and non-negative, got -1.")]
+    fn scale_refuses_a_negative_factor() {
+        rect(5, 3).scale(-1.0, Rounding::Down);
+    }
+
+    #[test]
+    fn the_largest_square_uses_the_shorter_side() {
+        assert_eq!(size(rect(7, 3).largest_square()), (3, 3));
+        assert_eq!(size(rect(2, 9).largest_square()), (2, 2));
+        assert_eq!(size(rect(4, 4).largest_square()), (4, 4));
+    }
+}
@@ -0,0 +1,153 @@
+// Finding the largest free rectangle inside a bounding area, given a set of
+// obstacles which are already placed there. This is useful for working out
+// where an overlay can go without covering anything important.
+//
+// Obstacle edges split the area into a grid of cells of varying sizes. Every
+// cell is either completely free or completely blocked, and the best free
+// rectangle always lines up with cell boundaries, so we only need to look at
+// the grid rather than every pixel.
+//
+// We then go through the grid one row at a time, keeping track of how tall
+// the free column above each cell is. Each row then looks like a histogram,
+// and the largest rectangle under a histogram can be found with a stack in
+// a single pass. With n obstacles there are at most 2n + 1 rows and columns.
+// Checking every cell against every obstacle makes the search O(n^3), which
+// is plenty fast for the handful of things on a screen.
+
+use crate::{Placement, Rectangle};
+
+// Sorted, de-duplicated cell boundaries along one axis, clipped to [0, size]
+fn boundaries(size: u32, edges: impl Iterator<Item = u32>) -> Vec<u32> {
+    let mut cuts: Vec<u32> = edges.map(|e| e.min(size)).collect();
+    cuts.push(0);
+    cuts.push(size);
+    cuts.sort_unstable();
+    cuts.dedup();
+    cuts
+}
+
+// Does the obstacle cover the cell which begins at `start`? Cells never
+// straddle an obstacle edge, so checking the cell's start point is enough.
+fn covers(obstacle_start: u32, obstacle_size: u32, start: u32) -> bool {
+    obstacle_start <= start && start < obstacle_start.saturating_add(obstacle_size)
+}
+
+// Returns the largest free rectangle inside `bounds`, positioned relative to
+// the top left of the bounds. Obstacles may overlap each other and may hang
+// off the edge of the bounds. If several rectangles share the largest area,
+// the first one found while scanning rows from the top down is returned.
+// Returns None if there is no free space at all.
+pub fn largest_free_rectangle(bounds: &Rectangle, obstacles: &[Placement]) -> Option<Placement> {
+    let xs = boundaries(
+        bounds.width,
+        obstacles.iter().flat_map(|o| [o.x, o.x.saturating_add(o.rect.width)]),
+    );
+    let ys = boundaries(
+        bounds.height,
+        obstacles.iter().flat_map(|o| [o.y, o.y.saturating_add(o.rect.height)]),
+    );
+    let columns = xs.len() - 1;
+
+    // heights[c] is the free height, in pixels, running upwards from the
+    // bottom of the current row in column c
+    let mut heights = vec![0u64; columns];
+    let mut best: Option<(u64, Placement)> = None;
+
+    for row in 0..ys.len() - 1 {
+        let (top, bottom) = (ys[row], ys[row + 1]);
+        for (c, height) in heights.iter_mut().enumerate() {
+            let blocked = obstacles
+                .iter()
+                .any(|o| covers(o.x, o.rect.width, xs[c]) && covers(o.y, o.rect.height, top));
+            *height = if blocked {
+                0
+            } else {
+                *height + u64::from(bottom - top)
+            };
+        }
+
+        // Largest rectangle in a histogram whose bars have different widths.
+        // The stack holds (first column, bar height) pairs with strictly
+        // increasing heights. When a lower bar turns up, every taller bar on
+        // the stack can't extend any further to the right, so we measure it.
+        let mut stack: Vec<(usize, u64)> = Vec::new();
+        for c in 0..=columns {
+            let height = if c < columns { heights[c] } else { 0 };
+            let mut start = c;
+            while let Some(&(first, bar)) = stack.last() {
+                if bar < height {
+                    break;
+                }
+                stack.pop();
+                let width = u64::from(xs[c] - xs[first]);
+                let area = width * bar;
+                if area > 0 && best.as_ref().is_none_or(|(best_area, _)| area > *best_area) {
+                    // The bar height came from adding whole rows together,
+                    // so it always fits back into a u32
+                    let bar = bar as u32;
+                    let placed = Placement::new(
+                        xs[first],
+                        bottom - bar,
+                        Rectangle {
+                            width: width as u32,
+                            height: bar,
+                        },
+                    );
+                    best = Some((area, placed));
+                }
+                start = first;
+            }
+            stack.push((start, height));
+        }
+    }
+
+    best.map(|(_, placed)| placed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(x: u32, y: u32, width: u32, height: u32) -> Placement {
+        Placement::new(x, y, Rectangle { width, height })
+    }
+
+    fn spot(placement: Placement) -> (u32, u32, u32, u32) {
+        (placement.x, placement.y, placement.rect.width, placement.rect.height)
+    }
+
+    fn largest(width: u32, height: u32, obstacles: &[Placement]) -> Option<(u32, u32, u32, u32)> {
+        largest_free_rectangle(&Rectangle { width, height }, obstacles).map(spot)
+    }
+
+    #[test]
+    fn with_no_obstacles_everything_is_free() {
+        assert_eq!(largest(100, 50, &[]), Some((0, 0, 100, 50)));
+        assert_eq!(largest(0, 50, &[]), None);
+    }
+
+    #[test]
+    fn an_obstacle_over_everything_leaves_nothing() {
+        assert_eq!(largest(100, 50, &[at(0, 0, 100, 50)]), None);
+        assert_eq!(largest(100, 50, &[at(0, 0, 500, 500)]), None);
+        // Two halves add up to the whole area too
+        assert_eq!(largest(100, 50, &[at(0, 0, 60, 50), at(40, 0, 60, 50)]), None);
+    }
+
+    #[test]
+    fn the_largest_gap_is_found() {
+        // Below the obstacle is bigger than either side of it
+        assert_eq!(largest(100, 100, &[at(40, 0, 20, 30)]), Some((0, 30, 100, 70)));
+        // Between two obstacles
+        assert_eq!(largest(100, 100, &[at(0, 0, 30, 100), at(80, 0, 20, 100)]), Some((30, 0, 50, 100)));
+    }
+
+    #[test]
+    fn obstacles_can_hang_off_the_edge() {
+        assert_eq!(largest(100, 50, &[at(80, 0, 50, 50)]), Some((0, 0, 80, 50)));
+        assert_eq!(largest(100, 50, &[at(0, 20, 100, 100)]), Some((0, 0, 100, 20)));
+        // Entirely outside the bounds, or so far out they would overflow
+        assert_eq!(largest(100, 50, &[at(200, 200, 10, 10)]), Some((0, 0, 100, 50)));
+        assert_eq!(largest(100, 50, &[at(u32::MAX - 5, 0, 100, 100)]), Some((0, 0, 100, 50)));
+    }
+}
@@ -0,0 +1,341 @@
+// A small row/column layout engine. A layout is a tree of nodes: leaves are
+// the things being drawn, and containers line their children up either in a
+// row (left to right) or a column (top to bottom). Each child asks for a
+// fixed size, a percentage of its parent, or a share of whatever space is
+// left over (flex). Working through the tree gives a Placement for every
+// node, which is all a terminal dashboard needs to draw itself.
+//
+// Everything is measured in whole cells, and any leftover cells from
+// dividing up flex space are handed out one at a time, so the children of a
+// container always fill it exactly.
+
+use crate::{Placement, Rectangle};
+
+// How much of its parent's main axis a node asks for. The main axis is the
+// width for children of a row, and the height for children of a column. On
+// the other axis, children always stretch to fill their parent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Size {
+    Fixed(u32),
+    // A percentage of the space available to the children, capped at 100
+    Percent(u32),
+    // A share of whatever is left once fixed and percentage sizes have been
+    // taken out, weighted against the other flex children
+    Flex(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Row,
+    Column,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Padding {
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+    pub left: u32,
+}
+
+impl Padding {
+    pub fn all(size: u32) -> Padding {
+        Padding {
+            top: size,
+            right: size,
+            bottom: size,
+            left: size,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Node {
+    name: String,
+    size: Size,
+    direction: Direction,
+    padding: Padding,
+    gap: u32,
+    children: Vec<Node>,
+}
+
+// Nodes are put together with a builder style API, for example:
+//   Node::row("body", Size::Flex(1))
+//       .gap(1)
+//       .child(Node::leaf("menu", Size::Fixed(20)))
+impl Node {
+    fn new(name: &str, size: Size, direction: Direction) -> Node {
+        Node {
+            name: String::from(name),
+            size,
+            direction,
+            padding: Padding::default(),
+            gap: 0,
+            children: Vec::new(),
+        }
+    }
+
+    pub fn leaf(name: &str, size: Size) -> Node {
+        Node::new(name, size, Direction::Row)
+    }
+
+    pub fn row(name: &str, size: Size) -> Node {
+        Node::new(name, size, Direction::Row)
+    }
+
+    pub fn column(name: &str, size: Size) -> Node {
+        Node::new(name, size, Direction::Column)
+    }
+
+    pub fn padding(mut self, padding: Padding) -> Node {
+        self.padding = padding;
+        self
+    }
+
+    pub fn gap(mut self, gap: u32) -> Node {
+        self.gap = gap;
+        self
+    }
+
+    pub fn child(mut self, child: Node) -> Node {
+        self.children.push(child);
+        self
+    }
+}
+
+// A node once it has been laid out. `path` joins the names of the node and
+// all of its parents with '/', so nodes with the same name can be told apart.
+#[derive(Debug)]
+pub struct LaidOut {
+    pub path: String,
+    pub placement: Placement,
+}
+
+// Splits `total` between flex children in proportion to their weights. Each
+// child first gets the whole number part of its share; the cells left over
+// then go to the children with the largest remainders (earliest first on a
+// tie), so nothing is lost to rounding.
+fn share_flex(total: u32, weights: &[u32]) -> Vec<u32> {
+    let weight_sum: u64 = weights.iter().map(|&w| u64::from(w)).sum();
+    if weight_sum == 0 {
+        return vec![0; weights.len()];
+    }
+    let exact: Vec<u64> = weights
+        .iter()
+        .map(|&w| u64::from(total) * u64::from(w))
+        .collect();
+    let mut shares: Vec<u32> = exact.iter().map(|e| (e / weight_sum) as u32).collect();
+
+    let handed_out: u32 = shares.iter().sum();
+    let mut by_remainder: Vec<usize> = (0..weights.len()).collect();
+    by_remainder.sort_by(|&a, &b| (exact[b] % weight_sum).cmp(&(exact[a] % weight_sum)).then(a.cmp(&b)));
+    for &i in by_remainder.iter().take((total - handed_out) as usize) {
+        shares[i] += 1;
+    }
+    shares
+}
+
+// Works out how long each child is along the container's main axis, given
+// `available` cells once padding and gaps are taken out. Fixed and
+// percentage sizes are handed out first, in order, and are cut short if they
+// would overflow the container.
+fn main_axis_sizes(children: &[Node], available: u32) -> Vec<u32> {
+    let mut sizes = vec![0; children.len()];
+    let mut remaining = available;
+    for (size, child) in sizes.iter_mut().zip(children) {
+        let wanted = match child.size {
+            Size::Fixed(cells) => cells,
+            Size::Percent(percent) => (u64::from(available) * u64::from(percent.min(100)) / 100) as u32,
+            Size::Flex(_) => continue,
+        };
+        *size = wanted.min(remaining);
+        remaining -= *size;
+    }
+
+    let flex: Vec<(usize, u32)> = children
+        .iter()
+        .enumerate()
+        .filter_map(|(i, child)| match child.size {
+            Size::Flex(weight) => Some((i, weight)),
+            _ => None,
+        })
+        .collect();
+    let weights: Vec<u32> = flex.iter().map(|&(_, w)| w).collect();
+    for (&(i, _), share) in flex.iter().zip(share_flex(remaining, &weights)) {
+        sizes[i] = share;
+    }
+    sizes
+}
+
+fn place(node: &Node, path: &str, area: Placement, out: &mut Vec<LaidOut>) {
+    // Parents are listed before their children
+    out.push(LaidOut {
+        path: String::from(path),
+        placement: area,
+    });
+    if node.children.is_empty() {
+        return;
+    }
+
+    // The space left for children once the padding has been taken off. If the
+    // padding is bigger than the node, the children get nothing.
+    let pad = node.padding;
+    let inner_width = area.rect.width.saturating_sub(pad.left + pad.right);
+    let inner_height = area.rect.height.saturating_sub(pad.top + pad.bottom);
+    let mut x = area.x + pad.left.min(area.rect.width);
+    let mut y = area.y + pad.top.min(area.rect.height);
+
+    let main_length = match node.direction {
+        Direction::Row => inner_width,
+        Direction::Column => inner_height,
+    };
+    let gaps = node.gap.saturating_mul(node.children.len() as u32 - 1);
+    let sizes = main_axis_sizes(&node.children, main_length.saturating_sub(gaps));
+
+    for (child, length) in node.children.iter().zip(sizes) {
+        let rect = match node.direction {
+            Direction::Row => Rectangle { width: length, height: inner_height },
+            Direction::Column => Rectangle { width: inner_width, height: length },
+        };
+        let child_path = format!("{}/{}", path, child.name);
+        place(child, &child_path, Placement::new(x, y, rect), out);
+        match node.direction {
+            Direction::Row => x += length + node.gap,
+            Direction::Column => y += length + node.gap,
+        }
+    }
+}
+
+// Lays out the tree rooted at `root` to fill `area`. The root's own size is
+// ignored, as it always takes up the whole area. Nodes are returned parent
+// first, in the same order as they appear in the tree.
+pub fn layout(root: &Node, area: Rectangle) -> Vec<LaidOut> {
+    let mut out = Vec::new();
+    place(root, &root.name, Placement::new(0, 0, area), &mut out);
+    out
+}
+
+// A plain text listing of a layout, one node per line. The output is stable,
+// so it can be compared against a saved copy to spot changes to a layout.
+pub fn describe(nodes: &[LaidOut]) -> String {
+    let mut text = String::new();
+    for node in nodes {
+        let p = &node.placement;
+        text.push_str(&format!(
+            "{:<28} x={:<3} y={:<3} w={:<3} h={}\n",
+            node.path, p.x, p.y, p.rect.width, p.rect.height
+        ));
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Each node's path, and its x, y, width and height
+    fn rects(nodes: &[LaidOut]) -> Vec<(&str, u32, u32, u32, u32)> {
+        nodes
+            .iter()
+            .map(|n| {
+                let p = &n.placement;
+                (n.path.as_str(), p.x, p.y, p.rect.width, p.rect.height)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn a_row_hands_out_fixed_then_percent_then_flex() {
+        let root = Node::row("row", Size::Flex(1))
+            .padding(Padding::all(1))
+            .gap(2)
+            .child(Node::leaf("fixed", Size::Fixed(20)))
+            .child(Node::leaf("flex", Size::Flex(1)))
+            .child(Node::leaf("percent", Size::Percent(25)));
+        let nodes = layout(&root, Rectangle { width: 100, height: 10 });
+        // 94 cells are left once padding and gaps are taken out, and 25% of
+        // that rounds down to 23
+        assert_eq!(
+            rects(&nodes),
+            vec![
+                ("row", 0, 0, 100, 10),
+                ("row/fixed", 1, 1, 20, 8),
+                ("row/flex", 23, 1, 51, 8),
+                ("row/percent", 76, 1, 23, 8),
+            ]
+        );
+    }
+
+    #[test]
+    fn a_column_splits_flex_space_by_weight() {
+        let root = Node::column("column", Size::Flex(1))
+            .child(Node::leaf("top", Size::Fixed(5)))
+            .child(Node::leaf("middle", Size::Flex(1)))
+            .child(Node::leaf("bottom", Size::Flex(2)));
+        let nodes = layout(&root, Rectangle { width: 10, height: 30 });
+        // 25 cells split 1:2 is 8.33 and 16.67, so the spare cell goes to the
+        // bottom, which has the larger remainder
+        assert_eq!(
+            rects(&nodes),
+            vec![
+                ("column", 0, 0, 10, 30),
+                ("column/top", 0, 0, 10, 5),
+                ("column/middle", 0, 5, 10, 8),
+                ("column/bottom", 0, 13, 10, 17),
+            ]
+        );
+    }
+
+    #[test]
+    fn leftover_cells_go_to_the_largest_remainders() {
+        assert_eq!(share_flex(10, &[1, 1, 1]), vec![4, 3, 3]);
+        assert_eq!(share_flex(100, &[1, 1, 1]), vec![34, 33, 33]);
+        assert_eq!(share_flex(8, &[3, 2, 2]), vec![4, 2, 2]);
+        assert_eq!(share_flex(8, &[2, 3, 2]), vec![2, 4, 2]);
+        assert_eq!(share_flex(5, &[1, 2, 2]), vec![1, 2, 2]);
+        assert_eq!(share_flex(7, &[0, 0]), vec![0, 0]);
+    }
+
+    #[test]
+    fn children_too_big_for_their_parent_are_cut_short() {
+        let root = Node::row("row", Size::Flex(1))
+            .child(Node::leaf("a", Size::Fixed(20)))
+            .child(Node::leaf("b", Size::Percent(150)))
+            .child(Node::leaf("c", Size::Flex(1)));
+        let nodes = layout(&root, Rectangle { width: 30, height: 4 });
+        assert_eq!(
+            rects(&nodes)[1..],
+            [("row/a", 0, 0, 20, 4), ("row/b", 20, 0, 10, 4), ("row/c", 30, 0, 0, 4)]
+        );
+
+        // Padding wider than the node leaves nothing for its children
+        let root = Node::column("column", Size::Flex(1))
+            .padding(Padding::all(20))
+            .child(Node::leaf("inside", Size::Flex(1)));
+        let nodes = layout(&root, Rectangle { width: 30, height: 30 });
+        assert_eq!(rects(&nodes)[1], ("column/inside", 20, 20, 0, 0));
+    }
+
+    #[test]
+    fn a_nested_layout_snapshot() {
+        let root = Node::column("screen", Size::Flex(1))
+            .child(Node::leaf("header", Size::Fixed(1)))
+            .child(
+                Node::row("body", Size::Flex(1))
+                    .gap(1)
+                    .child(Node::leaf("menu", Size::Fixed(10)))
+                    .child(Node::leaf("main", Size::Flex(1))),
+            )
+            .child(Node::leaf("footer", Size::Fixed(1)));
+        let nodes = layout(&root, Rectangle { width: 40, height: 12 });
+        let expected = "\
+screen                       x=0   y=0   w=40  h=12
+screen/header                x=0   y=0   w=40  h=1
+screen/body                  x=0   y=1   w=40  h=10
+screen/body/menu             x=0   y=1   w=10  h=10
+screen/body/main             x=11  y=1   w=29  h=10
+screen/footer                x=0   y=11  w=40  h=1
+";
+        assert_eq!(describe(&nodes), expected);
+    }
+}
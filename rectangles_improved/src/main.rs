@@ -1,5 +1,20 @@
+// Larger pieces of functionality built on top of Rectangle live in their
+// own modules
+mod collision;
+mod fit;
+mod free_space;
+mod layout;
+mod nesting;
+mod units;
+
+use collision::{Body, Vec2, World};
+use fit::Rounding;
+use layout::{Node, Padding, Size};
+use nesting::Rotation;
+use units::{Dpi, Length, PhysicalRectangle, Unit};
+
 // Previously, we defined Rectangle as a simple struct
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 struct Rectangle {
     width: u32,
     height: u32,
@@ -46,7 +61,22 @@ impl Rectangle {
 }
 
 // Note that a struct can have more than one implementation block, some use cases
-// for this will be covered later in the book
+// for this will be covered later in the book. There's a second one in fit.rs.
+
+// A Rectangle on its own only has a size. Placement pins one down to a
+// position, measured from the top left corner of whatever contains it.
+#[derive(Debug, Clone, Copy)]
+struct Placement {
+    x: u32,
+    y: u32,
+    rect: Rectangle,
+}
+
+impl Placement {
+    fn new(x: u32, y: u32, rect: Rectangle) -> Self {
+        Self { x, y, rect }
+    }
+}
 
 fn main() {
     let rect1 = Rectangle {
@@ -77,4 +107,142 @@ fn main() {
     println!("Can rect1 hold rect3? {}", rect1.can_hold(&rect3));
 
     let sq = Rectangle::square(3);
-}
\ No newline at end of file
+    println!("sq is {:?}, with an area of {}", sq, sq.area());
+
+    // Find the longest run of rectangles which each hold the next one
+    let boxes = [
+        Rectangle { width: 30, height: 50 },
+        Rectangle { width: 10, height: 40 },
+        Rectangle { width: 60, height: 45 },
+        Rectangle { width: 20, height: 25 },
+        Rectangle { width: 5, height: 8 },
+        Rectangle { width: 45, height: 12 },
+    ];
+    for rotation in [Rotation::Fixed, Rotation::Allowed] {
+        let chain = nesting::longest_nesting_chain(&boxes, rotation);
+        println!("Longest nesting chain ({:?}): {} rectangles", rotation, chain.len());
+        for link in chain {
+            let rotated = if link.rotated { " (rotated)" } else { "" };
+            println!("  {:?}{}", boxes[link.index], rotated);
+        }
+    }
+
+    // Mix units by converting through a DPI. At 254 DPI, 1mm is exactly 10px.
+    let dpi = Dpi::new(254).expect("254 is a valid DPI");
+    let card = PhysicalRectangle::new(Length::mm(85), Length::mm(55));
+    let photo = PhysicalRectangle::new(Length::inches(3), Length::px(500));
+    println!(
+        "The card covers {:.1} square millimetres, or {:.2} square inches",
+        card.area(dpi).in_unit(Unit::Millimetres),
+        card.area(dpi).in_unit(Unit::Inches)
+    );
+    println!("Can the photo hold the card? {}", photo.can_hold(&card, dpi));
+    println!("Photo area compared to card: {:?}", photo.cmp_area(&card, dpi));
+    match card.to_pixels(dpi) {
+        Ok(rect) => println!("In pixels, the card is {:?}", rect),
+        Err(e) => println!("Could not convert the card: {}", e),
+    }
+
+    // At the default of 96 DPI, a millimetre is not a whole number of pixels,
+    // so the conversion fails instead of silently truncating
+    let width = card.width;
+    println!("The card is {} {:?} wide", width.value(), width.unit());
+    match width.to_unit(Unit::Pixels, Dpi::default()) {
+        Ok(px) => println!("{} is {}", width, px),
+        Err(e) => println!("At {} DPI, {}", Dpi::default().value(), e),
+    }
+    if let Err(e) = Dpi::new(0) {
+        println!("{}", e);
+    }
+    let screen = PhysicalRectangle::from(&rect1);
+    println!(
+        "rect1 is {} by {}, covering {:.3} square inches at 96 DPI",
+        screen.width,
+        screen.height,
+        screen.area(Dpi::default()).in_unit(Unit::Inches)
+    );
+    println!(
+        "Compared to an inch, rect1's width is {:?}",
+        screen.width.cmp_at(&Length::new(1, Unit::Inches), Dpi::default())
+    );
+
+    // Drop a box into a walled pit and let it bounce around for two seconds.
+    // Every step is the same length, so the run can be checked headlessly.
+    let mut world = World::new(0.5);
+    world.gravity = Vec2::new(0.0, 600.0);
+    let crate_box = world.add(Body::moving(10.0, 0.0, Rectangle::square(10), Vec2::new(120.0, 300.0)));
+    let floor = world.add(Body::fixed(0.0, 100.0, Rectangle { width: 100, height: 10 }));
+    world.add(Body::fixed(-10.0, 0.0, Rectangle { width: 10, height: 110 }));
+    world.add(Body::fixed(100.0, 0.0, Rectangle { width: 10, height: 110 }));
+    let impacts = world.run(1.0 / 60.0, 120);
+    println!("{} collisions were resolved, the first few were:", impacts.len());
+    for impact in impacts.iter().take(3) {
+        println!(
+            "At {:.3}s body {} hit body {} (normal {:?})",
+            impact.time, impact.a, impact.b, impact.normal
+        );
+    }
+    let landed = impacts
+        .iter()
+        .any(|i| i.a == crate_box && i.b == floor);
+    let resting = world.bodies[crate_box];
+    println!(
+        "After {:.2}s the box is at ({:.1}, {:.1}); did it hit the floor? {}",
+        world.elapsed, resting.position.x, resting.position.y, landed
+    );
+
+    // Fit a 4:3 photo into a 16:9 viewport in a few different ways
+    let photo = Rectangle { width: 1600, height: 1200 };
+    let viewport = Rectangle { width: 1280, height: 720 };
+    println!("Contained: {:?}", photo.contain(&viewport));
+    println!("Covering: {:?}", photo.cover(&viewport));
+    if let Some(boxed) = photo.letterbox(&viewport) {
+        println!(
+            "Letterboxed: {:?} at ({}, {}), with bars {:?}",
+            boxed.content.rect, boxed.content.x, boxed.content.y, boxed.bars
+        );
+    }
+    for rounding in [Rounding::Down, Rounding::Up, Rounding::Nearest] {
+        println!("rect2 scaled by 0.15, {:?}: {:?}", rounding, rect2.scale(0.15, rounding));
+    }
+    println!("The largest square inside rect3 is {:?}", rect3.largest_square());
+
+    // Find the biggest gap on a 1280x720 screen for an overlay, steering
+    // clear of a header, a sidebar and a floating button
+    let screen = Rectangle { width: 1280, height: 720 };
+    let obstacles = [
+        Placement::new(0, 0, Rectangle { width: 1280, height: 60 }),
+        Placement::new(0, 60, Rectangle { width: 240, height: 660 }),
+        Placement::new(1180, 620, Rectangle { width: 80, height: 80 }),
+    ];
+    match free_space::largest_free_rectangle(&screen, &obstacles) {
+        Some(free) => println!(
+            "The largest free space is {:?} at ({}, {}), with an area of {}",
+            free.rect,
+            free.x,
+            free.y,
+            free.rect.area()
+        ),
+        None => println!("There is no free space on the screen"),
+    }
+
+    // Lay out a terminal dashboard: a header, a sidebar and a main panel
+    // split between a chart and a log, with a status line at the bottom
+    let dashboard = Node::column("dashboard", Size::Flex(1))
+        .child(Node::leaf("header", Size::Fixed(3)))
+        .child(
+            Node::row("body", Size::Flex(1))
+                .padding(Padding::all(1))
+                .gap(1)
+                .child(Node::leaf("sidebar", Size::Percent(25)))
+                .child(
+                    Node::column("main", Size::Flex(1))
+                        .gap(1)
+                        .child(Node::leaf("chart", Size::Flex(2)))
+                        .child(Node::leaf("log", Size::Flex(1))),
+                ),
+        )
+        .child(Node::leaf("status", Size::Fixed(1)));
+    let terminal = Rectangle { width: 80, height: 24 };
+    print!("{}", layout::describe(&layout::layout(&dashboard, terminal)));
+}
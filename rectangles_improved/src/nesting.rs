@@ -0,0 +1,151 @@
+// Using `can_hold` as a partial order over rectangles, we can ask for the
+// longest sequence in which each rectangle holds the next one. This is
+// the classic box-nesting problem.
+//
+// A naive approach compares every pair of rectangles, which is O(n^2).
+// Instead we sort the rectangles so that widths only ever increase, then
+// look for the longest strictly increasing run of heights. That second
+// step is the longest increasing subsequence (LIS) problem, which can be
+// solved in O(n log n) using binary search.
+
+use crate::Rectangle;
+
+// Whether a rectangle may be turned on its side to fit inside another
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rotation {
+    Fixed,
+    Allowed,
+}
+
+// One step of a nesting chain. `index` points back into the slice that was
+// passed in, and `rotated` records whether the rectangle had to be turned
+// through 90 degrees to take its place in the chain.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainLink {
+    pub index: usize,
+    pub rotated: bool,
+}
+
+// The width and height actually used for a rectangle once any rotation has
+// been applied
+fn oriented(rect: &Rectangle, rotation: Rotation) -> (u32, u32, bool) {
+    match rotation {
+        Rotation::Allowed if rect.width > rect.height => (rect.height, rect.width, true),
+        _ => (rect.width, rect.height, false),
+    }
+}
+
+// Returns the longest chain of rectangles in which each one can hold the
+// next, outermost rectangle first. The result only depends on the order of
+// the input, so the same rectangles will always give the same chain.
+pub fn longest_nesting_chain(rects: &[Rectangle], rotation: Rotation) -> Vec<ChainLink> {
+    // With rotation allowed, rect A holds rect B exactly when A's short side
+    // beats B's short side and A's long side beats B's long side. So we can
+    // normalise every rectangle to (short, long) and carry on as normal.
+    let dims: Vec<(u32, u32, bool)> = rects.iter().map(|r| oriented(r, rotation)).collect();
+
+    // Sort by width ascending. Rectangles with equal widths can never hold
+    // each other (`can_hold` is strict), so we order those by height
+    // descending; this stops the LIS below from picking two of them.
+    let mut order: Vec<usize> = (0..rects.len()).collect();
+    order.sort_by(|&a, &b| {
+        dims[a]
+            .0
+            .cmp(&dims[b].0)
+            .then(dims[b].1.cmp(&dims[a].1))
+            .then(a.cmp(&b))
+    });
+
+    // tails[k] holds the index of the rectangle which ends the best chain of
+    // length k + 1 found so far, i.e. the one with the smallest height.
+    // prev[i] remembers which rectangle came before i in its chain, so we
+    // can rebuild the chain at the end.
+    let mut tails: Vec<usize> = Vec::new();
+    let mut prev: Vec<Option<usize>> = vec![None; rects.len()];
+
+    for &i in &order {
+        let height = dims[i].1;
+        let pos = tails.partition_point(|&t| dims[t].1 < height);
+        if pos > 0 {
+            prev[i] = Some(tails[pos - 1]);
+        }
+        if pos == tails.len() {
+            tails.push(i);
+        } else {
+            tails[pos] = i;
+        }
+    }
+
+    // Walk back from the end of the longest chain. This visits the largest
+    // rectangle first, which is the order we want to return.
+    let mut chain = Vec::with_capacity(tails.len());
+    let mut current = tails.last().copied();
+    while let Some(i) = current {
+        chain.push(ChainLink {
+            index: i,
+            rotated: dims[i].2,
+        });
+        current = prev[i];
+    }
+    chain
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rects(sizes: &[(u32, u32)]) -> Vec<Rectangle> {
+        sizes.iter().map(|&(width, height)| Rectangle { width, height }).collect()
+    }
+
+    fn indexes(chain: &[ChainLink]) -> Vec<usize> {
+        chain.iter().map(|link| link.index).collect()
+    }
+
+    #[test]
+    fn nothing_to_nest() {
+        assert_eq!(longest_nesting_chain(&[], Rotation::Fixed), []);
+        assert_eq!(longest_nesting_chain(&[], Rotation::Allowed), []);
+    }
+
+    #[test]
+    fn a_chain_without_rotation() {
+        let boxes = rects(&[(3, 3), (10, 8), (5, 4), (6, 5), (2, 9), (1, 1)]);
+        let chain = longest_nesting_chain(&boxes, Rotation::Fixed);
+        assert_eq!(indexes(&chain), [1, 3, 2, 0, 5]);
+        assert!(chain.iter().all(|link| !link.rotated));
+        for pair in chain.windows(2) {
+            assert!(boxes[pair[0].index].can_hold(&boxes[pair[1].index]));
+        }
+    }
+
+    #[test]
+    fn rotation_makes_a_longer_chain() {
+        // 4x9 only fits between the others once they're turned round
+        let boxes = rects(&[(10, 8), (4, 9), (7, 3), (1, 1)]);
+        assert_eq!(indexes(&longest_nesting_chain(&boxes, Rotation::Fixed)), [0, 2, 3]);
+
+        let chain = longest_nesting_chain(&boxes, Rotation::Allowed);
+        assert_eq!(
+            chain,
+            [
+                ChainLink { index: 0, rotated: true },
+                ChainLink { index: 1, rotated: false },
+                ChainLink { index: 2, rotated: true },
+                ChainLink { index: 3, rotated: false },
+            ]
+        );
+    }
+
+    #[test]
+    fn equal_rectangles_dont_nest() {
+        let boxes = rects(&[(4, 4), (4, 4), (4, 4)]);
+        assert_eq!(longest_nesting_chain(&boxes, Rotation::Fixed).len(), 1);
+        // Sharing only a width, or only a height, isn't enough either
+        let boxes = rects(&[(4, 6), (4, 5), (3, 6), (3, 5)]);
+        assert_eq!(indexes(&longest_nesting_chain(&boxes, Rotation::Fixed)), [0, 3]);
+        // Nor is being the same rectangle turned on its side
+        let boxes = rects(&[(4, 6), (6, 4)]);
+        assert_eq!(longest_nesting_chain(&boxes, Rotation::Allowed).len(), 1);
+    }
+}
@@ -0,0 +1,309 @@
+// Rectangle measures everything in pixels, but real layouts mix pixels,
+// millimetres and inches. Here we attach a unit to each dimension, and use a
+// configurable DPI (dots, or pixels, per inch) to convert between them.
+//
+// Converting between units with floats would quietly lose precision, so
+// instead every length is turned into a whole number of "ticks". A tick is
+// 1/(254 * dpi) of an inch, which is small enough that a pixel, a millimetre
+// and an inch are all exact multiples of it:
+//   1 inch = 254 * dpi ticks
+//   1 mm   = 10 * dpi ticks   (as there are 25.4mm in an inch)
+//   1 px   = 254 ticks        (as there are dpi pixels in an inch)
+// Areas and `can_hold` checks are then done on ticks, so they are exact.
+//
+// The DPI is capped at Dpi::MAX so that the longest possible length (u32::MAX
+// inches) still fits in a u64 of ticks, and the largest area in a u128.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use crate::Rectangle;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Unit {
+    Pixels,
+    Millimetres,
+    Inches,
+}
+
+impl Unit {
+    // How many ticks make up one of this unit at the given DPI
+    fn ticks(&self, dpi: Dpi) -> u64 {
+        let dpi = u64::from(dpi.value());
+        match self {
+            Unit::Pixels => 254,
+            Unit::Millimetres => 10 * dpi,
+            Unit::Inches => 254 * dpi,
+        }
+    }
+
+    fn suffix(&self) -> &'static str {
+        match self {
+            Unit::Pixels => "px",
+            Unit::Millimetres => "mm",
+            Unit::Inches => "in",
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Unit::Pixels => "pixels",
+            Unit::Millimetres => "millimetres",
+            Unit::Inches => "inches",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnitError {
+    // A DPI of zero would make every conversion divide by zero
+    ZeroDpi,
+    // Above Dpi::MAX, a length in ticks could overflow
+    DpiTooHigh(u32),
+    // The length doesn't come out as a whole number in the target unit
+    Inexact { length: Length, target: Unit },
+}
+
+impl fmt::Display for UnitError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UnitError::ZeroDpi => write!(f, "DPI must be greater than zero"),
+            UnitError::DpiTooHigh(value) => {
+                write!(f, "a DPI of {} is too high, the most is {}", value, Dpi::MAX)
+            }
+            UnitError::Inexact { length, target } => write!(
+                f,
+                "{} is not a whole number of {}",
+                length,
+                target.name()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for UnitError {}
+
+// As with the Guess type in the error-handling exercise, wrapping the DPI in
+// its own type means the rest of the code can assume it is valid
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dpi(u32);
+
+impl Dpi {
+    // Far beyond any real screen or printer
+    pub const MAX: u32 = 1_000_000;
+
+    pub fn new(value: u32) -> Result<Dpi, UnitError> {
+        if value == 0 {
+            return Err(UnitError::ZeroDpi);
+        }
+        if value > Dpi::MAX {
+            return Err(UnitError::DpiTooHigh(value));
+        }
+        Ok(Dpi(value))
+    }
+
+    pub fn value(&self) -> u32 {
+        self.0
+    }
+}
+
+// 96 DPI is the conventional "CSS pixel" density
+impl Default for Dpi {
+    fn default() -> Self {
+        Dpi(96)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Length {
+    value: u32,
+    unit: Unit,
+}
+
+impl Length {
+    pub fn new(value: u32, unit: Unit) -> Length {
+        Length { value, unit }
+    }
+
+    pub fn px(value: u32) -> Length {
+        Length::new(value, Unit::Pixels)
+    }
+
+    pub fn mm(value: u32) -> Length {
+        Length::new(value, Unit::Millimetres)
+    }
+
+    pub fn inches(value: u32) -> Length {
+        Length::new(value, Unit::Inches)
+    }
+
+    pub fn value(&self) -> u32 {
+        self.value
+    }
+
+    pub fn unit(&self) -> Unit {
+        self.unit
+    }
+
+    fn ticks(&self, dpi: Dpi) -> u64 {
+        u64::from(self.value) * self.unit.ticks(dpi)
+    }
+
+    // Converts to another unit, but only if the result is a whole number.
+    // Rather than truncating, an inexact conversion is reported as an error so
+    // the caller can decide how it should be rounded.
+    pub fn to_unit(self, unit: Unit, dpi: Dpi) -> Result<Length, UnitError> {
+        let ticks = self.ticks(dpi);
+        let per_unit = unit.ticks(dpi);
+        let inexact = UnitError::Inexact {
+            length: self,
+            target: unit,
+        };
+        if !ticks.is_multiple_of(per_unit) {
+            return Err(inexact);
+        }
+        let value = u32::try_from(ticks / per_unit).map_err(|_| inexact)?;
+        Ok(Length::new(value, unit))
+    }
+
+    // Compares two lengths which may be in different units
+    pub fn cmp_at(&self, other: &Length, dpi: Dpi) -> Ordering {
+        self.ticks(dpi).cmp(&other.ticks(dpi))
+    }
+}
+
+impl fmt::Display for Length {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", self.value, self.unit.suffix())
+    }
+}
+
+// An area held in square ticks, along with the DPI used to measure it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Area {
+    square_ticks: u128,
+    dpi: Dpi,
+}
+
+impl Area {
+    // The area expressed in square units. This is only intended for display,
+    // comparisons should be made with `PhysicalRectangle::cmp_area` instead.
+    pub fn in_unit(&self, unit: Unit) -> f64 {
+        let per_unit = u128::from(unit.ticks(self.dpi));
+        self.square_ticks as f64 / (per_unit * per_unit) as f64
+    }
+}
+
+// A rectangle whose sides carry their own units. The width and height do not
+// need to share a unit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PhysicalRectangle {
+    pub width: Length,
+    pub height: Length,
+}
+
+impl PhysicalRectangle {
+    pub fn new(width: Length, height: Length) -> PhysicalRectangle {
+        PhysicalRectangle { width, height }
+    }
+
+    pub fn area(&self, dpi: Dpi) -> Area {
+        Area {
+            square_ticks: u128::from(self.width.ticks(dpi)) * u128::from(self.height.ticks(dpi)),
+            dpi,
+        }
+    }
+
+    pub fn cmp_area(&self, other: &PhysicalRectangle, dpi: Dpi) -> Ordering {
+        self.area(dpi).square_ticks.cmp(&other.area(dpi).square_ticks)
+    }
+
+    // The same strict comparison as Rectangle::can_hold, but across units
+    pub fn can_hold(&self, other: &PhysicalRectangle, dpi: Dpi) -> bool {
+        self.width.cmp_at(&other.width, dpi) == Ordering::Greater
+            && self.height.cmp_at(&other.height, dpi) == Ordering::Greater
+    }
+
+    // Converts back to a plain pixel Rectangle, failing if either side is not
+    // a whole number of pixels
+    pub fn to_pixels(self, dpi: Dpi) -> Result<Rectangle, UnitError> {
+        Ok(Rectangle {
+            width: self.width.to_unit(Unit::Pixels, dpi)?.value(),
+            height: self.height.to_unit(Unit::Pixels, dpi)?.value(),
+        })
+    }
+}
+
+// Any plain Rectangle can be treated as a physical one measured in pixels
+impl From<&Rectangle> for PhysicalRectangle {
+    fn from(rect: &Rectangle) -> Self {
+        PhysicalRectangle::new(Length::px(rect.width), Length::px(rect.height))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dpi(value: u32) -> Dpi {
+        Dpi::new(value).unwrap()
+    }
+
+    #[test]
+    fn dpi_must_be_in_range() {
+        assert_eq!(Dpi::new(0), Err(UnitError::ZeroDpi));
+        assert_eq!(Dpi::new(Dpi::MAX + 1), Err(UnitError::DpiTooHigh(Dpi::MAX + 1)));
+        assert_eq!(Dpi::new(Dpi::MAX).map(|dpi| dpi.value()), Ok(Dpi::MAX));
+    }
+
+    #[test]
+    fn an_inch_is_always_25_point_4_millimetres() {
+        // 254mm by 127mm is exactly 10in by 5in
+        let metric = PhysicalRectangle::new(Length::mm(254), Length::mm(127));
+        let imperial = PhysicalRectangle::new(Length::inches(10), Length::inches(5));
+        for value in [1, 72, 96, 300, Dpi::MAX] {
+            assert_eq!(metric.cmp_area(&imperial, dpi(value)), Ordering::Equal);
+            assert_eq!(Length::mm(254).to_unit(Unit::Inches, dpi(value)), Ok(Length::inches(10)));
+            assert!(!metric.can_hold(&imperial, dpi(value)));
+        }
+    }
+
+    #[test]
+    fn pixels_depend_on_the_dpi() {
+        let photo = PhysicalRectangle::new(Length::inches(6), Length::inches(4));
+        let at_96 = PhysicalRectangle::new(Length::px(577), Length::px(385));
+        assert!(at_96.can_hold(&photo, dpi(96)));
+        assert_eq!(at_96.cmp_area(&photo, dpi(96)), Ordering::Greater);
+        assert!(!at_96.can_hold(&photo, dpi(300)));
+        assert_eq!(at_96.cmp_area(&photo, dpi(300)), Ordering::Less);
+        assert_eq!(photo.to_pixels(dpi(300)).map(|r| (r.width, r.height)), Ok((1800, 1200)));
+    }
+
+    #[test]
+    fn mixed_units_are_compared_side_by_side() {
+        // A4 paper against a 8.5in by 11in letter page
+        let a4 = PhysicalRectangle::new(Length::mm(210), Length::mm(297));
+        let letter = PhysicalRectangle::new(Length::px(816), Length::inches(11));
+        // Letter is wider (215.9mm) but shorter (279.4mm), so neither holds
+        // the other, and A4 is very slightly larger
+        assert!(!a4.can_hold(&letter, dpi(96)));
+        assert!(!letter.can_hold(&a4, dpi(96)));
+        assert_eq!(a4.cmp_area(&letter, dpi(96)), Ordering::Greater);
+    }
+
+    #[test]
+    fn inexact_conversions_are_errors() {
+        let inexact = Length::mm(1).to_unit(Unit::Pixels, dpi(96));
+        assert_eq!(inexact, Err(UnitError::Inexact { length: Length::mm(1), target: Unit::Pixels }));
+        assert_eq!(inexact.unwrap_err().to_string(), "1mm is not a whole number of pixels");
+    }
+
+    #[test]
+    fn the_longest_lengths_dont_overflow() {
+        let most = dpi(Dpi::MAX);
+        let huge = PhysicalRectangle::new(Length::inches(u32::MAX), Length::inches(u32::MAX));
+        let smaller = PhysicalRectangle::new(Length::mm(u32::MAX), Length::mm(u32::MAX));
+        assert!(huge.can_hold(&smaller, most));
+        assert_eq!(huge.cmp_area(&smaller, most), Ordering::Greater);
+    }
+}
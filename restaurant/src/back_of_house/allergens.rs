@@ -0,0 +1,490 @@
+// Allergens and dietary tags for everything on the menu.
+//
+// Rather than tagging each dish by hand (and forgetting to update the tags
+// when a recipe changes), each ingredient is tagged once, with the allergens
+// it contains and whether it's vegan or vegetarian. A dish's tags are then
+// worked out from its recipe in inventory.rs: it contains every allergen its
+// ingredients do, and is only vegan if all of them are. Options can bring in
+// ingredients of their own, so a salad can be vegan with one dressing and
+// not with another.
+//
+// Guests can search the menu with queries like "vegan and nut-free", and a
+// party can tell us their allergies when they arrive. Checking an order
+// against those allergies gives an error for anything that conflicts, so the
+// order can't go through until the item is changed or the guest has
+// confirmed they're happy to have it.
+//
+// An ingredient we have no tags for could contain anything, so it's treated
+// as an error rather than as being safe.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::back_of_house::inventory::Recipes;
+use crate::back_of_house::menu::Menu;
+use crate::front_of_house::hosting::Party;
+use crate::front_of_house::serving::OrderItem;
+
+// The allergens restaurants are usually asked about
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Allergen {
+    Celery,
+    Eggs,
+    Fish,
+    Gluten,
+    Milk,
+    Mustard,
+    Peanuts,
+    Sesame,
+    Shellfish,
+    Soy,
+    Sulphites,
+    TreeNuts,
+}
+
+impl Allergen {
+    // The allergens a word in a query or allergy list refers to. "Nuts"
+    // covers both tree nuts and peanuts, as that's what guests usually mean.
+    fn named(word: &str) -> Option<&'static [Allergen]> {
+        use Allergen::*;
+        let allergens: &'static [Allergen] = match word {
+            "celery" => &[Celery],
+            "egg" | "eggs" => &[Eggs],
+            "fish" => &[Fish],
+            "gluten" | "wheat" => &[Gluten],
+            "milk" | "dairy" | "lactose" => &[Milk],
+            "mustard" => &[Mustard],
+            "nut" | "nuts" => &[TreeNuts, Peanuts],
+            "peanut" | "peanuts" => &[Peanuts],
+            "sesame" => &[Sesame],
+            "shellfish" => &[Shellfish],
+            "soy" | "soya" => &[Soy],
+            "sulphites" | "sulfites" => &[Sulphites],
+            "tree-nut" | "tree-nuts" | "treenuts" => &[TreeNuts],
+            _ => return None,
+        };
+        Some(allergens)
+    }
+}
+
+impl fmt::Display for Allergen {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Allergen::Celery => "celery",
+            Allergen::Eggs => "eggs",
+            Allergen::Fish => "fish",
+            Allergen::Gluten => "gluten",
+            Allergen::Milk => "milk",
+            Allergen::Mustard => "mustard",
+            Allergen::Peanuts => "peanuts",
+            Allergen::Sesame => "sesame",
+            Allergen::Shellfish => "shellfish",
+            Allergen::Soy => "soy",
+            Allergen::Sulphites => "sulphites",
+            Allergen::TreeNuts => "tree nuts",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// How strict a diet a dish suits. Variants are listed strictest first, so a
+// dish's diet is the least strict of its ingredients'.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Diet {
+    Vegan,
+    Vegetarian,
+    // Contains meat or fish
+    Meat,
+}
+
+impl fmt::Display for Diet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Diet::Vegan => write!(f, "vegan"),
+            Diet::Vegetarian => write!(f, "vegetarian"),
+            Diet::Meat => write!(f, "contains meat or fish"),
+        }
+    }
+}
+
+// The tags for an ingredient, or for a dish made from several
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DietaryInfo {
+    pub diet: Diet,
+    pub allergens: BTreeSet<Allergen>,
+}
+
+impl DietaryInfo {
+    pub fn new(diet: Diet, allergens: &[Allergen]) -> DietaryInfo {
+        DietaryInfo {
+            diet,
+            allergens: allergens.iter().copied().collect(),
+        }
+    }
+
+    fn merge(&mut self, other: &DietaryInfo) {
+        self.diet = self.diet.max(other.diet);
+        self.allergens.extend(other.allergens.iter().copied());
+    }
+}
+
+impl fmt::Display for DietaryInfo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.diet)?;
+        if !self.allergens.is_empty() {
+            let names: Vec<String> = self.allergens.iter().map(|a| a.to_string()).collect();
+            write!(f, ", contains {}", names.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AllergenError {
+    NoRecipe(String),
+    UnknownIngredient(String),
+    // A word in a query or allergy list we don't recognise
+    UnknownTag(String),
+    // An item on an order contains something one of the party is allergic to
+    Conflict { guest: String, item: String, allergens: Vec<Allergen> },
+}
+
+impl fmt::Display for AllergenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AllergenError::NoRecipe(item) => {
+                write!(f, "there's no recipe for {}, so its allergens aren't known", item)
+            }
+            AllergenError::UnknownIngredient(name) => {
+                write!(f, "{} hasn't been tagged, so it could contain anything", name)
+            }
+            AllergenError::UnknownTag(word) => write!(f, "\"{}\" is not a diet or allergen", word),
+            AllergenError::Conflict { guest, item, allergens } => {
+                let names: Vec<String> = allergens.iter().map(|a| a.to_string()).collect();
+                write!(
+                    f,
+                    "{} contains {}, which {}'s party is allergic to. Change the item, or confirm with the guest first.",
+                    item,
+                    names.join(" and "),
+                    guest
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for AllergenError {}
+
+// Reads a list of allergies like "nuts, shellfish"
+pub fn parse_allergies(text: &str) -> Result<Vec<Allergen>, AllergenError> {
+    let mut allergies = BTreeSet::new();
+    for word in text.split([',', ' ']).filter(|w| !w.is_empty()) {
+        let word = word.to_lowercase();
+        let allergens = Allergen::named(&word).ok_or(AllergenError::UnknownTag(word))?;
+        allergies.extend(allergens.iter().copied());
+    }
+    Ok(allergies.into_iter().collect())
+}
+
+// What a guest is looking for, e.g. "vegan and nut-free"
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Query {
+    diet: Option<Diet>,
+    free_from: BTreeSet<Allergen>,
+}
+
+impl Query {
+    pub fn new() -> Query {
+        Query::default()
+    }
+
+    pub fn diet(mut self, diet: Diet) -> Query {
+        self.diet = Some(diet);
+        self
+    }
+
+    pub fn free_from(mut self, allergen: Allergen) -> Query {
+        self.free_from.insert(allergen);
+        self
+    }
+
+    pub fn matches(&self, info: &DietaryInfo) -> bool {
+        self.diet.is_none_or(|diet| info.diet <= diet) && info.allergens.is_disjoint(&self.free_from)
+    }
+}
+
+// Reads queries made of "vegan", "vegetarian" and "<allergen>-free", joined
+// with "and" or commas
+impl FromStr for Query {
+    type Err = AllergenError;
+
+    fn from_str(s: &str) -> Result<Query, AllergenError> {
+        let mut query = Query::new();
+        let text = s.to_lowercase().replace(',', " ");
+        for word in text.split_whitespace().filter(|&w| w != "and") {
+            match word {
+                "vegan" => query = query.diet(Diet::Vegan),
+                // "vegan and vegetarian" is still vegan
+                "vegetarian" => query.diet = Some(query.diet.map_or(Diet::Vegetarian, |d| d.min(Diet::Vegetarian))),
+                _ => {
+                    let allergens = word
+                        .strip_suffix("-free")
+                        .and_then(Allergen::named)
+                        .ok_or_else(|| AllergenError::UnknownTag(String::from(word)))?;
+                    query.free_from.extend(allergens.iter().copied());
+                }
+            }
+        }
+        Ok(query)
+    }
+}
+
+// A dish which suits a query, as long as the options in `avoid` aren't
+// chosen
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MenuMatch {
+    pub id: String,
+    pub name: String,
+    pub avoid: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AllergenGuide {
+    recipes: Recipes,
+    ingredients: HashMap<String, DietaryInfo>,
+}
+
+impl AllergenGuide {
+    pub fn new(recipes: Recipes) -> AllergenGuide {
+        AllergenGuide {
+            recipes,
+            ingredients: HashMap::new(),
+        }
+    }
+
+    pub fn ingredient(mut self, name: &str, diet: Diet, allergens: &[Allergen]) -> AllergenGuide {
+        self.ingredients
+            .insert(String::from(name), DietaryInfo::new(diet, allergens));
+        self
+    }
+
+    // Tags for every ingredient in the house recipes
+    pub fn house() -> AllergenGuide {
+        use Allergen::*;
+        AllergenGuide::new(Recipes::house())
+            .ingredient("eggs", Diet::Vegetarian, &[Eggs])
+            .ingredient("butter", Diet::Vegetarian, &[Milk])
+            .ingredient("seasonal fruit", Diet::Vegan, &[])
+            .ingredient("rye bread", Diet::Vegan, &[Gluten])
+            .ingredient("wheat bread", Diet::Vegan, &[Gluten])
+            .ingredient("white bread", Diet::Vegan, &[Gluten, Soy])
+            .ingredient("sourdough", Diet::Vegan, &[Gluten])
+            // Soup of the day is always a vegetable soup finished with cream
+            .ingredient("soup", Diet::Vegetarian, &[Celery, Milk])
+            .ingredient("bread rolls", Diet::Vegan, &[Gluten, Sesame])
+            .ingredient("salad leaves", Diet::Vegan, &[])
+            .ingredient("tomatoes", Diet::Vegan, &[])
+            .ingredient("vinaigrette", Diet::Vegan, &[Mustard, Sulphites])
+            .ingredient("ranch dressing", Diet::Vegetarian, &[Milk, Eggs])
+            .ingredient("walnut oil", Diet::Vegan, &[TreeNuts])
+    }
+
+    // The tags for one of an item with the given options chosen
+    pub fn info(&self, menu_id: &str, chosen: &[String]) -> Result<DietaryInfo, AllergenError> {
+        let recipe = self
+            .recipes
+            .get(menu_id)
+            .ok_or_else(|| AllergenError::NoRecipe(String::from(menu_id)))?;
+        let mut info = DietaryInfo::new(Diet::Vegan, &[]);
+        for (ingredient, _) in recipe.needs(chosen) {
+            let tags = self
+                .ingredients
+                .get(&ingredient)
+                .ok_or(AllergenError::UnknownIngredient(ingredient))?;
+            info.merge(tags);
+        }
+        Ok(info)
+    }
+
+    // Everything on the menu that can be ordered in a way that suits the
+    // query. Items and options which have run out are left out.
+    pub fn matching(&self, menu: &Menu, query: &Query) -> Result<Vec<MenuMatch>, AllergenError> {
+        let mut matches = Vec::new();
+        for item in menu.items().filter(|item| item.available) {
+            if !query.matches(&self.info(&item.id, &[])?) {
+                continue;
+            }
+            let mut avoid = Vec::new();
+            let mut possible = true;
+            for group in &item.modifiers {
+                let mut suitable = 0;
+                for option in &group.options {
+                    if query.matches(&self.info(&item.id, std::slice::from_ref(&option.name))?) {
+                        suitable += usize::from(option.available);
+                    } else {
+                        avoid.push(option.name.clone());
+                    }
+                }
+                possible &= !group.required || suitable > 0;
+            }
+            if possible {
+                matches.push(MenuMatch {
+                    id: item.id.clone(),
+                    name: item.name.clone(),
+                    avoid,
+                });
+            }
+        }
+        Ok(matches)
+    }
+
+    // Checks an order against the party's allergies. The first item which
+    // contains something they're allergic to blocks the order.
+    pub fn check(&self, party: &Party, items: &[OrderItem]) -> Result<(), AllergenError> {
+        if party.allergies.is_empty() {
+            return Ok(());
+        }
+        for item in items {
+            let info = self.info(&item.menu_id, &item.modifiers)?;
+            let allergens: Vec<Allergen> = party
+                .allergies
+                .iter()
+                .filter(|a| info.allergens.contains(a))
+                .copied()
+                .collect();
+            if !allergens.is_empty() {
+                return Err(AllergenError::Conflict {
+                    guest: party.name.clone(),
+                    item: item.name.clone(),
+                    allergens,
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::Time;
+    use Allergen::*;
+
+    fn info(menu_id: &str, chosen: &[&str]) -> DietaryInfo {
+        let chosen: Vec<String> = chosen.iter().map(|c| String::from(*c)).collect();
+        AllergenGuide::house().info(menu_id, &chosen).unwrap()
+    }
+
+    #[test]
+    fn a_dish_has_the_tags_of_everything_in_it() {
+        assert_eq!(info("breakfast", &["Rye"]), DietaryInfo::new(Diet::Vegetarian, &[Eggs, Gluten, Milk]));
+        // White bread brings soy with it
+        assert_eq!(info("breakfast", &["White"]), DietaryInfo::new(Diet::Vegetarian, &[Eggs, Gluten, Milk, Soy]));
+        assert_eq!(info("soup", &[]), DietaryInfo::new(Diet::Vegetarian, &[Celery, Milk]));
+        assert_eq!(info("soup", &["Crusty roll"]).to_string(), "vegetarian, contains celery, gluten, milk, sesame");
+    }
+
+    #[test]
+    fn options_can_change_what_a_dish_is() {
+        assert_eq!(info("salad", &[]), DietaryInfo::new(Diet::Vegan, &[]));
+        assert_eq!(info("salad", &["Vinaigrette"]), DietaryInfo::new(Diet::Vegan, &[Mustard, Sulphites]));
+        assert_eq!(info("salad", &["Ranch"]), DietaryInfo::new(Diet::Vegetarian, &[Eggs, Milk]));
+        assert_eq!(info("salad", &["Walnut oil"]), DietaryInfo::new(Diet::Vegan, &[TreeNuts]));
+    }
+
+    #[test]
+    fn untagged_ingredients_are_not_assumed_safe() {
+        let guide = AllergenGuide::new(Recipes::house()).ingredient("soup", Diet::Vegetarian, &[Celery, Milk]);
+        assert!(guide.info("soup", &[]).is_ok());
+        assert_eq!(
+            guide.info("soup", &[String::from("Crusty roll")]),
+            Err(AllergenError::UnknownIngredient(String::from("bread rolls")))
+        );
+        assert_eq!(guide.info("curry", &[]), Err(AllergenError::NoRecipe(String::from("curry"))));
+    }
+
+    #[test]
+    fn queries_combine_a_diet_and_allergens() {
+        let query: Query = "Vegan and nut-free".parse().unwrap();
+        assert_eq!(query, Query::new().diet(Diet::Vegan).free_from(TreeNuts).free_from(Peanuts));
+        let query: Query = "vegetarian, gluten-free".parse().unwrap();
+        assert_eq!(query, Query::new().diet(Diet::Vegetarian).free_from(Gluten));
+        let query: Query = "vegetarian and vegan".parse().unwrap();
+        assert_eq!(query, Query::new().diet(Diet::Vegan));
+        assert_eq!("spicy-free".parse::<Query>(), Err(AllergenError::UnknownTag(String::from("spicy-free"))));
+        assert_eq!("pescatarian".parse::<Query>(), Err(AllergenError::UnknownTag(String::from("pescatarian"))));
+    }
+
+    #[test]
+    fn searching_the_menu_says_which_options_to_avoid() {
+        let guide = AllergenGuide::house();
+        let mut menu = Menu::house();
+        let query = "vegan and nut-free".parse().unwrap();
+        assert_eq!(
+            guide.matching(&menu, &query).unwrap(),
+            [MenuMatch {
+                id: String::from("salad"),
+                name: String::from("Garden salad"),
+                avoid: vec![String::from("Ranch"), String::from("Walnut oil")],
+            }]
+        );
+
+        // Without the one dressing that suits, there's no way to have it
+        menu.set_option_available("salad", "Vinaigrette", false).unwrap();
+        assert!(guide.matching(&menu, &query).unwrap().is_empty());
+
+        let query = "vegetarian and milk-free".parse().unwrap();
+        let ids: Vec<String> = guide.matching(&Menu::house(), &query).unwrap().into_iter().map(|m| m.id).collect();
+        assert_eq!(ids, ["salad"]);
+    }
+
+    #[test]
+    fn allergies_are_read_from_a_list() {
+        assert_eq!(parse_allergies("Nuts, shellfish"), Ok(vec![Peanuts, Shellfish, TreeNuts]));
+        assert_eq!(parse_allergies("dairy lactose"), Ok(vec![Milk]));
+        assert_eq!(parse_allergies(""), Ok(Vec::new()));
+        assert_eq!(parse_allergies("nuts, gravel"), Err(AllergenError::UnknownTag(String::from("gravel"))));
+    }
+
+    #[test]
+    fn an_item_the_party_is_allergic_to_blocks_the_order() {
+        let guide = AllergenGuide::house();
+        let menu = Menu::house();
+        let mut party = Party::new("Lee", 2, Time::hm(19, 0));
+        party.allergies = parse_allergies("nuts").unwrap();
+        let items = [
+            OrderItem::from_menu(&menu, "soup", &[], 1).unwrap(),
+            OrderItem::from_menu(&menu, "salad", &["Walnut oil"], 1).unwrap(),
+        ];
+
+        let error = guide.check(&party, &items).unwrap_err();
+        assert_eq!(
+            error,
+            AllergenError::Conflict {
+                guest: String::from("Lee"),
+                item: String::from("Garden salad"),
+                allergens: vec![TreeNuts],
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "Garden salad contains tree nuts, which Lee's party is allergic to. \
+             Change the item, or confirm with the guest first."
+        );
+
+        // The same salad with another dressing is fine
+        let items = [OrderItem::from_menu(&menu, "salad", &["Vinaigrette"], 1).unwrap()];
+        assert_eq!(guide.check(&party, &items), Ok(()));
+
+        // Every allergen the item has in common with the party is listed
+        party.allergies = parse_allergies("eggs, milk").unwrap();
+        let items = [OrderItem::from_menu(&menu, "breakfast", &["Rye"], 1).unwrap()];
+        match guide.check(&party, &items) {
+            Err(AllergenError::Conflict { allergens, .. }) => assert_eq!(allergens, [Eggs, Milk]),
+            other => panic!("expected a conflict, got {:?}", other),
+        }
+    }
+}
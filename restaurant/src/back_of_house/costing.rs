@@ -0,0 +1,466 @@
+// What each dish costs us to make, and how much of its price we keep.
+//
+// Every ingredient has a price for the pack it's bought in (a tray of 30
+// eggs, a kilo of butter), and the recipes in inventory.rs say how much of
+// each a dish uses. Adding those up gives the plate cost. From that:
+//
+//   food cost  the plate cost as a percentage of the price
+//   margin     the price less the plate cost
+//
+// Prices on the menu are before tax, so that's what the costs are compared
+// against. Percentages are given in basis points, as they are for billing.
+//
+// A slice of bread costs a fraction of a cent, so costs are added up in
+// thousandths of a cent and only rounded to the nearest cent at the end.
+// For an order, each line is rounded on its own and the total is rounded
+// from the exact costs, so the lines can add up to a cent or so more or
+// less than the total.
+//
+// The margin report lists every dish as it can be served (one line for
+// each choice of its required options) and every optional extra on its
+// own, and flags anything whose margin is below a target. A free option
+// which costs us something, like butter on the side, is always flagged.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::back_of_house::inventory::Recipes;
+use crate::back_of_house::menu::{Menu, MenuError, MenuItem};
+use crate::front_of_house::serving::OrderItem;
+use crate::money::Money;
+
+// What we pay for `pack` units of an ingredient (in the unit it's stocked in)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IngredientCost {
+    pub pack: u32,
+    pub price: Money,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CostingError {
+    NoRecipe(String),
+    UnknownIngredient(String),
+    Menu(MenuError),
+}
+
+impl fmt::Display for CostingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CostingError::NoRecipe(id) => write!(f, "there is no recipe for \"{}\"", id),
+            CostingError::UnknownIngredient(name) => {
+                write!(f, "there's no price for {}, so dishes using it can't be costed", name)
+            }
+            CostingError::Menu(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for CostingError {}
+
+impl From<MenuError> for CostingError {
+    fn from(e: MenuError) -> CostingError {
+        CostingError::Menu(e)
+    }
+}
+
+// A percentage in basis points, to one decimal place, like "17.8%"
+fn percent(basis_points: i64) -> String {
+    let tenths = (basis_points + basis_points.signum() * 5) / 10;
+    let sign = if tenths < 0 { "-" } else { "" };
+    format!("{}{}.{}%", sign, tenths.abs() / 10, tenths.abs() % 10)
+}
+
+// The price and plate cost of something we sell, whether that's one dish,
+// an extra, or a whole order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CostLine {
+    pub name: String,
+    pub price: Money,
+    pub cost: Money,
+}
+
+impl CostLine {
+    pub fn margin(&self) -> Money {
+        self.price - self.cost
+    }
+
+    // The plate cost as a share of the price, in basis points. There's no
+    // such thing for something we give away.
+    pub fn food_cost(&self) -> Option<i64> {
+        if !self.price.is_positive() {
+            return None;
+        }
+        let scaled = i128::from(self.cost.cents()) * 10_000;
+        let price = i128::from(self.price.cents());
+        Some(((scaled + price / 2) / price) as i64)
+    }
+
+    // The margin as a share of the price, in basis points
+    pub fn margin_percent(&self) -> Option<i64> {
+        self.food_cost().map(|food_cost| 10_000 - food_cost)
+    }
+
+    // Whether we keep less than `target` basis points of the price
+    pub fn below(&self, target: u32) -> bool {
+        match self.margin_percent() {
+            Some(margin) => margin < i64::from(target),
+            None => self.cost.is_positive(),
+        }
+    }
+}
+
+impl fmt::Display for CostLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let food_cost = self.food_cost().map_or_else(|| String::from("-"), percent);
+        write!(
+            f,
+            "{:<36}{:>8}{:>8}{:>10}{:>8}",
+            self.name,
+            self.price,
+            self.cost,
+            food_cost,
+            self.margin()
+        )
+    }
+}
+
+fn header(f: &mut fmt::Formatter) -> fmt::Result {
+    writeln!(f, "{:<36}{:>8}{:>8}{:>10}{:>8}", "", "Price", "Cost", "Food cost", "Margin")
+}
+
+// What an order cost to make, item by item. Prices are what the guests
+// were charged, so an item's options and quantity are included.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OrderCost {
+    pub lines: Vec<CostLine>,
+    // The cost of every line before rounding, in thousandths of a cent
+    exact_cost: i128,
+}
+
+impl OrderCost {
+    pub fn total(&self) -> CostLine {
+        CostLine {
+            name: String::from("Total"),
+            price: self.lines.iter().map(|l| l.price).sum(),
+            cost: Costing::to_money(self.exact_cost),
+        }
+    }
+}
+
+impl fmt::Display for OrderCost {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        header(f)?;
+        for line in &self.lines {
+            writeln!(f, "{}", line)?;
+        }
+        writeln!(f, "{}", self.total())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MarginReport {
+    // The margin every line should reach, in basis points
+    pub target: u32,
+    pub lines: Vec<CostLine>,
+}
+
+impl MarginReport {
+    // Everything that falls short of the target
+    pub fn flagged(&self) -> impl Iterator<Item = &CostLine> {
+        self.lines.iter().filter(move |l| l.below(self.target))
+    }
+}
+
+impl fmt::Display for MarginReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Margins (target {})", percent(i64::from(self.target)))?;
+        header(f)?;
+        for line in &self.lines {
+            let flag = if line.below(self.target) { "  below target" } else { "" };
+            writeln!(f, "{}{}", line, flag)?;
+        }
+        Ok(())
+    }
+}
+
+// Every way of filling in an item's required options, one choice from each
+// required group
+fn required_choices(item: &MenuItem) -> Vec<Vec<String>> {
+    let mut choices = vec![Vec::new()];
+    for group in item.modifiers.iter().filter(|g| g.required) {
+        choices = choices
+            .iter()
+            .flat_map(|chosen| {
+                group.options.iter().map(move |option| {
+                    let mut chosen = chosen.clone();
+                    chosen.push(option.name.clone());
+                    chosen
+                })
+            })
+            .collect();
+    }
+    choices
+}
+
+#[derive(Debug, Clone)]
+pub struct Costing {
+    recipes: Recipes,
+    ingredients: HashMap<String, IngredientCost>,
+}
+
+impl Costing {
+    pub fn new(recipes: Recipes) -> Costing {
+        Costing {
+            recipes,
+            ingredients: HashMap::new(),
+        }
+    }
+
+    pub fn ingredient(mut self, name: &str, pack: u32, price: Money) -> Costing {
+        assert!(pack > 0, "an ingredient has to come in packs of at least one");
+        self.ingredients
+            .insert(String::from(name), IngredientCost { pack, price });
+        self
+    }
+
+    // What we pay our suppliers for everything in the house recipes
+    pub fn house() -> Costing {
+        let cents = Money::from_cents;
+        Costing::new(Recipes::house())
+            .ingredient("eggs", 30, cents(720))
+            .ingredient("butter", 1000, cents(900))
+            .ingredient("seasonal fruit", 10, cents(600))
+            .ingredient("rye bread", 20, cents(340))
+            .ingredient("wheat bread", 20, cents(260))
+            .ingredient("white bread", 20, cents(200))
+            .ingredient("sourdough", 16, cents(480))
+            .ingredient("soup", 1000, cents(320))
+            .ingredient("bread rolls", 12, cents(420))
+            .ingredient("salad leaves", 1000, cents(950))
+            .ingredient("tomatoes", 6, cents(210))
+            .ingredient("vinaigrette", 500, cents(400))
+            .ingredient("ranch dressing", 500, cents(500))
+            .ingredient("walnut oil", 250, cents(875))
+    }
+
+    // What it costs to make the ingredients in `needs`, in thousandths of a
+    // cent
+    fn cost_of(&self, needs: Vec<(String, u32)>) -> Result<i128, CostingError> {
+        let mut total = 0;
+        for (ingredient, quantity) in needs {
+            let cost = self
+                .ingredients
+                .get(&ingredient)
+                .ok_or(CostingError::UnknownIngredient(ingredient))?;
+            let scaled = i128::from(cost.price.cents()) * i128::from(quantity) * 1_000;
+            let pack = i128::from(cost.pack);
+            total += (scaled + pack / 2) / pack;
+        }
+        Ok(total)
+    }
+
+    fn to_money(thousandths: i128) -> Money {
+        Money::from_cents(((thousandths + 500) / 1_000) as i64)
+    }
+
+    fn exact_plate_cost(&self, menu_id: &str, chosen: &[String]) -> Result<i128, CostingError> {
+        let recipe = self
+            .recipes
+            .get(menu_id)
+            .ok_or_else(|| CostingError::NoRecipe(String::from(menu_id)))?;
+        self.cost_of(recipe.needs(chosen))
+    }
+
+    // The cost of one of an item with the given options chosen
+    pub fn plate_cost(&self, menu_id: &str, chosen: &[String]) -> Result<Money, CostingError> {
+        Ok(Costing::to_money(self.exact_plate_cost(menu_id, chosen)?))
+    }
+
+    // One of an item as it's priced on the menu. Whether it's available
+    // right now doesn't matter here, unlike when it's ordered.
+    pub fn dish(&self, menu: &Menu, menu_id: &str, chosen: &[String]) -> Result<CostLine, CostingError> {
+        let item = menu.item(menu_id)?;
+        let mut price = item.price;
+        for option in item.choices(chosen)? {
+            price += option.price;
+        }
+        let name = if chosen.is_empty() {
+            item.name.clone()
+        } else {
+            format!("{} ({})", item.name, chosen.join(", "))
+        };
+        Ok(CostLine {
+            name,
+            price,
+            cost: self.plate_cost(menu_id, chosen)?,
+        })
+    }
+
+    pub fn order(&self, items: &[OrderItem]) -> Result<OrderCost, CostingError> {
+        let mut lines = Vec::new();
+        let mut exact_cost = 0;
+        for item in items {
+            let cost = self.exact_plate_cost(&item.menu_id, &item.modifiers)? * i128::from(item.quantity);
+            exact_cost += cost;
+            let name = if item.modifiers.is_empty() {
+                format!("{} x {}", item.quantity, item.name)
+            } else {
+                format!("{} x {} ({})", item.quantity, item.name, item.modifiers.join(", "))
+            };
+            lines.push(CostLine {
+                name,
+                price: item.total(),
+                cost: Costing::to_money(cost),
+            });
+        }
+        Ok(OrderCost { lines, exact_cost })
+    }
+
+    // Every dish and extra on the menu, checked against a target margin in
+    // basis points (7500 means we want to keep 75% of the price)
+    pub fn report(&self, menu: &Menu, target: u32) -> Result<MarginReport, CostingError> {
+        let mut lines = Vec::new();
+        for item in menu.items() {
+            let recipe = self
+                .recipes
+                .get(&item.id)
+                .ok_or_else(|| CostingError::NoRecipe(item.id.clone()))?;
+            for chosen in required_choices(item) {
+                lines.push(self.dish(menu, &item.id, &chosen)?);
+            }
+
+            // An extra costs whatever its ingredients add to the plate
+            for group in item.modifiers.iter().filter(|g| !g.required) {
+                for option in &group.options {
+                    let with = recipe.needs(std::slice::from_ref(&option.name));
+                    let extra = self.cost_of(with)? - self.cost_of(recipe.needs(&[]))?;
+                    lines.push(CostLine {
+                        name: format!("{} + {}", item.name, option.name),
+                        price: option.price,
+                        cost: Costing::to_money(extra),
+                    });
+                }
+            }
+        }
+        Ok(MarginReport { target, lines })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chosen(options: &[&str]) -> Vec<String> {
+        options.iter().map(|o| String::from(*o)).collect()
+    }
+
+    fn dish(menu_id: &str, options: &[&str]) -> CostLine {
+        Costing::house().dish(&Menu::house(), menu_id, &chosen(options)).unwrap()
+    }
+
+    #[test]
+    fn a_plate_costs_what_its_ingredients_do() {
+        let costing = Costing::house();
+        // 300ml of soup at 3.20 a litre
+        assert_eq!(costing.plate_cost("soup", &[]), Ok(Money::from_cents(96)));
+        // Plus a roll from a pack of 12 for 4.20
+        assert_eq!(costing.plate_cost("soup", &chosen(&["Crusty roll"])), Ok(Money::from_cents(131)));
+        // Eggs 0.48, butter 0.09, fruit 0.60 and rye 0.34
+        assert_eq!(costing.plate_cost("breakfast", &chosen(&["Rye"])), Ok(Money::from_cents(151)));
+        // 15ml of walnut oil is 52.5 cents, which is only rounded at the end
+        assert_eq!(costing.plate_cost("salad", &chosen(&["Walnut oil"])), Ok(Money::from_cents(164)));
+    }
+
+    #[test]
+    fn food_cost_and_margin_are_worked_out_from_the_price() {
+        let breakfast = dish("breakfast", &["Rye"]);
+        assert_eq!(breakfast.name, "Breakfast (Rye)");
+        assert_eq!(breakfast.price, Money::from_cents(850));
+        assert_eq!(breakfast.margin(), Money::from_cents(699));
+        // 1.51 of 8.50 is 17.76%
+        assert_eq!(breakfast.food_cost(), Some(1776));
+        assert_eq!(breakfast.margin_percent(), Some(8224));
+        assert_eq!(
+            breakfast.to_string(),
+            "Breakfast (Rye)                         8.50    1.51     17.8%    6.99"
+        );
+
+        // Options add to the price as well as the cost
+        let sourdough = dish("breakfast", &["Sourdough"]);
+        assert_eq!((sourdough.price, sourdough.cost), (Money::from_cents(900), Money::from_cents(177)));
+    }
+
+    #[test]
+    fn something_given_away_has_no_food_cost() {
+        let free = CostLine {
+            name: String::from("Butter on the side"),
+            price: Money::ZERO,
+            cost: Money::from_cents(14),
+        };
+        assert_eq!(free.food_cost(), None);
+        assert_eq!(free.margin(), Money::from_cents(-14));
+        assert!(free.below(0));
+
+        let nothing = CostLine { cost: Money::ZERO, ..free };
+        assert!(!nothing.below(7500));
+    }
+
+    #[test]
+    fn an_order_is_costed_line_by_line() {
+        let menu = Menu::house();
+        let items = [
+            OrderItem::from_menu(&menu, "salad", &["Walnut oil"], 3).unwrap(),
+            OrderItem::from_menu(&menu, "breakfast", &["Rye", "Butter on the side"], 1).unwrap(),
+        ];
+        let cost = Costing::house().order(&items).unwrap();
+        assert_eq!(cost.lines[0].name, "3 x Garden salad (Walnut oil)");
+        assert_eq!(cost.lines[0].price, Money::from_cents(1920));
+        // Three salads at 1.635 each, rather than three at 1.64
+        assert_eq!(cost.lines[0].cost, Money::from_cents(491));
+        assert_eq!(cost.lines[1].name, "1 x Breakfast (Rye, Butter on the side)");
+        assert_eq!(cost.lines[1].cost, Money::from_cents(165));
+
+        // 4.905 and 1.645 make 6.55 exactly
+        let total = cost.total();
+        assert_eq!(total.price, Money::from_cents(2770));
+        assert_eq!(total.cost, Money::from_cents(655));
+        assert_eq!(total.margin(), Money::from_cents(2115));
+        assert_eq!(total.food_cost(), Some(2365));
+    }
+
+    #[test]
+    fn the_report_flags_everything_below_the_target() {
+        let report = Costing::house().report(&Menu::house(), 7500).unwrap();
+        // Four toasts, the soup, three dressings, and four extras
+        assert_eq!(report.lines.len(), 12);
+        let flagged: Vec<&str> = report.flagged().map(|l| l.name.as_str()).collect();
+        assert_eq!(
+            flagged,
+            [
+                "Breakfast + Extra fruit",
+                "Breakfast + Butter on the side",
+                "Soup of the day + Crusty roll",
+                "Garden salad (Walnut oil)",
+            ]
+        );
+        assert!(report.to_string().starts_with("Margins (target 75.0%)\n"));
+        assert!(report
+            .to_string()
+            .contains("Garden salad (Walnut oil)               6.40    1.64     25.6%    4.76  below target"));
+
+        // A lower target lets the salad through
+        let report = Costing::house().report(&Menu::house(), 7400).unwrap();
+        assert_eq!(report.flagged().count(), 3);
+    }
+
+    #[test]
+    fn dishes_without_a_recipe_or_prices_cant_be_costed() {
+        let costing = Costing::new(Recipes::house()).ingredient("soup", 1000, Money::from_cents(320));
+        assert_eq!(costing.plate_cost("soup", &[]), Ok(Money::from_cents(96)));
+        assert_eq!(
+            costing.plate_cost("soup", &chosen(&["Crusty roll"])),
+            Err(CostingError::UnknownIngredient(String::from("bread rolls")))
+        );
+        assert_eq!(costing.plate_cost("curry", &[]), Err(CostingError::NoRecipe(String::from("curry"))));
+    }
+}
@@ -0,0 +1,547 @@
+// What's in the stores, and what each dish takes out of them.
+//
+// Every menu item has a recipe: the ingredients one of it uses, plus any
+// extra ingredients needed for particular options (rye toast needs rye
+// bread, a crusty roll needs a roll). When the kitchen cooks an order, the
+// ingredients for every item are taken out of stock in one go. If there
+// isn't enough of something, nothing is taken and the order is refused.
+//
+// Each ingredient has a low-stock threshold. Alerts are raised as stock
+// drops to the threshold, and again when it runs out, and are kept until
+// someone takes them. `apply_to` then marks anything the kitchen can no
+// longer make as unavailable on the menu, so it can't be ordered.
+//
+// Stock levels are saved to a JSON file so the counts carry over from one
+// day to the next. Recipes are part of the program rather than the file.
+//
+// Quantities are whole numbers in each ingredient's own unit. The breakfast
+// fruit changes with the seasons, so it's counted in portions of whatever
+// is in season rather than by the fruit.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::back_of_house::menu::Menu;
+use crate::front_of_house::serving::OrderItem;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Unit {
+    Grams,
+    Millilitres,
+    Pieces,
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Unit::Grams => write!(f, "g"),
+            Unit::Millilitres => write!(f, "ml"),
+            Unit::Pieces => write!(f, " pcs"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recipe {
+    ingredients: Vec<(String, u32)>,
+    // (option, ingredient, quantity) for ingredients only some options need
+    options: Vec<(String, String, u32)>,
+}
+
+impl Recipe {
+    pub fn new() -> Recipe {
+        Recipe::default()
+    }
+
+    pub fn uses(mut self, ingredient: &str, quantity: u32) -> Recipe {
+        self.ingredients.push((String::from(ingredient), quantity));
+        self
+    }
+
+    pub fn with_option(mut self, option: &str, ingredient: &str, quantity: u32) -> Recipe {
+        self.options
+            .push((String::from(option), String::from(ingredient), quantity));
+        self
+    }
+
+    // What one of the item takes with the given options chosen
+    pub(crate) fn needs(&self, chosen: &[String]) -> Vec<(String, u32)> {
+        let extras = self
+            .options
+            .iter()
+            .filter(|(option, _, _)| chosen.contains(option))
+            .map(|(_, ingredient, quantity)| (ingredient.clone(), *quantity));
+        self.ingredients.iter().cloned().chain(extras).collect()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Recipes {
+    by_item: HashMap<String, Recipe>,
+}
+
+impl Recipes {
+    pub fn new() -> Recipes {
+        Recipes::default()
+    }
+
+    pub fn recipe(mut self, menu_id: &str, recipe: Recipe) -> Recipes {
+        self.by_item.insert(String::from(menu_id), recipe);
+        self
+    }
+
+    // Recipes for everything on the house menu
+    pub fn house() -> Recipes {
+        Recipes::new()
+            .recipe(
+                "breakfast",
+                Recipe::new()
+                    .uses("eggs", 2)
+                    .uses("butter", 10)
+                    .uses("seasonal fruit", 1)
+                    .with_option("Rye", "rye bread", 2)
+                    .with_option("Wheat", "wheat bread", 2)
+                    .with_option("White", "white bread", 2)
+                    .with_option("Sourdough", "sourdough", 2)
+                    .with_option("Extra fruit", "seasonal fruit", 1)
+                    .with_option("Butter on the side", "butter", 15),
+            )
+            .recipe(
+                "soup",
+                Recipe::new()
+                    .uses("soup", 300)
+                    .with_option("Crusty roll", "bread rolls", 1),
+            )
+            .recipe(
+                "salad",
+                Recipe::new()
+                    .uses("salad leaves", 80)
+                    .uses("tomatoes", 1)
+                    .with_option("Vinaigrette", "vinaigrette", 30)
+                    .with_option("Ranch", "ranch dressing", 30)
+                    .with_option("Walnut oil", "walnut oil", 15),
+            )
+    }
+
+    pub fn get(&self, menu_id: &str) -> Option<&Recipe> {
+        self.by_item.get(menu_id)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StockLevel {
+    pub unit: Unit,
+    pub on_hand: u32,
+    // An alert is raised once stock drops to this level
+    pub low_at: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StockAlert {
+    Low { ingredient: String, on_hand: u32, unit: Unit },
+    Out { ingredient: String },
+}
+
+impl fmt::Display for StockAlert {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StockAlert::Low { ingredient, on_hand, unit } => {
+                write!(f, "running low on {}: {}{} left", ingredient, on_hand, unit)
+            }
+            StockAlert::Out { ingredient } => write!(f, "run out of {}", ingredient),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InventoryError {
+    Io(String),
+    Parse(String),
+    NoRecipe(String),
+    UnknownIngredient(String),
+    OutOfStock { ingredient: String, needed: u32, on_hand: u32 },
+    // An order needs more of an ingredient than can be counted
+    TooMuch(String),
+}
+
+impl fmt::Display for InventoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InventoryError::Io(message) => write!(f, "could not read or write stock: {}", message),
+            InventoryError::Parse(message) => write!(f, "stock file is not valid: {}", message),
+            InventoryError::NoRecipe(id) => write!(f, "there is no recipe for \"{}\"", id),
+            InventoryError::UnknownIngredient(name) => write!(f, "{} isn't kept in stock", name),
+            InventoryError::OutOfStock { ingredient, needed, on_hand } => write!(
+                f,
+                "not enough {}: {} needed but only {} left",
+                ingredient, needed, on_hand
+            ),
+            InventoryError::TooMuch(name) => write!(f, "more {} is needed than can be counted", name),
+        }
+    }
+}
+
+impl std::error::Error for InventoryError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inventory {
+    recipes: Recipes,
+    // Sorted by name, so the saved file is easy to read and compare
+    stock: BTreeMap<String, StockLevel>,
+    alerts: Vec<StockAlert>,
+}
+
+impl Inventory {
+    pub fn new(recipes: Recipes) -> Inventory {
+        Inventory {
+            recipes,
+            stock: BTreeMap::new(),
+            alerts: Vec::new(),
+        }
+    }
+
+    // The house recipes, with a morning's delivery in the stores
+    pub fn house() -> Inventory {
+        Inventory::new(Recipes::house())
+            .stock("eggs", Unit::Pieces, 60, 12)
+            .stock("butter", Unit::Grams, 1000, 200)
+            .stock("seasonal fruit", Unit::Pieces, 30, 6)
+            .stock("rye bread", Unit::Pieces, 24, 6)
+            .stock("wheat bread", Unit::Pieces, 24, 6)
+            .stock("white bread", Unit::Pieces, 24, 6)
+            .stock("sourdough", Unit::Pieces, 16, 4)
+            .stock("soup", Unit::Millilitres, 6000, 1200)
+            .stock("bread rolls", Unit::Pieces, 20, 5)
+            .stock("salad leaves", Unit::Grams, 2000, 400)
+            .stock("tomatoes", Unit::Pieces, 30, 6)
+            .stock("vinaigrette", Unit::Millilitres, 500, 100)
+            .stock("ranch dressing", Unit::Millilitres, 500, 100)
+            .stock("walnut oil", Unit::Millilitres, 250, 50)
+    }
+
+    pub fn stock(mut self, ingredient: &str, unit: Unit, on_hand: u32, low_at: u32) -> Inventory {
+        self.stock
+            .insert(String::from(ingredient), StockLevel { unit, on_hand, low_at });
+        self
+    }
+
+    pub fn level(&self, ingredient: &str) -> Option<StockLevel> {
+        self.stock.get(ingredient).copied()
+    }
+
+    pub fn levels(&self) -> impl Iterator<Item = (&str, StockLevel)> {
+        self.stock.iter().map(|(name, level)| (name.as_str(), *level))
+    }
+
+    fn level_mut(&mut self, ingredient: &str) -> Result<&mut StockLevel, InventoryError> {
+        self.stock
+            .get_mut(ingredient)
+            .ok_or_else(|| InventoryError::UnknownIngredient(String::from(ingredient)))
+    }
+
+    pub fn restock(&mut self, ingredient: &str, quantity: u32) -> Result<(), InventoryError> {
+        let level = self.level_mut(ingredient)?;
+        level.on_hand = level.on_hand.saturating_add(quantity);
+        Ok(())
+    }
+
+    pub fn set_threshold(&mut self, ingredient: &str, low_at: u32) -> Result<(), InventoryError> {
+        self.level_mut(ingredient)?.low_at = low_at;
+        Ok(())
+    }
+
+    // Everything needed to make these items, with each ingredient added up
+    fn needs(&self, items: &[OrderItem]) -> Result<BTreeMap<String, u32>, InventoryError> {
+        let mut totals = BTreeMap::new();
+        for item in items {
+            let recipe = self
+                .recipes
+                .get(&item.menu_id)
+                .ok_or_else(|| InventoryError::NoRecipe(item.menu_id.clone()))?;
+            for (ingredient, quantity) in recipe.needs(&item.modifiers) {
+                let total: &mut u32 = totals.entry(ingredient.clone()).or_insert(0);
+                *total = quantity
+                    .checked_mul(item.quantity)
+                    .and_then(|needed| total.checked_add(needed))
+                    .ok_or(InventoryError::TooMuch(ingredient))?;
+            }
+        }
+        Ok(totals)
+    }
+
+    fn check_needs(&self, needs: &BTreeMap<String, u32>) -> Result<(), InventoryError> {
+        for (ingredient, &needed) in needs {
+            let on_hand = self.stock.get(ingredient).map_or(0, |l| l.on_hand);
+            if on_hand < needed {
+                return Err(InventoryError::OutOfStock {
+                    ingredient: ingredient.clone(),
+                    needed,
+                    on_hand,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // Whether there's enough in stock to make all of these items
+    pub fn check(&self, items: &[OrderItem]) -> Result<(), InventoryError> {
+        self.check_needs(&self.needs(items)?)
+    }
+
+    // Takes the ingredients for these items out of stock. Either everything
+    // is taken, or (if anything is short) nothing is.
+    pub fn use_for(&mut self, items: &[OrderItem]) -> Result<(), InventoryError> {
+        let needs = self.needs(items)?;
+        self.check_needs(&needs)?;
+        for (ingredient, needed) in needs {
+            let level = self
+                .stock
+                .get_mut(&ingredient)
+                .expect("check_needs makes sure every ingredient is stocked");
+            let before = level.on_hand;
+            level.on_hand -= needed;
+            if level.on_hand == 0 && before > 0 {
+                self.alerts.push(StockAlert::Out { ingredient });
+            } else if level.on_hand <= level.low_at && before > level.low_at {
+                self.alerts.push(StockAlert::Low {
+                    on_hand: level.on_hand,
+                    unit: level.unit,
+                    ingredient,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    // Hands over any alerts raised since they were last taken
+    pub fn take_alerts(&mut self) -> Vec<StockAlert> {
+        std::mem::take(&mut self.alerts)
+    }
+
+    fn can_make(&self, needs: Vec<(String, u32)>) -> bool {
+        let mut totals: BTreeMap<String, u32> = BTreeMap::new();
+        for (ingredient, quantity) in needs {
+            *totals.entry(ingredient).or_insert(0) += quantity;
+        }
+        self.check_needs(&totals).is_ok()
+    }
+
+    // Marks every item and option on the menu as available or not,
+    // depending on whether there's enough in stock to make one. Items
+    // without a recipe are left alone. An item with a required choice (like
+    // the toast for a breakfast) is only available if one of its options is.
+    pub fn apply_to(&self, menu: &mut Menu) {
+        let mut items = Vec::new();
+        let mut options = Vec::new();
+        for item in menu.items() {
+            let recipe = match self.recipes.get(&item.id) {
+                Some(recipe) => recipe,
+                None => continue,
+            };
+            let mut available = self.can_make(recipe.needs(&[]));
+            for group in &item.modifiers {
+                let mut any = false;
+                for option in &group.options {
+                    let extras = recipe.needs(std::slice::from_ref(&option.name));
+                    let ok = self.can_make(extras);
+                    options.push((item.id.clone(), option.name.clone(), ok));
+                    any |= ok;
+                }
+                if group.required && !any {
+                    available = false;
+                }
+            }
+            items.push((item.id.clone(), available));
+        }
+
+        for (id, available) in items {
+            menu.set_available(&id, available)
+                .expect("the id was just read from the menu");
+        }
+        for (id, option, available) in options {
+            menu.set_option_available(&id, &option, available)
+                .expect("the option was just read from the menu");
+        }
+    }
+
+    // Writes to a temporary file first and then renames it over the old
+    // one, so a crash part way through leaves the old stock file whole
+    pub fn save(&self, path: &Path) -> Result<(), InventoryError> {
+        let io_error = |path: &Path, e: io::Error| InventoryError::Io(format!("{}: {}", path.display(), e));
+        let text = serde_json::to_string_pretty(&self.stock)
+            .map_err(|e| InventoryError::Parse(e.to_string()))?;
+        let temporary = path.with_extension("saving");
+        let mut file = File::create(&temporary).map_err(|e| io_error(&temporary, e))?;
+        file.write_all(text.as_bytes()).map_err(|e| io_error(&temporary, e))?;
+        file.sync_all().map_err(|e| io_error(&temporary, e))?;
+        fs::rename(&temporary, path).map_err(|e| io_error(path, e))
+    }
+
+    // Reads stock levels saved by `save`, to be used with these recipes
+    pub fn load(path: &Path, recipes: Recipes) -> Result<Inventory, InventoryError> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| InventoryError::Io(format!("{}: {}", path.display(), e)))?;
+        let stock = serde_json::from_str(&text).map_err(|e| InventoryError::Parse(e.to_string()))?;
+        Ok(Inventory {
+            recipes,
+            stock,
+            alerts: Vec::new(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn soups(quantity: u32) -> Vec<OrderItem> {
+        vec![OrderItem::from_menu(&Menu::house(), "soup", &[], quantity).unwrap()]
+    }
+
+    fn on_hand(inventory: &Inventory, ingredient: &str) -> u32 {
+        inventory.level(ingredient).unwrap().on_hand
+    }
+
+    #[test]
+    fn cooking_takes_the_recipe_out_of_stock() {
+        let mut inventory = Inventory::house();
+        let menu = Menu::house();
+        let items = [
+            OrderItem::from_menu(&menu, "breakfast", &["Sourdough", "Extra fruit"], 2).unwrap(),
+            OrderItem::from_menu(&menu, "soup", &["Crusty roll"], 1).unwrap(),
+        ];
+        inventory.use_for(&items).unwrap();
+        assert_eq!(on_hand(&inventory, "eggs"), 56);
+        assert_eq!(on_hand(&inventory, "sourdough"), 12);
+        // One for each breakfast, and one more for each with extra fruit
+        assert_eq!(on_hand(&inventory, "seasonal fruit"), 26);
+        assert_eq!(on_hand(&inventory, "soup"), 5700);
+        assert_eq!(on_hand(&inventory, "bread rolls"), 19);
+        assert_eq!(on_hand(&inventory, "rye bread"), 24);
+    }
+
+    #[test]
+    fn nothing_is_taken_if_anything_is_short() {
+        let mut inventory = Inventory::house();
+        let items = [OrderItem::from_menu(&Menu::house(), "breakfast", &["Sourdough"], 9).unwrap()];
+        assert_eq!(
+            inventory.use_for(&items),
+            Err(InventoryError::OutOfStock {
+                ingredient: String::from("sourdough"),
+                needed: 18,
+                on_hand: 16,
+            })
+        );
+        assert_eq!(inventory, Inventory::house());
+    }
+
+    #[test]
+    fn huge_orders_are_refused_rather_than_overflowing() {
+        let mut inventory = Inventory::house();
+        assert_eq!(
+            inventory.use_for(&soups(u32::MAX)),
+            Err(InventoryError::TooMuch(String::from("soup")))
+        );
+        // Two lines that only overflow once they're added together
+        let items = [soups(u32::MAX / 300), soups(u32::MAX / 300)].concat();
+        assert_eq!(inventory.check(&items), Err(InventoryError::TooMuch(String::from("soup"))));
+        assert_eq!(inventory, Inventory::house());
+    }
+
+    #[test]
+    fn alerts_are_raised_once_as_stock_runs_low_and_out() {
+        let mut inventory = Inventory::house();
+        // 6000ml of soup, and 300ml a bowl. 15 bowls leaves 1500ml, which is
+        // still above the 1200ml threshold.
+        inventory.use_for(&soups(15)).unwrap();
+        assert!(inventory.take_alerts().is_empty());
+
+        inventory.use_for(&soups(1)).unwrap();
+        let alerts = inventory.take_alerts();
+        assert_eq!(
+            alerts,
+            [StockAlert::Low {
+                ingredient: String::from("soup"),
+                on_hand: 1200,
+                unit: Unit::Millilitres,
+            }]
+        );
+        assert_eq!(alerts[0].to_string(), "running low on soup: 1200ml left");
+        assert!(inventory.take_alerts().is_empty());
+
+        inventory.use_for(&soups(1)).unwrap();
+        assert!(inventory.take_alerts().is_empty());
+
+        inventory.use_for(&soups(3)).unwrap();
+        assert_eq!(inventory.take_alerts(), [StockAlert::Out { ingredient: String::from("soup") }]);
+    }
+
+    #[test]
+    fn the_threshold_can_be_changed() {
+        let mut inventory = Inventory::house();
+        inventory.set_threshold("soup", 5500).unwrap();
+        inventory.use_for(&soups(2)).unwrap();
+        assert_eq!(inventory.take_alerts().len(), 1);
+        assert_eq!(
+            inventory.set_threshold("caviar", 1),
+            Err(InventoryError::UnknownIngredient(String::from("caviar")))
+        );
+    }
+
+    #[test]
+    fn dishes_that_cant_be_made_are_taken_off_the_menu() {
+        let mut inventory = Inventory::house();
+        let mut menu = Menu::house();
+        inventory.use_for(&soups(20)).unwrap();
+        inventory.apply_to(&mut menu);
+        assert!(!menu.item("soup").unwrap().available);
+        assert!(menu.item("salad").unwrap().available);
+
+        // Without ranch the salad can still be had with another dressing
+        let ranch = inventory.level("ranch dressing").unwrap().on_hand;
+        let items = [OrderItem::from_menu(&Menu::house(), "salad", &["Ranch"], ranch / 30).unwrap()];
+        inventory.use_for(&items).unwrap();
+        inventory.apply_to(&mut menu);
+        let salad = menu.item("salad").unwrap();
+        assert!(salad.available);
+        let dressings = &salad.modifiers[0].options;
+        assert!(!dressings.iter().find(|o| o.name == "Ranch").unwrap().available);
+        assert!(dressings.iter().find(|o| o.name == "Vinaigrette").unwrap().available);
+
+        // Restocking puts it back
+        inventory.restock("soup", 300).unwrap();
+        inventory.apply_to(&mut menu);
+        assert!(menu.item("soup").unwrap().available);
+    }
+
+    #[test]
+    fn stock_levels_can_be_saved_and_loaded() {
+        let path = std::env::temp_dir().join(format!("restaurant-stock-{}.json", std::process::id()));
+        let mut inventory = Inventory::house();
+        inventory.use_for(&soups(17)).unwrap();
+        inventory.take_alerts();
+        inventory.save(&path).unwrap();
+        // Nothing is left behind from writing it
+        assert!(!path.with_extension("saving").exists());
+
+        let loaded = Inventory::load(&path, Recipes::house()).unwrap();
+        assert_eq!(loaded, inventory);
+        assert_eq!(on_hand(&loaded, "soup"), 900);
+
+        // Saving again replaces what was there
+        let fresh = Inventory::house();
+        fresh.save(&path).unwrap();
+        assert_eq!(Inventory::load(&path, Recipes::house()).unwrap(), fresh);
+        fs::remove_file(&path).unwrap();
+
+        match Inventory::load(&path, Recipes::house()) {
+            Err(InventoryError::Io(_)) => {}
+            other => panic!("expected a missing file, got {:?}", other),
+        }
+    }
+}
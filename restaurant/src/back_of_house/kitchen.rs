@@ -0,0 +1,615 @@
+// The kitchen: tickets come in from the front of house, and each item on a
+// ticket is routed to the station that cooks it (grill, fryer or cold).
+//
+// Every station runs on its own worker thread, and everything is passed
+// around using channels (see chapter 16 of the book). The Kitchen sends jobs
+// to each station down one channel, and the stations send word back when
+// each item is done down another.
+//
+// Each station cooks one thing at a time, so the Kitchen keeps track of when
+// every station will have finished what it's already been sent. A new
+// ticket is planned backwards from the moment its busiest station could be
+// done: on each station its items are cooked one after another, longest
+// first, timed so that the last one comes off right at that moment. That
+// way the whole ticket is ready at once, and nothing sits getting cold.
+//
+// A kitchen can also be given an Inventory, in which case the ingredients
+// for every ticket are taken out of stock as it's sent to the stations, and
+// anything that can't be made from what's left is refused.
+//
+// Remakes are sent as rush jobs, which each station cooks before anything
+// else it has waiting, even a job it was waiting to start. A remake is one
+// plate, however many of the item were on the order, just as a comp takes
+// one of it off the bill.
+//
+// Prep times are given in kitchen minutes. How long a kitchen minute lasts in
+// real time is up to whoever creates the Kitchen, so tests and demos can run
+// a whole service in a fraction of a second.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::back_of_house::inventory::{Inventory, InventoryError};
+use crate::front_of_house::serving::{Order, OrderError, OrderId, OrderItem};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Station {
+    Grill,
+    Fryer,
+    Cold,
+}
+
+impl Station {
+    pub const ALL: [Station; 3] = [Station::Grill, Station::Fryer, Station::Cold];
+}
+
+// Which station cooks each menu item, and how many minutes it takes
+#[derive(Debug, Clone, Default)]
+pub struct Routing {
+    dishes: HashMap<String, (Station, u32)>,
+}
+
+impl Routing {
+    pub fn new() -> Routing {
+        Routing::default()
+    }
+
+    pub fn route(mut self, menu_id: &str, station: Station, prep_minutes: u32) -> Routing {
+        self.dishes.insert(String::from(menu_id), (station, prep_minutes));
+        self
+    }
+
+    // Routing for everything on the house menu
+    pub fn house() -> Routing {
+        Routing::new()
+            .route("breakfast", Station::Grill, 10)
+            .route("soup", Station::Grill, 4)
+            .route("salad", Station::Cold, 3)
+    }
+
+    pub fn lookup(&self, menu_id: &str) -> Option<(Station, u32)> {
+        self.dishes.get(menu_id).copied()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KitchenError {
+    // Nobody in the kitchen knows how to cook this menu item
+    UnknownDish(String),
+    UnknownTicket(TicketId),
+    Order(OrderError),
+    Inventory(InventoryError),
+}
+
+impl fmt::Display for KitchenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KitchenError::UnknownDish(id) => write!(f, "no station cooks \"{}\"", id),
+            KitchenError::UnknownTicket(id) => write!(f, "there is no ticket {}", id.0),
+            KitchenError::Order(e) => write!(f, "{}", e),
+            KitchenError::Inventory(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for KitchenError {}
+
+impl From<OrderError> for KitchenError {
+    fn from(e: OrderError) -> KitchenError {
+        KitchenError::Order(e)
+    }
+}
+
+impl From<InventoryError> for KitchenError {
+    fn from(e: InventoryError) -> KitchenError {
+        KitchenError::Inventory(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TicketId(pub u32);
+
+// A single line of an order for a station to cook. Items ordered more than
+// once are cooked together, so the name includes the quantity.
+#[derive(Debug, Clone)]
+struct Job {
+    ticket: TicketId,
+    name: String,
+    rush: bool,
+    start_at: Instant,
+    prep: Duration,
+}
+
+// Sent back from a station when a job has been cooked
+#[derive(Debug, Clone)]
+struct Done {
+    ticket: TicketId,
+    station: Station,
+    name: String,
+    finished_at: Instant,
+}
+
+// A station is a worker thread and the channel used to send it jobs
+struct Worker {
+    station: Station,
+    jobs: Option<Sender<Job>>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Worker {
+    fn spawn(station: Station, done: Sender<Done>) -> Worker {
+        let (jobs, inbox) = mpsc::channel::<Job>();
+        let handle = thread::spawn(move || {
+            let mut waiting: Vec<Job> = Vec::new();
+            let mut open = true;
+            loop {
+                // Only wait for news when there's nothing to cook. The loop
+                // ends once the Kitchen drops its end of the channel and
+                // everything waiting has been cooked.
+                if waiting.is_empty() {
+                    match inbox.recv() {
+                        Ok(job) => waiting.push(job),
+                        Err(_) => break,
+                    }
+                }
+                while let Ok(job) = inbox.try_recv() {
+                    waiting.push(job);
+                }
+
+                // Rush jobs first, then whichever is due to start soonest
+                let next = waiting
+                    .iter()
+                    .enumerate()
+                    .min_by_key(|(_, job)| (!job.rush, job.start_at))
+                    .map(|(i, _)| i)
+                    .expect("there is always a job waiting here");
+
+                // While we wait for it to be time to start, keep listening:
+                // a rush job that comes in meanwhile should go first
+                let now = Instant::now();
+                let start_at = waiting[next].start_at;
+                if start_at > now {
+                    if open {
+                        match inbox.recv_timeout(start_at - now) {
+                            Ok(job) => {
+                                waiting.push(job);
+                                continue;
+                            }
+                            Err(mpsc::RecvTimeoutError::Timeout) => {}
+                            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                                open = false;
+                                continue;
+                            }
+                        }
+                    } else {
+                        thread::sleep(start_at - now);
+                    }
+                }
+                let job = waiting.remove(next);
+                thread::sleep(job.prep);
+                let report = Done {
+                    ticket: job.ticket,
+                    station,
+                    name: job.name,
+                    finished_at: Instant::now(),
+                };
+                // If the kitchen has gone away there's nobody to tell
+                if done.send(report).is_err() {
+                    break;
+                }
+            }
+        });
+        Worker {
+            station,
+            jobs: Some(jobs),
+            handle: Some(handle),
+        }
+    }
+}
+
+// Where a ticket is up to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TicketStatus {
+    pub id: TicketId,
+    pub order: OrderId,
+    pub outstanding: usize,
+    // How many kitchen minutes the ticket has been open for, or took in
+    // total once it's done
+    pub age_minutes: u32,
+    // (station, item) pairs, in the order they were finished
+    pub finished: Vec<(Station, String)>,
+}
+
+impl TicketStatus {
+    pub fn is_ready(&self) -> bool {
+        self.outstanding == 0
+    }
+}
+
+// Plans a ticket's jobs backwards from when they should all be ready. `jobs`
+// are (station, prep time) pairs, and `free_in` says how long until each
+// station has finished what it already has. Gives back how long until each
+// job should start, and how long until the whole ticket will be ready.
+fn schedule(jobs: &[(Station, Duration)], free_in: impl Fn(Station) -> Duration) -> (Vec<Duration>, Duration) {
+    let ready_in = Station::ALL
+        .iter()
+        .map(|&station| {
+            let cooking: Duration = jobs.iter().filter(|(s, _)| *s == station).map(|(_, prep)| *prep).sum();
+            if cooking.is_zero() {
+                Duration::ZERO
+            } else {
+                free_in(station) + cooking
+            }
+        })
+        .max()
+        .unwrap_or(Duration::ZERO);
+
+    // Walk each station's jobs from the last (the quickest) to the first,
+    // each one finishing when the one after it starts
+    let mut starts = vec![Duration::ZERO; jobs.len()];
+    for &station in &Station::ALL {
+        let mut order: Vec<usize> = (0..jobs.len()).filter(|&i| jobs[i].0 == station).collect();
+        order.sort_by_key(|&i| std::cmp::Reverse(jobs[i].1));
+        let mut ends = ready_in;
+        for &i in order.iter().rev() {
+            ends -= jobs[i].1;
+            starts[i] = ends;
+        }
+    }
+    (starts, ready_in)
+}
+
+struct TicketState {
+    order: OrderId,
+    sent_at: Instant,
+    outstanding: usize,
+    finished: Vec<(Station, String)>,
+    finished_at: Option<Instant>,
+}
+
+pub struct Kitchen {
+    routing: Routing,
+    minute: Duration,
+    inventory: Option<Inventory>,
+    workers: Vec<Worker>,
+    done: Receiver<Done>,
+    tickets: HashMap<TicketId, TicketState>,
+    next_ticket: u32,
+    // When each station will have cooked everything it's been sent so far
+    busy_until: HashMap<Station, Instant>,
+}
+
+impl Kitchen {
+    // `minute` is how long one kitchen minute takes in real time
+    pub fn new(routing: Routing, minute: Duration) -> Kitchen {
+        let (done_sender, done) = mpsc::channel();
+        let workers = Station::ALL
+            .iter()
+            .map(|&station| Worker::spawn(station, done_sender.clone()))
+            .collect();
+        Kitchen {
+            routing,
+            minute,
+            inventory: None,
+            workers,
+            done,
+            tickets: HashMap::new(),
+            next_ticket: 1,
+            busy_until: HashMap::new(),
+        }
+    }
+
+    // Keeps track of stock as tickets are cooked
+    pub fn with_inventory(mut self, inventory: Inventory) -> Kitchen {
+        self.inventory = Some(inventory);
+        self
+    }
+
+    pub fn inventory(&self) -> Option<&Inventory> {
+        self.inventory.as_ref()
+    }
+
+    pub fn inventory_mut(&mut self) -> Option<&mut Inventory> {
+        self.inventory.as_mut()
+    }
+
+    fn minutes(&self, duration: Duration) -> u32 {
+        (duration.as_nanos() / self.minute.as_nanos().max(1)) as u32
+    }
+
+    // How long until a station has cooked everything it's been sent
+    fn free_in(&self, station: Station, now: Instant) -> Duration {
+        self.busy_until
+            .get(&station)
+            .map_or(Duration::ZERO, |&until| until.saturating_duration_since(now))
+    }
+
+    // Sends every item on an order to its station, staggering the start
+    // times so they all finish together. Nothing is sent unless every item
+    // can be routed and there's enough in stock to make it all.
+    pub fn submit(&mut self, order: &Order) -> Result<TicketId, KitchenError> {
+        let mut routed = Vec::new();
+        for item in order.items() {
+            let (station, prep) = self
+                .routing
+                .lookup(&item.menu_id)
+                .ok_or_else(|| KitchenError::UnknownDish(item.menu_id.clone()))?;
+            routed.push((item, station, prep));
+        }
+        if let Some(inventory) = &mut self.inventory {
+            inventory.use_for(order.items())?;
+        }
+
+        let id = TicketId(self.next_ticket);
+        self.next_ticket += 1;
+        let sent_at = Instant::now();
+        let jobs: Vec<(Station, Duration)> = routed
+            .iter()
+            .map(|&(_, station, prep)| (station, self.minute * prep))
+            .collect();
+        let (starts, ready_in) = schedule(&jobs, |station| self.free_in(station, sent_at));
+
+        for (&(item, station, _), (&(_, prep), start)) in routed.iter().zip(jobs.iter().zip(starts)) {
+            let job = Job {
+                ticket: id,
+                name: format!("{} x{}", item.name, item.quantity),
+                rush: false,
+                start_at: sent_at + start,
+                prep,
+            };
+            self.send(station, job);
+            self.busy_until.insert(station, sent_at + ready_in);
+        }
+
+        self.tickets.insert(
+            id,
+            TicketState {
+                order: order.id,
+                sent_at,
+                outstanding: routed.len(),
+                finished: Vec::new(),
+                finished_at: if routed.is_empty() { Some(sent_at) } else { None },
+            },
+        );
+        Ok(id)
+    }
+
+    // Cooks one of an item from an order again, ahead of everything else
+    // waiting at its station. The remake gets a ticket of its own.
+    pub fn remake(&mut self, order: &Order, index: usize) -> Result<TicketId, KitchenError> {
+        let item = order.items().get(index).ok_or(OrderError::NoSuchItem {
+            order: order.id,
+            index,
+        })?;
+        let item = OrderItem {
+            quantity: 1,
+            ..item.clone()
+        };
+        let (station, prep) = self
+            .routing
+            .lookup(&item.menu_id)
+            .ok_or_else(|| KitchenError::UnknownDish(item.menu_id.clone()))?;
+        if let Some(inventory) = &mut self.inventory {
+            inventory.use_for(std::slice::from_ref(&item))?;
+        }
+
+        let id = TicketId(self.next_ticket);
+        self.next_ticket += 1;
+        let sent_at = Instant::now();
+        let prep = self.minute * prep;
+        self.send(
+            station,
+            Job {
+                ticket: id,
+                name: format!("{} x1 (remake)", item.name),
+                rush: true,
+                start_at: sent_at,
+                prep,
+            },
+        );
+        // Everything else at the station is pushed back while it's cooked
+        let free_in = self.free_in(station, sent_at);
+        self.busy_until.insert(station, sent_at + free_in + prep);
+        self.tickets.insert(
+            id,
+            TicketState {
+                order: order.id,
+                sent_at,
+                outstanding: 1,
+                finished: Vec::new(),
+                finished_at: None,
+            },
+        );
+        Ok(id)
+    }
+
+    fn send(&self, station: Station, job: Job) {
+        let worker = self
+            .workers
+            .iter()
+            .find(|w| w.station == station)
+            .expect("there is a worker for every station");
+        worker
+            .jobs
+            .as_ref()
+            .expect("jobs are only taken away when the kitchen shuts down")
+            .send(job)
+            .expect("station threads run until the kitchen shuts down");
+    }
+
+    fn record(&mut self, done: Done) {
+        if let Some(state) = self.tickets.get_mut(&done.ticket) {
+            state.outstanding -= 1;
+            state.finished.push((done.station, done.name));
+            if state.outstanding == 0 {
+                state.finished_at = Some(done.finished_at);
+            }
+        }
+    }
+
+    // Picks up any news from the stations without waiting
+    pub fn poll(&mut self) {
+        while let Ok(done) = self.done.try_recv() {
+            self.record(done);
+        }
+    }
+
+    fn status_of(&self, id: TicketId, state: &TicketState) -> TicketStatus {
+        let end = state.finished_at.unwrap_or_else(Instant::now);
+        TicketStatus {
+            id,
+            order: state.order,
+            outstanding: state.outstanding,
+            age_minutes: self.minutes(end - state.sent_at),
+            finished: state.finished.clone(),
+        }
+    }
+
+    pub fn status(&mut self, id: TicketId) -> Result<TicketStatus, KitchenError> {
+        self.poll();
+        let state = self.tickets.get(&id).ok_or(KitchenError::UnknownTicket(id))?;
+        Ok(self.status_of(id, state))
+    }
+
+    // Blocks until every item on the ticket has been cooked
+    pub fn wait_for(&mut self, id: TicketId) -> Result<TicketStatus, KitchenError> {
+        loop {
+            let state = self.tickets.get(&id).ok_or(KitchenError::UnknownTicket(id))?;
+            if state.outstanding == 0 {
+                return Ok(self.status_of(id, state));
+            }
+            let done = self
+                .done
+                .recv()
+                .expect("station threads run until the kitchen shuts down");
+            self.record(done);
+        }
+    }
+
+    // How long each ticket still being cooked has been open, oldest first
+    pub fn ticket_ages(&mut self) -> Vec<(TicketId, u32)> {
+        self.poll();
+        let mut ages: Vec<(TicketId, u32)> = self
+            .tickets
+            .iter()
+            .filter(|(_, state)| state.outstanding > 0)
+            .map(|(&id, state)| (id, self.minutes(state.sent_at.elapsed())))
+            .collect();
+        ages.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        ages
+    }
+}
+
+// When the kitchen closes, we drop each station's job channel so its loop
+// ends, then wait for the threads to finish what they were doing. This is the
+// same graceful shutdown used for the web server at the end of the book.
+impl Drop for Kitchen {
+    fn drop(&mut self) {
+        for worker in &mut self.workers {
+            drop(worker.jobs.take());
+        }
+        for worker in &mut self.workers {
+            if let Some(handle) = worker.handle.take() {
+                handle.join().expect("station threads don't panic");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::back_of_house::menu::Menu;
+    use crate::clock::Time;
+    use crate::front_of_house::hosting::PartyId;
+    use crate::back_of_house::inventory::Inventory;
+    use crate::front_of_house::serving::{self, OrderBook};
+
+    // Kitchen minutes as whole seconds, so the schedules are easy to read
+    fn minutes(m: u64) -> Duration {
+        Duration::from_secs(m)
+    }
+
+    fn idle(_: Station) -> Duration {
+        Duration::ZERO
+    }
+
+    #[test]
+    fn one_station_cooks_its_items_one_after_another() {
+        // Breakfast and soup are both on the grill, the salad is cold
+        let jobs = [
+            (Station::Grill, minutes(4)),
+            (Station::Grill, minutes(10)),
+            (Station::Cold, minutes(3)),
+        ];
+        let (starts, ready_in) = schedule(&jobs, idle);
+        assert_eq!(ready_in, minutes(14));
+        assert_eq!(starts, vec![minutes(10), minutes(0), minutes(11)]);
+    }
+
+    #[test]
+    fn a_busy_station_pushes_the_whole_ticket_back() {
+        let jobs = [(Station::Grill, minutes(10)), (Station::Cold, minutes(3))];
+        let busy = |station| if station == Station::Grill { minutes(5) } else { minutes(0) };
+        let (starts, ready_in) = schedule(&jobs, busy);
+        assert_eq!(ready_in, minutes(15));
+        assert_eq!(starts, vec![minutes(5), minutes(12)]);
+
+        // A station busier than the rest of the ticket needs still has its
+        // own items done in time
+        let busy = |station| if station == Station::Cold { minutes(20) } else { minutes(0) };
+        let (starts, ready_in) = schedule(&jobs, busy);
+        assert_eq!(ready_in, minutes(23));
+        assert_eq!(starts, vec![minutes(13), minutes(20)]);
+    }
+
+    #[test]
+    fn an_empty_ticket_is_ready_straight_away() {
+        assert_eq!(schedule(&[], idle), (Vec::new(), Duration::ZERO));
+    }
+
+    fn order(items: &[(&str, &[&str], u32)]) -> Order {
+        let menu = Menu::house();
+        let items = items
+            .iter()
+            .map(|&(id, modifiers, quantity)| OrderItem::from_menu(&menu, id, modifiers, quantity).unwrap())
+            .collect();
+        let mut orders = OrderBook::new();
+        let id = serving::take_order(&mut orders, PartyId(1), items, Time::hm(12, 0)).unwrap();
+        orders.get(id).unwrap().clone()
+    }
+
+    #[test]
+    fn a_rush_job_doesnt_wait_for_a_later_start() {
+        let minute = Duration::from_millis(20);
+        let mut kitchen = Kitchen::new(Routing::house(), minute);
+
+        // The salad won't start for 7 minutes, so that the breakfast can
+        // catch up with it
+        let lunch = order(&[("breakfast", &["Rye"], 1), ("salad", &["Ranch"], 1)]);
+        let ticket = kitchen.submit(&lunch).unwrap();
+        let remake = kitchen.remake(&lunch, 1).unwrap();
+
+        let remade = kitchen.wait_for(remake).unwrap();
+        assert!(remade.age_minutes < 7, "the remake took {} minutes", remade.age_minutes);
+        let done = kitchen.wait_for(ticket).unwrap();
+        assert_eq!(done.finished.len(), 2);
+    }
+
+    #[test]
+    fn a_remake_is_one_plate() {
+        let mut kitchen = Kitchen::new(Routing::house(), Duration::from_millis(1))
+            .with_inventory(Inventory::house());
+        let soups = order(&[("soup", &["Crusty roll"], 3)]);
+        let rolls = |kitchen: &Kitchen| kitchen.inventory().unwrap().level("bread rolls").unwrap().on_hand;
+        let before = rolls(&kitchen);
+
+        let remake = kitchen.remake(&soups, 0).unwrap();
+        assert_eq!(rolls(&kitchen), before - 1);
+        let done = kitchen.wait_for(remake).unwrap();
+        assert_eq!(done.finished, vec![(Station::Grill, String::from("Soup of the day x1 (remake)"))]);
+    }
+}
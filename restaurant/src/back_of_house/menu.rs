@@ -0,0 +1,495 @@
+// The menu catalog: categories of items, each with a price and optional
+// modifiers (like the type of toast that comes with a breakfast).
+//
+// Menus are written as JSON files, e.g.
+//
+//   { "categories": [ { "name": "Appetizers", "items": [
+//       { "id": "soup", "name": "Soup of the day", "price": "5.25" } ] } ] }
+//
+// Prices are written as strings so they can be read straight into Money
+// without passing through a float. Loading happens in two stages: serde
+// reads the file into the Raw* structs below, which mirror the file exactly,
+// then `validate` checks every entry and builds the real Menu. Any problem
+// is reported along with the path to the entry that caused it, such as
+// `categories[1].items[0] ("soup"): price ...`.
+//
+// Items and options can be marked as unavailable while the restaurant is
+// open, for example when the kitchen runs out of something (see
+// inventory.rs). Everything is available when a menu is first loaded.
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::money::Money;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModifierOption {
+    pub name: String,
+    // Added on top of the item's price. Zero for most options.
+    pub price: Money,
+    pub available: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ModifierGroup {
+    pub name: String,
+    // Required groups need exactly one option choosing. Optional groups can
+    // have any number of their options chosen, including none.
+    pub required: bool,
+    pub options: Vec<ModifierOption>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MenuItem {
+    pub id: String,
+    pub name: String,
+    pub price: Money,
+    pub modifiers: Vec<ModifierGroup>,
+    pub available: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Category {
+    pub name: String,
+    pub items: Vec<MenuItem>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Menu {
+    categories: Vec<Category>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MenuError {
+    Io(String),
+    // The file isn't valid JSON, or doesn't have the expected shape
+    Parse { line: usize, column: usize, message: String },
+    // The file parsed, but the entry at `path` doesn't make sense
+    Invalid { path: String, problem: String },
+    UnknownItem(String),
+    // Problems with the modifiers chosen when ordering an item
+    UnknownModifier { item: String, modifier: String },
+    MissingModifier { item: String, group: String },
+    TooManyModifiers { item: String, group: String },
+    DuplicateModifier { item: String, modifier: String },
+    // Nobody orders none of something
+    ZeroQuantity(String),
+    // The item, or the option chosen for it, has run out
+    Unavailable { item: String, option: Option<String> },
+}
+
+impl fmt::Display for MenuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MenuError::Io(message) => write!(f, "could not read menu: {}", message),
+            MenuError::Parse { line, column, message } => {
+                write!(f, "menu is not valid at line {} column {}: {}", line, column, message)
+            }
+            MenuError::Invalid { path, problem } => write!(f, "{}: {}", path, problem),
+            MenuError::UnknownItem(id) => write!(f, "there is no \"{}\" on the menu", id),
+            MenuError::UnknownModifier { item, modifier } => {
+                write!(f, "\"{}\" is not an option for {}", modifier, item)
+            }
+            MenuError::MissingModifier { item, group } => {
+                write!(f, "{} needs a choice of {}", item, group)
+            }
+            MenuError::TooManyModifiers { item, group } => {
+                write!(f, "{} only takes one choice of {}", item, group)
+            }
+            MenuError::DuplicateModifier { item, modifier } => {
+                write!(f, "\"{}\" was chosen more than once for {}", modifier, item)
+            }
+            MenuError::ZeroQuantity(item) => write!(f, "an order of {} needs a quantity of at least one", item),
+            MenuError::Unavailable { item, option: None } => {
+                write!(f, "{} is not available right now", item)
+            }
+            MenuError::Unavailable { item, option: Some(option) } => {
+                write!(f, "{} is not available with {} right now", item, option)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MenuError {}
+
+// These mirror the layout of the JSON file. Anything optional in the file is
+// an Option (or defaults to empty) here, and prices are still strings.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawMenu {
+    categories: Vec<RawCategory>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawCategory {
+    name: String,
+    #[serde(default)]
+    items: Vec<RawItem>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawItem {
+    id: String,
+    name: String,
+    price: String,
+    #[serde(default)]
+    modifiers: Vec<RawModifierGroup>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawModifierGroup {
+    name: String,
+    #[serde(default)]
+    required: bool,
+    options: Vec<RawOption>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawOption {
+    name: String,
+    price: Option<String>,
+}
+
+fn invalid(path: &str, problem: String) -> MenuError {
+    MenuError::Invalid {
+        path: String::from(path),
+        problem,
+    }
+}
+
+fn parse_price(path: &str, price: &str) -> Result<Money, MenuError> {
+    let money: Money = price
+        .parse()
+        .map_err(|e| invalid(path, format!("price {}", e)))?;
+    if money.is_negative() {
+        return Err(invalid(path, format!("price {} can't be negative", money)));
+    }
+    Ok(money)
+}
+
+fn check_name(path: &str, name: &str) -> Result<(), MenuError> {
+    if name.trim().is_empty() {
+        return Err(invalid(path, String::from("name can't be empty")));
+    }
+    Ok(())
+}
+
+fn validate(raw: RawMenu) -> Result<Menu, MenuError> {
+    let mut ids = HashSet::new();
+    let mut categories = Vec::new();
+
+    for (c, raw_category) in raw.categories.into_iter().enumerate() {
+        let category_path = format!("categories[{}]", c);
+        check_name(&category_path, &raw_category.name)?;
+
+        let mut items = Vec::new();
+        for (i, raw_item) in raw_category.items.into_iter().enumerate() {
+            let path = format!("{}.items[{}] (\"{}\")", category_path, i, raw_item.id);
+            if raw_item.id.trim().is_empty() {
+                return Err(invalid(&path, String::from("id can't be empty")));
+            }
+            if !ids.insert(raw_item.id.clone()) {
+                return Err(invalid(&path, String::from("id is used by another item")));
+            }
+            check_name(&path, &raw_item.name)?;
+            let price = parse_price(&path, &raw_item.price)?;
+
+            let mut modifiers = Vec::new();
+            for (g, raw_group) in raw_item.modifiers.into_iter().enumerate() {
+                let group_path = format!("{}.modifiers[{}]", path, g);
+                check_name(&group_path, &raw_group.name)?;
+                if raw_group.options.is_empty() {
+                    return Err(invalid(&group_path, String::from("needs at least one option")));
+                }
+
+                let mut options: Vec<ModifierOption> = Vec::new();
+                for (o, raw_option) in raw_group.options.into_iter().enumerate() {
+                    let option_path = format!("{}.options[{}]", group_path, o);
+                    check_name(&option_path, &raw_option.name)?;
+                    if options.iter().any(|existing| existing.name == raw_option.name) {
+                        return Err(invalid(&option_path, String::from("option is listed twice")));
+                    }
+                    // Choices are given by name alone, so each name can only
+                    // belong to one of the item's groups
+                    if let Some(other) = modifiers
+                        .iter()
+                        .find(|g: &&ModifierGroup| g.options.iter().any(|o| o.name == raw_option.name))
+                    {
+                        return Err(invalid(&option_path, format!("option is also in {}", other.name)));
+                    }
+                    let price = match raw_option.price {
+                        Some(price) => parse_price(&option_path, &price)?,
+                        None => Money::ZERO,
+                    };
+                    options.push(ModifierOption {
+                        name: raw_option.name,
+                        price,
+                        available: true,
+                    });
+                }
+                modifiers.push(ModifierGroup {
+                    name: raw_group.name,
+                    required: raw_group.required,
+                    options,
+                });
+            }
+
+            items.push(MenuItem {
+                id: raw_item.id,
+                name: raw_item.name,
+                price,
+                modifiers,
+                available: true,
+            });
+        }
+        categories.push(Category {
+            name: raw_category.name,
+            items,
+        });
+    }
+
+    Ok(Menu { categories })
+}
+
+impl Menu {
+    pub fn from_json(text: &str) -> Result<Menu, MenuError> {
+        let raw: RawMenu = serde_json::from_str(text).map_err(|e| {
+            // serde_json adds the position to the end of its message, but we
+            // report that separately
+            let message = e.to_string();
+            let message = message.split(" at line ").next().unwrap_or_default();
+            MenuError::Parse {
+                line: e.line(),
+                column: e.column(),
+                message: String::from(message),
+            }
+        })?;
+        validate(raw)
+    }
+
+    pub fn load(path: &Path) -> Result<Menu, MenuError> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| MenuError::Io(format!("{}: {}", path.display(), e)))?;
+        Menu::from_json(&text)
+    }
+
+    // The menu that ships with the crate, in menu.json. include_str! reads
+    // the file in at compile time, so it's always available.
+    pub fn house() -> Menu {
+        Menu::from_json(include_str!("../../menu.json")).expect("menu.json should always be valid")
+    }
+
+    pub fn categories(&self) -> &[Category] {
+        &self.categories
+    }
+
+    pub fn items(&self) -> impl Iterator<Item = &MenuItem> {
+        self.categories.iter().flat_map(|c| c.items.iter())
+    }
+
+    pub fn item(&self, id: &str) -> Result<&MenuItem, MenuError> {
+        self.items()
+            .find(|item| item.id == id)
+            .ok_or_else(|| MenuError::UnknownItem(String::from(id)))
+    }
+
+    fn item_mut(&mut self, id: &str) -> Result<&mut MenuItem, MenuError> {
+        self.categories
+            .iter_mut()
+            .flat_map(|c| c.items.iter_mut())
+            .find(|item| item.id == id)
+            .ok_or_else(|| MenuError::UnknownItem(String::from(id)))
+    }
+
+    pub fn set_available(&mut self, id: &str, available: bool) -> Result<(), MenuError> {
+        self.item_mut(id)?.available = available;
+        Ok(())
+    }
+
+    pub fn set_option_available(
+        &mut self,
+        id: &str,
+        option: &str,
+        available: bool,
+    ) -> Result<(), MenuError> {
+        let item = self.item_mut(id)?;
+        let name = item.name.clone();
+        let option = item
+            .modifiers
+            .iter_mut()
+            .flat_map(|g| g.options.iter_mut())
+            .find(|o| o.name == option)
+            .ok_or_else(|| MenuError::UnknownModifier {
+                item: name,
+                modifier: String::from(option),
+            })?;
+        option.available = available;
+        Ok(())
+    }
+}
+
+impl MenuItem {
+    // Checks a set of chosen modifiers against this item, and finds the
+    // option each one names. Every choice is looked up in the group it
+    // belongs to, each can only be made once, and every required group
+    // needs exactly one choice. Whether the options are available isn't
+    // checked here.
+    pub fn choices(&self, chosen: &[String]) -> Result<Vec<&ModifierOption>, MenuError> {
+        // How many choices have been made from each group
+        let mut picked = vec![0; self.modifiers.len()];
+        let mut options = Vec::new();
+        for (i, choice) in chosen.iter().enumerate() {
+            if chosen[..i].contains(choice) {
+                return Err(MenuError::DuplicateModifier {
+                    item: self.name.clone(),
+                    modifier: choice.clone(),
+                });
+            }
+            let (group, option) = self
+                .modifiers
+                .iter()
+                .enumerate()
+                .find_map(|(g, group)| group.options.iter().find(|o| &o.name == choice).map(|o| (g, o)))
+                .ok_or_else(|| MenuError::UnknownModifier {
+                    item: self.name.clone(),
+                    modifier: choice.clone(),
+                })?;
+            picked[group] += 1;
+            options.push(option);
+        }
+
+        for (group, picked) in self.modifiers.iter().zip(picked) {
+            if !group.required {
+                continue;
+            }
+            match picked {
+                0 => {
+                    return Err(MenuError::MissingModifier {
+                        item: self.name.clone(),
+                        group: group.name.clone(),
+                    })
+                }
+                1 => {}
+                _ => {
+                    return Err(MenuError::TooManyModifiers {
+                        item: self.name.clone(),
+                        group: group.name.clone(),
+                    })
+                }
+            }
+        }
+        Ok(options)
+    }
+
+    // Works out the price of one item with the chosen modifiers added, as
+    // long as the item and all of the options are available right now
+    pub fn price_with(&self, chosen: &[String]) -> Result<Money, MenuError> {
+        if !self.available {
+            return Err(MenuError::Unavailable {
+                item: self.name.clone(),
+                option: None,
+            });
+        }
+        let mut price = self.price;
+        for option in self.choices(chosen)? {
+            if !option.available {
+                return Err(MenuError::Unavailable {
+                    item: self.name.clone(),
+                    option: Some(option.name.clone()),
+                });
+            }
+            price += option.price;
+        }
+        Ok(price)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chosen(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| String::from(*n)).collect()
+    }
+
+    fn breakfast(menu: &Menu) -> &MenuItem {
+        menu.item("breakfast").unwrap()
+    }
+
+    #[test]
+    fn options_add_to_the_price() {
+        let menu = Menu::house();
+        let price = breakfast(&menu).price_with(&chosen(&["Sourdough", "Extra fruit"]));
+        assert_eq!(price, Ok(Money::from_cents(1050)));
+        let price = breakfast(&menu).price_with(&chosen(&["Rye"]));
+        assert_eq!(price, Ok(Money::from_cents(850)));
+    }
+
+    #[test]
+    fn a_choice_can_only_be_made_once() {
+        let menu = Menu::house();
+        let error = breakfast(&menu).price_with(&chosen(&["Sourdough", "Sourdough"])).unwrap_err();
+        assert_eq!(
+            error,
+            MenuError::DuplicateModifier { item: String::from("Breakfast"), modifier: String::from("Sourdough") }
+        );
+        let error = breakfast(&menu).price_with(&chosen(&["Rye", "Extra fruit", "Extra fruit"]));
+        assert!(matches!(error, Err(MenuError::DuplicateModifier { .. })));
+    }
+
+    #[test]
+    fn required_groups_take_exactly_one_choice() {
+        let menu = Menu::house();
+        let error = breakfast(&menu).price_with(&chosen(&["Extra fruit"])).unwrap_err();
+        assert_eq!(
+            error,
+            MenuError::MissingModifier { item: String::from("Breakfast"), group: String::from("Toast") }
+        );
+        let error = breakfast(&menu).price_with(&chosen(&["Rye", "Wheat"])).unwrap_err();
+        assert_eq!(
+            error,
+            MenuError::TooManyModifiers { item: String::from("Breakfast"), group: String::from("Toast") }
+        );
+        let error = breakfast(&menu).price_with(&chosen(&["Rye", "Ranch"])).unwrap_err();
+        assert_eq!(
+            error,
+            MenuError::UnknownModifier { item: String::from("Breakfast"), modifier: String::from("Ranch") }
+        );
+    }
+
+    #[test]
+    fn an_option_that_has_run_out() {
+        let mut menu = Menu::house();
+        menu.set_option_available("breakfast", "Sourdough", false).unwrap();
+        let error = breakfast(&menu).price_with(&chosen(&["Sourdough"])).unwrap_err();
+        assert_eq!(
+            error,
+            MenuError::Unavailable { item: String::from("Breakfast"), option: Some(String::from("Sourdough")) }
+        );
+        // It's still an option, just not right now
+        assert_eq!(breakfast(&menu).choices(&chosen(&["Sourdough"])).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn an_option_belongs_to_one_group() {
+        let json = r#"{"categories": [{"name": "Mains", "items": [{
+            "id": "pie", "name": "Pie", "price": "9.00",
+            "modifiers": [
+                {"name": "Side", "required": true, "options": [{"name": "Chips"}, {"name": "None"}]},
+                {"name": "Sauce", "options": [{"name": "Gravy"}, {"name": "None"}]}
+            ]
+        }]}]}"#;
+        match Menu::from_json(json) {
+            Err(MenuError::Invalid { problem, .. }) => assert_eq!(problem, "option is also in Side"),
+            other => panic!("expected the menu to be invalid, got {:?}", other),
+        }
+    }
+}
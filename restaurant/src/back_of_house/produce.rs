@@ -0,0 +1,241 @@
+// A seasonal produce calendar, used to pick the fruit that comes with a
+// Breakfast.
+//
+// Each fruit is listed with the months it is in season for in the northern
+// hemisphere. The seasons are flipped south of the equator, so for a
+// southern hemisphere restaurant we simply shift every month by six.
+//
+// The default calendar deliberately leaves a gap in March (the "hungry gap",
+// when last year's stores have run out and nothing new is ready yet), so
+// ordering a seasonal breakfast has to cope with there being no fruit.
+
+use std::fmt;
+
+use crate::clock::Date;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Hemisphere {
+    #[default]
+    Northern,
+    Southern,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+impl Season {
+    // Uses meteorological seasons, which start on the first of the month:
+    // in the north, spring is March to May, summer June to August and so on
+    pub fn of(date: Date, hemisphere: Hemisphere) -> Season {
+        match northern_month(date.month(), hemisphere) {
+            3..=5 => Season::Spring,
+            6..=8 => Season::Summer,
+            9..=11 => Season::Autumn,
+            _ => Season::Winter,
+        }
+    }
+
+    // The northern hemisphere months making up this season
+    fn months(&self) -> [u32; 3] {
+        match self {
+            Season::Spring => [3, 4, 5],
+            Season::Summer => [6, 7, 8],
+            Season::Autumn => [9, 10, 11],
+            Season::Winter => [12, 1, 2],
+        }
+    }
+}
+
+// The month in the northern hemisphere with the same season as `month` does
+// in the given hemisphere
+fn northern_month(month: u32, hemisphere: Hemisphere) -> u32 {
+    match hemisphere {
+        Hemisphere::Northern => month,
+        Hemisphere::Southern => (month + 5) % 12 + 1,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NothingInSeason {
+    pub date: Option<Date>,
+    pub season: Season,
+    pub hemisphere: Hemisphere,
+}
+
+impl fmt::Display for NothingInSeason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.date {
+            Some(date) => write!(
+                f,
+                "no fruit is in season on {} ({:?}, {:?} hemisphere)",
+                date, self.season, self.hemisphere
+            ),
+            None => write!(
+                f,
+                "no fruit is in season in {:?} ({:?} hemisphere)",
+                self.season, self.hemisphere
+            ),
+        }
+    }
+}
+
+impl std::error::Error for NothingInSeason {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProduceCalendar {
+    hemisphere: Hemisphere,
+    // Fruit in order of preference, with the (northern hemisphere) months
+    // each one is in season
+    fruit: Vec<(String, Vec<u32>)>,
+}
+
+impl ProduceCalendar {
+    pub fn new(hemisphere: Hemisphere) -> ProduceCalendar {
+        ProduceCalendar {
+            hemisphere,
+            fruit: Vec::new(),
+        }
+    }
+
+    // The calendar the kitchen uses unless told otherwise. Peaches come first
+    // so that a summer breakfast still comes with peaches.
+    pub fn standard(hemisphere: Hemisphere) -> ProduceCalendar {
+        ProduceCalendar::new(hemisphere)
+            .with("peaches", &[6, 7, 8, 9])
+            .with("strawberries", &[6, 7, 8])
+            .with("blueberries", &[7, 8, 9])
+            .with("plums", &[8, 9, 10])
+            .with("apples", &[9, 10, 11, 12, 1, 2])
+            .with("pears", &[9, 10, 11, 12, 1])
+            .with("rhubarb", &[4, 5, 6])
+    }
+
+    // Adds a fruit to the calendar, after everything already listed. Months
+    // are always given as they are in the northern hemisphere.
+    pub fn with(mut self, fruit: &str, months: &[u32]) -> ProduceCalendar {
+        self.fruit.push((String::from(fruit), months.to_vec()));
+        self
+    }
+
+    pub fn hemisphere(&self) -> Hemisphere {
+        self.hemisphere
+    }
+
+    // Every fruit in season on a date, most preferred first
+    pub fn in_season(&self, date: Date) -> Vec<&str> {
+        let month = northern_month(date.month(), self.hemisphere);
+        self.fruit
+            .iter()
+            .filter(|(_, months)| months.contains(&month))
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    // The most preferred fruit in season on a date
+    pub fn fruit_for(&self, date: Date) -> Result<&str, NothingInSeason> {
+        self.in_season(date).first().copied().ok_or(NothingInSeason {
+            date: Some(date),
+            season: Season::of(date, self.hemisphere),
+            hemisphere: self.hemisphere,
+        })
+    }
+
+    // The most preferred fruit available at some point during a season
+    pub fn fruit_for_season(&self, season: Season) -> Result<&str, NothingInSeason> {
+        let season_months = season.months();
+        self.fruit
+            .iter()
+            .find(|(_, months)| months.iter().any(|m| season_months.contains(m)))
+            .map(|(name, _)| name.as_str())
+            .ok_or(NothingInSeason {
+                date: None,
+                season,
+                hemisphere: self.hemisphere,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::back_of_house::Breakfast;
+
+    fn date(year: i32, month: u32, day: u32) -> Date {
+        Date::new(year, month, day).unwrap()
+    }
+
+    fn southern() -> ProduceCalendar {
+        ProduceCalendar::standard(Hemisphere::Southern)
+    }
+
+    #[test]
+    fn the_same_day_is_a_different_season_south_of_the_equator() {
+        let july = date(2026, 7, 15);
+        assert_eq!(Season::of(july, Hemisphere::Northern), Season::Summer);
+        assert_eq!(Season::of(july, Hemisphere::Southern), Season::Winter);
+
+        let north = Breakfast::for_date(july, "Rye").unwrap();
+        assert_eq!(north.seasonal_fruit(), "peaches");
+        let south = Breakfast::for_date_with(&southern(), july, "Rye").unwrap();
+        assert_eq!(south.seasonal_fruit(), "apples");
+        assert_eq!(southern().in_season(july), ["apples", "pears"]);
+    }
+
+    #[test]
+    fn seasons_change_on_the_first_of_the_month() {
+        let (may, june) = (date(2026, 5, 31), date(2026, 6, 1));
+        assert_eq!(Season::of(may, Hemisphere::Northern), Season::Spring);
+        assert_eq!(Season::of(june, Hemisphere::Northern), Season::Summer);
+        assert_eq!(Breakfast::for_date(may, "Rye").unwrap().seasonal_fruit(), "rhubarb");
+        assert_eq!(Breakfast::for_date(june, "Rye").unwrap().seasonal_fruit(), "peaches");
+
+        assert_eq!(Season::of(may, Hemisphere::Southern), Season::Autumn);
+        assert_eq!(Season::of(june, Hemisphere::Southern), Season::Winter);
+        // December to February is winter in the north, whichever year
+        assert_eq!(Season::of(date(2026, 12, 1), Hemisphere::Northern), Season::Winter);
+        assert_eq!(Season::of(date(2027, 2, 28), Hemisphere::Northern), Season::Winter);
+        assert_eq!(Season::of(date(2027, 3, 1), Hemisphere::Northern), Season::Spring);
+    }
+
+    #[test]
+    fn nothing_in_season_is_an_error_rather_than_a_made_up_fruit() {
+        let march = date(2026, 3, 10);
+        let error = Breakfast::for_date(march, "Rye").err().unwrap();
+        assert_eq!(
+            error,
+            NothingInSeason {
+                date: Some(march),
+                season: Season::Spring,
+                hemisphere: Hemisphere::Northern,
+            }
+        );
+        assert_eq!(error.to_string(), "no fruit is in season on 2026-03-10 (Spring, Northern hemisphere)");
+
+        // March is autumn in the south, and the gap comes in September
+        assert_eq!(southern().fruit_for(march), Ok("peaches"));
+        let september = date(2026, 9, 10);
+        assert_eq!(
+            southern().fruit_for(september).unwrap_err().to_string(),
+            "no fruit is in season on 2026-09-10 (Spring, Southern hemisphere)"
+        );
+    }
+
+    #[test]
+    fn a_season_has_whatever_is_ready_at_some_point_in_it() {
+        let calendar = ProduceCalendar::standard(Hemisphere::Northern);
+        assert_eq!(calendar.fruit_for_season(Season::Spring), Ok("rhubarb"));
+        assert_eq!(calendar.fruit_for_season(Season::Winter), Ok("apples"));
+        assert_eq!(Breakfast::summer("Rye").seasonal_fruit(), "peaches");
+
+        let empty = ProduceCalendar::new(Hemisphere::Northern);
+        assert_eq!(
+            empty.fruit_for_season(Season::Winter).unwrap_err().to_string(),
+            "no fruit is in season in Winter (Northern hemisphere)"
+        );
+    }
+}
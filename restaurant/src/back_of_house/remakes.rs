@@ -0,0 +1,332 @@
+// Keeping track of food that had to be made again.
+//
+// Whenever a guest sends something back, the item is flagged with a reason
+// and cooked again as a rush job. Every remake is written down, whether or
+// not it was taken off the bill, so that at the end of the day we can see
+// what went wrong most often and how much it cost us.
+
+use std::fmt;
+
+use crate::back_of_house::kitchen::{KitchenError, TicketId};
+use crate::clock::Time;
+use crate::front_of_house::billing::BillingError;
+use crate::front_of_house::serving::{Order, OrderId};
+use crate::money::Money;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RemakeReason {
+    WrongItem,
+    Undercooked,
+    Overcooked,
+    Cold,
+    // Something the guest is allergic to ended up on the plate
+    Allergen,
+    Other(String),
+}
+
+impl fmt::Display for RemakeReason {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RemakeReason::WrongItem => write!(f, "wrong item"),
+            RemakeReason::Undercooked => write!(f, "undercooked"),
+            RemakeReason::Overcooked => write!(f, "overcooked"),
+            RemakeReason::Cold => write!(f, "served cold"),
+            RemakeReason::Allergen => write!(f, "contained an allergen"),
+            RemakeReason::Other(reason) => write!(f, "{}", reason),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RemakeId(pub u32);
+
+// One entry in the audit trail
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Remake {
+    pub id: RemakeId,
+    pub order: OrderId,
+    // Which of the order's items was sent back, and what it was
+    pub item: usize,
+    pub name: String,
+    pub reason: RemakeReason,
+    pub flagged_at: Time,
+    pub ticket: TicketId,
+    // How much was taken off the bill, if anything
+    pub comped: Option<Money>,
+    // When the new plate reached the table
+    pub resolved_at: Option<Time>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RemakeError {
+    Kitchen(KitchenError),
+    Billing(BillingError),
+    // A bill was given for comping, but it's for a different order
+    WrongBill { order: OrderId, bill_for: OrderId },
+    UnknownRemake(RemakeId),
+}
+
+impl fmt::Display for RemakeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RemakeError::Kitchen(e) => write!(f, "{}", e),
+            RemakeError::Billing(e) => write!(f, "{}", e),
+            RemakeError::WrongBill { order, bill_for } => write!(
+                f,
+                "can't comp order {} on the bill for order {}",
+                order.0, bill_for.0
+            ),
+            RemakeError::UnknownRemake(id) => write!(f, "there is no remake {}", id.0),
+        }
+    }
+}
+
+impl std::error::Error for RemakeError {}
+
+impl From<KitchenError> for RemakeError {
+    fn from(e: KitchenError) -> RemakeError {
+        RemakeError::Kitchen(e)
+    }
+}
+
+impl From<BillingError> for RemakeError {
+    fn from(e: BillingError) -> RemakeError {
+        RemakeError::Billing(e)
+    }
+}
+
+// What went wrong over a day, for the end-of-day report
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemakeReport {
+    pub remakes: usize,
+    pub unresolved: usize,
+    pub comped: Money,
+    // Counts, most common first
+    pub by_reason: Vec<(String, usize)>,
+    pub by_item: Vec<(String, usize)>,
+}
+
+impl fmt::Display for RemakeReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} remakes ({} still to go out), {} comped",
+            self.remakes, self.unresolved, self.comped
+        )?;
+        for (reason, count) in &self.by_reason {
+            writeln!(f, "  {:<24}{:>3}", reason, count)?;
+        }
+        for (item, count) in &self.by_item {
+            writeln!(f, "  {:<24}{:>3}", item, count)?;
+        }
+        Ok(())
+    }
+}
+
+// Counts how often each label appears, most common first (then by name, so
+// the report comes out the same every time)
+fn tally(labels: impl Iterator<Item = String>) -> Vec<(String, usize)> {
+    let mut counts: Vec<(String, usize)> = Vec::new();
+    for label in labels {
+        match counts.iter_mut().find(|(l, _)| *l == label) {
+            Some((_, count)) => *count += 1,
+            None => counts.push((label, 1)),
+        }
+    }
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    counts
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RemakeLog {
+    entries: Vec<Remake>,
+}
+
+impl RemakeLog {
+    pub fn new() -> RemakeLog {
+        RemakeLog::default()
+    }
+
+    // The item must be on the order; fix_incorrect_order checks that
+    // before anything is recorded
+    pub(crate) fn record(
+        &mut self,
+        order: &Order,
+        item: usize,
+        reason: RemakeReason,
+        flagged_at: Time,
+        ticket: TicketId,
+        comped: Option<Money>,
+    ) -> RemakeId {
+        let id = RemakeId(self.entries.len() as u32 + 1);
+        self.entries.push(Remake {
+            id,
+            order: order.id,
+            item,
+            name: order.items()[item].name.clone(),
+            reason,
+            flagged_at,
+            ticket,
+            comped,
+            resolved_at: None,
+        });
+        id
+    }
+
+    // Notes that the new plate has gone out to the guest
+    pub fn resolve(&mut self, id: RemakeId, at: Time) -> Result<(), RemakeError> {
+        let entry = self
+            .entries
+            .iter_mut()
+            .find(|e| e.id == id)
+            .ok_or(RemakeError::UnknownRemake(id))?;
+        entry.resolved_at = Some(at);
+        Ok(())
+    }
+
+    pub fn entries(&self) -> &[Remake] {
+        &self.entries
+    }
+
+    pub fn for_order(&self, order: OrderId) -> impl Iterator<Item = &Remake> {
+        self.entries.iter().filter(move |e| e.order == order)
+    }
+
+    pub fn report(&self) -> RemakeReport {
+        RemakeReport {
+            remakes: self.entries.len(),
+            unresolved: self.entries.iter().filter(|e| e.resolved_at.is_none()).count(),
+            comped: self.entries.iter().filter_map(|e| e.comped).sum(),
+            by_reason: tally(self.entries.iter().map(|e| e.reason.to_string())),
+            by_item: tally(self.entries.iter().map(|e| e.name.clone())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::back_of_house::fix_incorrect_order;
+    use crate::back_of_house::kitchen::{Kitchen, Routing, Station};
+    use crate::back_of_house::menu::Menu;
+    use crate::front_of_house::billing::{Bill, BillingConfig};
+    use crate::front_of_house::hosting::PartyId;
+    use crate::front_of_house::serving::OrderItem;
+
+    // Two breakfasts and a soup, all cooked on the grill
+    fn lunch() -> Order {
+        let menu = Menu::house();
+        let mut order = Order::new(OrderId(4), PartyId(2), Time::hm(12, 0));
+        for (id, modifiers) in [("breakfast", &["Rye"][..]), ("breakfast", &["Wheat"]), ("soup", &[])] {
+            order.add_item(OrderItem::from_menu(&menu, id, modifiers, 1).unwrap()).unwrap();
+        }
+        order
+    }
+
+    fn kitchen() -> Kitchen {
+        Kitchen::new(Routing::house(), Duration::from_millis(1))
+    }
+
+    #[test]
+    fn a_remake_is_logged_with_its_reason() {
+        let (mut kitchen, mut log, order) = (kitchen(), RemakeLog::new(), lunch());
+        let reason = RemakeReason::Other(String::from("too salty"));
+        let id = fix_incorrect_order(&mut kitchen, &mut log, &order, 2, reason, None, Time::hm(12, 30)).unwrap();
+
+        let remake = &log.entries()[0];
+        assert_eq!(remake.id, id);
+        assert_eq!((remake.order, remake.item), (OrderId(4), 2));
+        assert_eq!(remake.name, "Soup of the day");
+        assert_eq!(remake.reason.to_string(), "too salty");
+        assert_eq!(remake.flagged_at, Time::hm(12, 30));
+        assert_eq!(remake.comped, None);
+        assert_eq!(remake.resolved_at, None);
+        kitchen.wait_for(remake.ticket).unwrap();
+    }
+
+    #[test]
+    fn a_remake_goes_ahead_of_everything_waiting() {
+        let mut kitchen = Kitchen::new(Routing::house(), Duration::from_millis(20));
+        let mut log = RemakeLog::new();
+        let order = lunch();
+        // 24 minutes of cooking on the grill, with the soup going on last
+        let ticket = kitchen.submit(&order).unwrap();
+        let id = fix_incorrect_order(&mut kitchen, &mut log, &order, 2, RemakeReason::Cold, None, Time::hm(12, 5))
+            .unwrap();
+
+        let remade = kitchen.wait_for(log.entries()[0].ticket).unwrap();
+        assert_eq!(remade.finished, [(Station::Grill, String::from("Soup of the day x1 (remake)"))]);
+        assert!(kitchen.status(ticket).unwrap().outstanding > 0);
+        log.resolve(id, Time::hm(12, 12)).unwrap();
+        assert_eq!(log.entries()[0].resolved_at, Some(Time::hm(12, 12)));
+        kitchen.wait_for(ticket).unwrap();
+    }
+
+    #[test]
+    fn a_comp_takes_one_of_the_item_off_the_bill() {
+        let (mut kitchen, mut log, order) = (kitchen(), RemakeLog::new(), lunch());
+        let mut bill = Bill::for_order(&order, &BillingConfig::new());
+        let before = bill.total();
+        fix_incorrect_order(&mut kitchen, &mut log, &order, 2, RemakeReason::Cold, Some(&mut bill), Time::hm(12, 30))
+            .unwrap();
+
+        assert_eq!(log.entries()[0].comped, Some(Money::from_cents(525)));
+        let comp = bill.lines().last().unwrap();
+        assert_eq!(comp.description, "Comp: Soup of the day (served cold)");
+        assert_eq!(comp.total, Money::from_cents(-525));
+        assert_eq!(comp.comp_of, Some(2));
+        assert_eq!(bill.total(), before - Money::from_cents(525));
+
+        // There was only one soup, so it can't be comped again. Nothing is
+        // logged when a remake is refused.
+        let error = fix_incorrect_order(&mut kitchen, &mut log, &order, 2, RemakeReason::Cold, Some(&mut bill), Time::hm(12, 40))
+            .unwrap_err();
+        assert_eq!(error, RemakeError::Billing(BillingError::CantComp { line: 2 }));
+        assert_eq!(log.entries().len(), 1);
+        kitchen.wait_for(log.entries()[0].ticket).unwrap();
+    }
+
+    #[test]
+    fn the_bill_has_to_be_for_the_same_order() {
+        let (mut kitchen, mut log, order) = (kitchen(), RemakeLog::new(), lunch());
+        let other = Order::new(OrderId(9), PartyId(3), Time::hm(12, 0));
+        let mut bill = Bill::for_order(&other, &BillingConfig::new());
+        let error = fix_incorrect_order(&mut kitchen, &mut log, &order, 0, RemakeReason::Cold, Some(&mut bill), Time::hm(12, 30))
+            .unwrap_err();
+        assert_eq!(error, RemakeError::WrongBill { order: OrderId(4), bill_for: OrderId(9) });
+        assert!(log.entries().is_empty());
+        assert_eq!(log.resolve(RemakeId(1), Time::hm(13, 0)), Err(RemakeError::UnknownRemake(RemakeId(1))));
+    }
+
+    #[test]
+    fn the_end_of_day_report_adds_everything_up() {
+        let (mut kitchen, mut log, order) = (kitchen(), RemakeLog::new(), lunch());
+        let mut bill = Bill::for_order(&order, &BillingConfig::new());
+        let mut remake = |item, reason, bill: Option<&mut Bill>| {
+            fix_incorrect_order(&mut kitchen, &mut log, &order, item, reason, bill, Time::hm(12, 30)).unwrap()
+        };
+        let first = remake(0, RemakeReason::Overcooked, Some(&mut bill));
+        remake(1, RemakeReason::Overcooked, Some(&mut bill));
+        remake(2, RemakeReason::WrongItem, None);
+        log.resolve(first, Time::hm(12, 45)).unwrap();
+
+        let report = log.report();
+        assert_eq!(report.remakes, 3);
+        assert_eq!(report.unresolved, 2);
+        assert_eq!(report.comped, Money::from_cents(1700));
+        assert_eq!(report.by_reason, [(String::from("overcooked"), 2), (String::from("wrong item"), 1)]);
+        assert_eq!(report.by_item, [(String::from("Breakfast"), 2), (String::from("Soup of the day"), 1)]);
+        assert_eq!(
+            report.to_string(),
+            "3 remakes (2 still to go out), 17.00 comped\n\
+             \x20 overcooked                2\n\
+             \x20 wrong item                1\n\
+             \x20 Breakfast                 2\n\
+             \x20 Soup of the day           1\n"
+        );
+        assert_eq!(log.for_order(OrderId(4)).count(), 3);
+        assert_eq!(log.for_order(OrderId(5)).count(), 0);
+    }
+}
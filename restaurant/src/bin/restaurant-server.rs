@@ -0,0 +1,45 @@
+// Runs the restaurant's HTTP API. See src/server.rs for the endpoints.
+//
+// Binaries in src/bin are built alongside the library, and use it like any
+// other crate would. Start it with
+//
+//   cargo run --bin restaurant-server -- [log file] [address]
+//
+// The log defaults to restaurant.log in the current directory, and the
+// address to 127.0.0.1:7878 (the port the book's web server uses).
+
+use std::env;
+use std::path::PathBuf;
+use std::process;
+
+use restaurant::menu::Menu;
+use restaurant::server::Server;
+use restaurant::storage::{State, Store};
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let path = PathBuf::from(args.next().unwrap_or_else(|| String::from("restaurant.log")));
+    let addr = args.next().unwrap_or_else(|| String::from("127.0.0.1:7878"));
+
+    let store = Store::open(&path, State::house()).unwrap_or_else(|e| {
+        eprintln!("Couldn't open {}: {}", path.display(), e);
+        process::exit(1);
+    });
+    if store.recovered_bytes() > 0 {
+        eprintln!(
+            "Dropped {} bytes of a half-written change from the end of {}",
+            store.recovered_bytes(),
+            path.display()
+        );
+    }
+
+    let server = Server::bind(&addr, store, Menu::house()).unwrap_or_else(|e| {
+        eprintln!("Couldn't listen on {}: {}", addr, e);
+        process::exit(1);
+    });
+    match server.local_addr() {
+        Ok(addr) => println!("Listening on http://{}", addr),
+        Err(_) => println!("Listening on http://{}", addr),
+    }
+    server.run();
+}
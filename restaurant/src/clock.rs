@@ -0,0 +1,149 @@
+// Times of day and calendar dates, used to record when things happen.
+//
+// Restaurants work in minutes, and only ever need to know about a single
+// day's service, so rather than pulling in a full date/time library we store
+// the number of minutes since midnight. Keeping time as a plain value (rather
+// than reading the system clock) also means a whole evening can be replayed
+// or simulated without waiting for it.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct Time(u32);
+
+impl Time {
+    // As with the Guess type from the error handling chapter, an invalid
+    // time is a bug in the caller, so we panic rather than returning a Result
+    pub fn hm(hours: u32, minutes: u32) -> Time {
+        if hours > 23 || minutes > 59 {
+            panic!("Time must be between 00:00 and 23:59, got {hours}:{minutes}.");
+        }
+        Time(hours * 60 + minutes)
+    }
+
+    pub fn from_minutes(minutes: u32) -> Time {
+        Time(minutes)
+    }
+
+    pub fn minutes(&self) -> u32 {
+        self.0
+    }
+
+    // The number of minutes from `earlier` until this time, or zero if
+    // `earlier` is actually later
+    pub fn minutes_since(&self, earlier: Time) -> u32 {
+        self.0.saturating_sub(earlier.0)
+    }
+
+    pub fn plus_minutes(&self, minutes: u32) -> Time {
+        Time(self.0 + minutes)
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02}:{:02}", self.0 / 60 % 24, self.0 % 60)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseTimeError(String);
+
+impl fmt::Display for ParseTimeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "\"{}\" is not a valid time, expected something like 19:30", self.0)
+    }
+}
+
+impl std::error::Error for ParseTimeError {}
+
+// Reads a time written the same way Display writes one, as HH:MM. Unlike
+// Time::hm, a bad time here comes from outside the program, so it's an error
+// rather than a panic.
+impl FromStr for Time {
+    type Err = ParseTimeError;
+
+    fn from_str(s: &str) -> Result<Time, ParseTimeError> {
+        let error = || ParseTimeError(String::from(s));
+        let (hours, minutes) = s.trim().split_once(':').ok_or_else(error)?;
+        if minutes.len() != 2 {
+            return Err(error());
+        }
+        let hours: u32 = hours.parse().map_err(|_| error())?;
+        let minutes: u32 = minutes.parse().map_err(|_| error())?;
+        if hours > 23 || minutes > 59 {
+            return Err(error());
+        }
+        Ok(Time(hours * 60 + minutes))
+    }
+}
+
+// A calendar date. Only what the menu needs is supported: checking a date is
+// real, and finding out which month it falls in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    year: i32,
+    month: u32,
+    day: u32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidDate {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl fmt::Display for InvalidDate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02} is not a real date", self.year, self.month, self.day)
+    }
+}
+
+impl std::error::Error for InvalidDate {}
+
+fn is_leap_year(year: i32) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    match month {
+        4 | 6 | 9 | 11 => 30,
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        _ => 31,
+    }
+}
+
+impl Date {
+    // Dates come from outside the program (a booking form, a command line),
+    // so an invalid one is reported as an error rather than a panic
+    pub fn new(year: i32, month: u32, day: u32) -> Result<Date, InvalidDate> {
+        if !(1..=12).contains(&month) || day == 0 || day > days_in_month(year, month) {
+            return Err(InvalidDate { year, month, day });
+        }
+        Ok(Date { year, month, day })
+    }
+
+    pub fn year(&self) -> i32 {
+        self.year
+    }
+
+    // 1 for January through to 12 for December
+    pub fn month(&self) -> u32 {
+        self.month
+    }
+
+    pub fn day(&self) -> u32 {
+        self.day
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
@@ -0,0 +1,793 @@
+// Turning an order into a bill, and settling it.
+//
+// A bill is made up of:
+//   subtotal        the order's items added together, less any discounts
+//   taxes           each tax rule applied to the items it covers
+//   service charge  a percentage of the subtotal, if the restaurant adds one
+//   tip             whatever the guests choose to add
+//
+// When something has to be remade, the restaurant may comp it: a negative
+// line is added to take one of the item off the bill, and the taxes and
+// service charge are worked out again without it.
+//
+// Discounts come from the pricing rules in the BillingConfig (see
+// pricing.rs). They're worked out again whenever the bill changes, from the
+// time the order was placed and any vouchers or loyalty card the guests
+// have given us, and taxes are charged on the discounted prices.
+//
+// What a bill was worked out to be is kept as its Charges. Saving those
+// alongside each payment means a bill can be read back exactly as it was
+// paid, even if the pricing rules have changed since.
+//
+// Percentages are given in basis points (1/100th of a percent) so that rates
+// like 12.5% can be written exactly as 1250. Each tax and charge is rounded
+// to the cent once, on its total, rather than line by line.
+//
+// Bills can be split evenly, by the items each guest had, or by custom
+// shares. However a bill is split, the shares always add back up to exactly
+// the total: see Money::allocate for how the odd cents are handed out.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::clock::Time;
+use crate::front_of_house::pricing::{Discount, PricingRules};
+use crate::front_of_house::serving::{Order, OrderError, OrderId};
+use crate::money::Money;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TaxRule {
+    pub name: String,
+    pub basis_points: u32,
+    // Menu ids this tax applies to, or None if it applies to everything
+    pub applies_to: Option<Vec<String>>,
+}
+
+impl TaxRule {
+    pub fn new(name: &str, basis_points: u32) -> TaxRule {
+        TaxRule {
+            name: String::from(name),
+            basis_points,
+            applies_to: None,
+        }
+    }
+
+    // Limits the tax to only some menu items
+    pub fn only_on(mut self, menu_ids: &[&str]) -> TaxRule {
+        self.applies_to = Some(menu_ids.iter().map(|id| String::from(*id)).collect());
+        self
+    }
+
+    fn applies(&self, menu_id: &str) -> bool {
+        match &self.applies_to {
+            None => true,
+            Some(ids) => ids.iter().any(|id| id == menu_id),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BillingConfig {
+    pub taxes: Vec<TaxRule>,
+    pub service_charge_basis_points: u32,
+    pub pricing: PricingRules,
+}
+
+impl BillingConfig {
+    pub fn new() -> BillingConfig {
+        BillingConfig::default()
+    }
+
+    pub fn tax(mut self, rule: TaxRule) -> BillingConfig {
+        self.taxes.push(rule);
+        self
+    }
+
+    pub fn service_charge(mut self, basis_points: u32) -> BillingConfig {
+        self.service_charge_basis_points = basis_points;
+        self
+    }
+
+    pub fn pricing(mut self, rules: PricingRules) -> BillingConfig {
+        self.pricing = rules;
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BillLine {
+    pub menu_id: String,
+    pub description: String,
+    pub quantity: u32,
+    pub unit_price: Money,
+    pub total: Money,
+    // For a comp, the line it takes the item off
+    pub comp_of: Option<usize>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TenderKind {
+    Cash,
+    Card,
+    Voucher,
+}
+
+// One payment towards a bill
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tender {
+    pub kind: TenderKind,
+    pub amount: Money,
+    pub at: Time,
+}
+
+impl Tender {
+    pub fn new(kind: TenderKind, amount: Money, at: Time) -> Tender {
+        Tender { kind, amount, at }
+    }
+}
+
+// What happened when a tender was recorded. `change` is only ever non-zero
+// for cash, as that's the only way to hand over more than is owed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TenderReceipt {
+    pub applied: Money,
+    pub change: Money,
+    pub balance: Money,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BillingError {
+    AlreadyPaid,
+    // Tenders have to be for more than nothing
+    InvalidAmount(Money),
+    // Guests can leave nothing, but they can't take money off the bill
+    NegativeTip(Money),
+    // Cards and vouchers can't be used to pay more than is owed
+    Overpayment { kind: TenderKind, amount: Money, balance: Money },
+    NoGuests,
+    // A bill line was given to a guest who isn't part of the split, or given
+    // to nobody at all
+    BadAssignment { line: usize },
+    // There's no such line, it's a comp itself, or every one of the item on
+    // it has already been comped
+    CantComp { line: usize },
+    // There's no pricing rule for the voucher code or loyalty tier
+    UnknownVoucher(String),
+    UnknownTier(String),
+    // The same voucher can't be used twice on one bill
+    VoucherUsed(String),
+    Order(OrderError),
+}
+
+impl fmt::Display for BillingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BillingError::AlreadyPaid => write!(f, "the bill has already been paid"),
+            BillingError::InvalidAmount(amount) => write!(f, "{} is not a valid payment", amount),
+            BillingError::NegativeTip(tip) => write!(f, "a tip of {} can't be negative", tip),
+            BillingError::Overpayment { kind, amount, balance } => write!(
+                f,
+                "can't take {} by {:?} when only {} is owed",
+                amount, kind, balance
+            ),
+            BillingError::NoGuests => write!(f, "a bill has to be split between at least one guest"),
+            BillingError::BadAssignment { line } => {
+                write!(f, "line {} of the bill isn't assigned to a valid guest", line)
+            }
+            BillingError::CantComp { line } => write!(f, "line {} of the bill can't be comped", line),
+            BillingError::UnknownVoucher(code) => write!(f, "{} is not a voucher we take", code),
+            BillingError::UnknownTier(tier) => write!(f, "there's no {} tier of the loyalty scheme", tier),
+            BillingError::VoucherUsed(code) => write!(f, "voucher {} is already on the bill", code),
+            BillingError::Order(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for BillingError {}
+
+// Lets the ? operator turn an OrderError into a BillingError for us
+impl From<OrderError> for BillingError {
+    fn from(e: OrderError) -> BillingError {
+        BillingError::Order(e)
+    }
+}
+
+// One guest's part of a split bill. `items` is what they had, and `extras`
+// is their part of the taxes, service charge and tip.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Share {
+    pub guest: usize,
+    pub items: Money,
+    pub extras: Money,
+    pub total: Money,
+}
+
+// Everything the billing rules added to or took off a bill's lines
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Charges {
+    pub discounts: Vec<Discount>,
+    pub taxes: Vec<(String, Money)>,
+    pub service_charge: Money,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bill {
+    pub order: OrderId,
+    config: BillingConfig,
+    lines: Vec<BillLine>,
+    // When the order was placed, for discounts that depend on the time
+    placed_at: Time,
+    vouchers: Vec<String>,
+    loyalty: Option<String>,
+    discounts: Vec<Discount>,
+    taxes: Vec<(String, Money)>,
+    service_charge: Money,
+    tip: Money,
+    tenders: Vec<Tender>,
+}
+
+impl Bill {
+    pub fn for_order(order: &Order, config: &BillingConfig) -> Bill {
+        let lines: Vec<BillLine> = order
+            .items()
+            .iter()
+            .map(|item| BillLine {
+                menu_id: item.menu_id.clone(),
+                description: item.name.clone(),
+                quantity: item.quantity,
+                unit_price: item.unit_price,
+                total: item.total(),
+                comp_of: None,
+            })
+            .collect();
+
+        let mut bill = Bill {
+            order: order.id,
+            config: config.clone(),
+            lines,
+            placed_at: order.history()[0].1,
+            vouchers: Vec::new(),
+            loyalty: None,
+            discounts: Vec::new(),
+            taxes: Vec::new(),
+            service_charge: Money::ZERO,
+            tip: Money::ZERO,
+            tenders: Vec::new(),
+        };
+        bill.calculate_charges();
+        bill
+    }
+
+    fn calculate_charges(&mut self) {
+        self.discounts = self.config.pricing.apply(
+            &self.lines,
+            self.placed_at,
+            &self.vouchers,
+            self.loyalty.as_deref(),
+        );
+        let lines = &self.lines;
+        let discounts = &self.discounts;
+        self.taxes = self
+            .config
+            .taxes
+            .iter()
+            .map(|rule| {
+                let taxable: Money = lines
+                    .iter()
+                    .filter(|l| rule.applies(&l.menu_id))
+                    .map(|l| l.total)
+                    .sum::<Money>()
+                    + discounts
+                        .iter()
+                        .filter(|d| rule.applies(&lines[d.line].menu_id))
+                        .map(|d| d.amount)
+                        .sum();
+                (rule.name.clone(), taxable.percent(rule.basis_points))
+            })
+            .collect();
+        self.service_charge = self.subtotal().percent(self.config.service_charge_basis_points);
+    }
+
+    // Checks that one of the item on a line could be comped, and says how
+    // much that would take off
+    pub fn can_comp(&self, line: usize) -> Result<Money, BillingError> {
+        if self.is_settled() {
+            return Err(BillingError::AlreadyPaid);
+        }
+        let original = match self.lines.get(line) {
+            Some(l) if l.comp_of.is_none() => l,
+            _ => return Err(BillingError::CantComp { line }),
+        };
+        let comped: u32 = self
+            .lines
+            .iter()
+            .filter(|l| l.comp_of == Some(line))
+            .map(|l| l.quantity)
+            .sum();
+        if comped >= original.quantity {
+            return Err(BillingError::CantComp { line });
+        }
+        Ok(original.unit_price)
+    }
+
+    // Takes one of the item on a line off the bill
+    pub fn comp(&mut self, line: usize, reason: &str) -> Result<Money, BillingError> {
+        let amount = self.can_comp(line)?;
+        let original = &self.lines[line];
+        self.lines.push(BillLine {
+            menu_id: original.menu_id.clone(),
+            description: format!("Comp: {} ({})", original.description, reason),
+            quantity: 1,
+            unit_price: -amount,
+            total: -amount,
+            comp_of: Some(line),
+        });
+        self.calculate_charges();
+        Ok(amount)
+    }
+
+    // Adds a discount voucher the guests have given us
+    pub fn redeem_voucher(&mut self, code: &str) -> Result<(), BillingError> {
+        if self.is_settled() {
+            return Err(BillingError::AlreadyPaid);
+        }
+        if !self.config.pricing.has_voucher(code) {
+            return Err(BillingError::UnknownVoucher(String::from(code)));
+        }
+        if self.vouchers.iter().any(|v| v == code) {
+            return Err(BillingError::VoucherUsed(String::from(code)));
+        }
+        self.vouchers.push(String::from(code));
+        self.calculate_charges();
+        Ok(())
+    }
+
+    // The guests showed a loyalty card of this tier
+    pub fn set_loyalty(&mut self, tier: &str) -> Result<(), BillingError> {
+        if self.is_settled() {
+            return Err(BillingError::AlreadyPaid);
+        }
+        if !self.config.pricing.has_tier(tier) {
+            return Err(BillingError::UnknownTier(String::from(tier)));
+        }
+        self.loyalty = Some(String::from(tier));
+        self.calculate_charges();
+        Ok(())
+    }
+
+    pub fn lines(&self) -> &[BillLine] {
+        &self.lines
+    }
+
+    pub fn vouchers(&self) -> &[String] {
+        &self.vouchers
+    }
+
+    pub fn loyalty(&self) -> Option<&str> {
+        self.loyalty.as_deref()
+    }
+
+    // Every discount, in the order the rules were applied
+    pub fn discounts(&self) -> &[Discount] {
+        &self.discounts
+    }
+
+    // The discounts on one line, which say which rules fired on it and why
+    pub fn discounts_on(&self, line: usize) -> impl Iterator<Item = &Discount> {
+        self.discounts.iter().filter(move |d| d.line == line)
+    }
+
+    pub fn charges(&self) -> Charges {
+        Charges {
+            discounts: self.discounts.clone(),
+            taxes: self.taxes.clone(),
+            service_charge: self.service_charge,
+        }
+    }
+
+    // Puts back charges the bill was worked out to have before, in place of
+    // what the current rules make them. They stay until the bill next
+    // changes (a comp, say), when it's worked out again.
+    pub fn restore_charges(&mut self, charges: Charges) {
+        self.discounts = charges.discounts;
+        self.taxes = charges.taxes;
+        self.service_charge = charges.service_charge;
+    }
+
+    pub fn taxes(&self) -> &[(String, Money)] {
+        &self.taxes
+    }
+
+    pub fn subtotal(&self) -> Money {
+        self.lines.iter().map(|l| l.total).sum::<Money>() + self.discounts.iter().map(|d| d.amount).sum()
+    }
+
+    pub fn service_charge(&self) -> Money {
+        self.service_charge
+    }
+
+    pub fn tip(&self) -> Money {
+        self.tip
+    }
+
+    // Tips can be changed until the bill is paid. A bill that comes to
+    // nothing (before anything's been ordered, say) hasn't been paid, so it
+    // can still have a tip added.
+    pub fn set_tip(&mut self, tip: Money) -> Result<(), BillingError> {
+        if self.is_paid() {
+            return Err(BillingError::AlreadyPaid);
+        }
+        if tip.is_negative() {
+            return Err(BillingError::NegativeTip(tip));
+        }
+        self.tip = tip;
+        Ok(())
+    }
+
+    // A tip worked out as a percentage of the subtotal
+    pub fn set_tip_percent(&mut self, basis_points: u32) -> Result<(), BillingError> {
+        self.set_tip(self.subtotal().percent(basis_points))
+    }
+
+    // Everything on top of the items themselves
+    fn extras(&self) -> Money {
+        self.taxes.iter().map(|(_, amount)| *amount).sum::<Money>() + self.service_charge + self.tip
+    }
+
+    pub fn total(&self) -> Money {
+        self.subtotal() + self.extras()
+    }
+
+    pub fn tenders(&self) -> &[Tender] {
+        &self.tenders
+    }
+
+    pub fn paid(&self) -> Money {
+        self.tenders.iter().map(|t| t.amount).sum()
+    }
+
+    pub fn balance(&self) -> Money {
+        self.total() - self.paid()
+    }
+
+    pub fn is_settled(&self) -> bool {
+        !self.balance().is_positive()
+    }
+
+    fn is_paid(&self) -> bool {
+        !self.tenders.is_empty() && self.is_settled()
+    }
+
+    // Records a payment against the bill. Cash can be for more than is owed,
+    // in which case only the balance is applied and the rest is change.
+    pub fn record_tender(&mut self, tender: Tender) -> Result<TenderReceipt, BillingError> {
+        let balance = self.balance();
+        if !balance.is_positive() {
+            return Err(BillingError::AlreadyPaid);
+        }
+        if !tender.amount.is_positive() {
+            return Err(BillingError::InvalidAmount(tender.amount));
+        }
+
+        let (applied, change) = if tender.amount > balance {
+            if tender.kind != TenderKind::Cash {
+                return Err(BillingError::Overpayment {
+                    kind: tender.kind,
+                    amount: tender.amount,
+                    balance,
+                });
+            }
+            (balance, tender.amount - balance)
+        } else {
+            (tender.amount, Money::ZERO)
+        };
+
+        self.tenders.push(Tender {
+            amount: applied,
+            ..tender
+        });
+        Ok(TenderReceipt {
+            applied,
+            change,
+            balance: self.balance(),
+        })
+    }
+
+    // Splits the whole bill into shares with the given weights, so [2, 1]
+    // means the first guest pays twice as much as the second
+    pub fn split_by_shares(&self, weights: &[u64]) -> Result<Vec<Share>, BillingError> {
+        if weights.is_empty() {
+            return Err(BillingError::NoGuests);
+        }
+        let items = self.subtotal().allocate(weights);
+        let extras = self.extras().allocate(weights);
+        Ok(items
+            .into_iter()
+            .zip(extras)
+            .enumerate()
+            .map(|(guest, (items, extras))| Share {
+                guest,
+                items,
+                extras,
+                total: items + extras,
+            })
+            .collect())
+    }
+
+    pub fn split_evenly(&self, guests: usize) -> Result<Vec<Share>, BillingError> {
+        self.split_by_shares(&vec![1; guests])
+    }
+
+    // Splits the bill by who had what. `assignments` has an entry for each
+    // line of the bill, listing the guests (numbered from 0) who shared it.
+    // A shared line is divided evenly between the guests who had it. Comps
+    // and discounts don't need an entry: they go to whoever shared the line
+    // they're taken off. The extras are then divided in proportion to how
+    // much each guest had.
+    pub fn split_by_item(
+        &self,
+        guests: usize,
+        assignments: &[Vec<usize>],
+    ) -> Result<Vec<Share>, BillingError> {
+        if guests == 0 {
+            return Err(BillingError::NoGuests);
+        }
+        // Comps are always added after the lines they comp
+        let items_ordered = self.lines.iter().filter(|l| l.comp_of.is_none()).count();
+        if assignments.len() != items_ordered {
+            return Err(BillingError::BadAssignment {
+                line: assignments.len().min(items_ordered),
+            });
+        }
+
+        let mut items = vec![Money::ZERO; guests];
+        let amounts = self
+            .lines
+            .iter()
+            .enumerate()
+            .map(|(line, l)| (line, l.comp_of.unwrap_or(line), l.total))
+            .chain(self.discounts.iter().map(|d| (d.line, d.line, d.amount)));
+        for (line, shared, amount) in amounts {
+            let sharers = &assignments[shared];
+            if sharers.is_empty() || sharers.iter().any(|&g| g >= guests) {
+                return Err(BillingError::BadAssignment { line });
+            }
+            for (&guest, part) in sharers.iter().zip(amount.allocate(&vec![1; sharers.len()])) {
+                items[guest] += part;
+            }
+        }
+
+        let weights: Vec<u64> = items.iter().map(|m| m.cents().max(0) as u64).collect();
+        let extras = self.extras().allocate(&weights);
+        Ok(items
+            .into_iter()
+            .zip(extras)
+            .enumerate()
+            .map(|(guest, (items, extras))| Share {
+                guest,
+                items,
+                extras,
+                total: items + extras,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::back_of_house::menu::Menu;
+    use crate::front_of_house::hosting::PartyId;
+    use crate::front_of_house::serving::OrderItem;
+
+    fn soup_bill() -> Bill {
+        let mut order = Order::new(OrderId(1), PartyId(1), Time::hm(12, 0));
+        order.add_item(OrderItem::from_menu(&Menu::house(), "soup", &[], 2).unwrap()).unwrap();
+        Bill::for_order(&order, &BillingConfig::new())
+    }
+
+    // Two soups and a salad (16.50) with 20% tax and a 12.5% service charge
+    fn lunch_bill() -> Bill {
+        let menu = Menu::house();
+        let mut order = Order::new(OrderId(2), PartyId(2), Time::hm(12, 0));
+        order.add_item(OrderItem::from_menu(&menu, "soup", &[], 2).unwrap()).unwrap();
+        order.add_item(OrderItem::from_menu(&menu, "salad", &["Ranch"], 1).unwrap()).unwrap();
+        let config = BillingConfig::new().tax(TaxRule::new("VAT", 2000)).service_charge(1250);
+        Bill::for_order(&order, &config)
+    }
+
+    fn card(cents: i64) -> Tender {
+        Tender::new(TenderKind::Card, Money::from_cents(cents), Time::hm(13, 0))
+    }
+
+    fn cash(cents: i64) -> Tender {
+        Tender::new(TenderKind::Cash, Money::from_cents(cents), Time::hm(13, 0))
+    }
+
+    fn totals(shares: &[Share]) -> Vec<Money> {
+        shares.iter().map(|s| s.total).collect()
+    }
+
+    #[test]
+    fn a_tip_is_added_to_the_total() {
+        let mut bill = soup_bill();
+        let before = bill.total();
+        bill.set_tip(Money::from_cents(250)).unwrap();
+        assert_eq!(bill.total(), before + Money::from_cents(250));
+        bill.set_tip(Money::ZERO).unwrap();
+        assert_eq!(bill.total(), before);
+    }
+
+    #[test]
+    fn a_tip_cant_be_negative() {
+        let mut bill = soup_bill();
+        bill.set_tip(Money::from_cents(100)).unwrap();
+        let error = bill.set_tip(Money::from_cents(-500)).unwrap_err();
+        assert_eq!(error, BillingError::NegativeTip(Money::from_cents(-500)));
+        assert_eq!(error.to_string(), "a tip of -5.00 can't be negative");
+        assert_eq!(bill.tip(), Money::from_cents(100));
+    }
+
+    #[test]
+    fn taxes_and_the_service_charge_are_worked_out_on_the_subtotal() {
+        let bill = lunch_bill();
+        assert_eq!(bill.subtotal(), Money::from_cents(1650));
+        assert_eq!(bill.taxes(), [(String::from("VAT"), Money::from_cents(330))]);
+        // 12.5% of 16.50 is 2.0625
+        assert_eq!(bill.service_charge(), Money::from_cents(206));
+        assert_eq!(bill.total(), Money::from_cents(2186));
+    }
+
+    #[test]
+    fn a_tax_can_be_limited_to_some_items() {
+        let menu = Menu::house();
+        let mut order = Order::new(OrderId(3), PartyId(3), Time::hm(12, 0));
+        order.add_item(OrderItem::from_menu(&menu, "soup", &[], 2).unwrap()).unwrap();
+        order.add_item(OrderItem::from_menu(&menu, "salad", &["Ranch"], 1).unwrap()).unwrap();
+        let config = BillingConfig::new().tax(TaxRule::new("Soup duty", 1000).only_on(&["soup"]));
+        let bill = Bill::for_order(&order, &config);
+        assert_eq!(bill.taxes(), [(String::from("Soup duty"), Money::from_cents(105))]);
+        assert_eq!(bill.total(), Money::from_cents(1755));
+    }
+
+    #[test]
+    fn a_tip_can_be_a_percentage_of_the_subtotal() {
+        let mut bill = lunch_bill();
+        // 15% of 16.50 is 2.475, which rounds up
+        bill.set_tip_percent(1500).unwrap();
+        assert_eq!(bill.tip(), Money::from_cents(248));
+        assert_eq!(bill.total(), Money::from_cents(2434));
+    }
+
+    #[test]
+    fn even_splits_add_up_to_the_total() {
+        let bill = lunch_bill();
+        let shares = bill.split_evenly(3).unwrap();
+        // The 5.36 of extras doesn't divide by three, so the odd cents go to
+        // the first guests
+        assert_eq!(
+            totals(&shares),
+            [Money::from_cents(729), Money::from_cents(729), Money::from_cents(728)]
+        );
+        for guests in 1..=9 {
+            let shares = bill.split_evenly(guests).unwrap();
+            assert_eq!(shares.len(), guests);
+            assert_eq!(shares.iter().map(|s| s.total).sum::<Money>(), bill.total());
+        }
+        assert_eq!(bill.split_evenly(0), Err(BillingError::NoGuests));
+    }
+
+    #[test]
+    fn item_splits_charge_each_guest_for_what_they_had() {
+        let bill = lunch_bill();
+        // Both guests shared the soups, and the second also had the salad
+        let shares = bill.split_by_item(2, &[vec![0, 1], vec![1]]).unwrap();
+        assert_eq!(shares[0].items, Money::from_cents(525));
+        assert_eq!(shares[1].items, Money::from_cents(1125));
+        // The extras are shared 525 to 1125, and the odd cent goes to the
+        // first guest, who lost more to rounding
+        assert_eq!(shares[0].extras, Money::from_cents(171));
+        assert_eq!(shares[1].extras, Money::from_cents(365));
+        assert_eq!(totals(&shares).into_iter().sum::<Money>(), bill.total());
+
+        assert_eq!(
+            bill.split_by_item(2, &[vec![0, 2], vec![1]]),
+            Err(BillingError::BadAssignment { line: 0 })
+        );
+        assert_eq!(
+            bill.split_by_item(2, &[vec![0]]),
+            Err(BillingError::BadAssignment { line: 1 })
+        );
+    }
+
+    #[test]
+    fn comps_go_to_whoever_had_the_item() {
+        let mut bill = lunch_bill();
+        bill.comp(1, "wilted").unwrap();
+        let shares = bill.split_by_item(2, &[vec![0], vec![1]]).unwrap();
+        assert_eq!(shares[0].items, Money::from_cents(1050));
+        assert_eq!(shares[1].items, Money::ZERO);
+        assert_eq!(shares[1].extras, Money::ZERO);
+        assert_eq!(totals(&shares).into_iter().sum::<Money>(), bill.total());
+    }
+
+    #[test]
+    fn weighted_splits_add_up_to_the_total() {
+        let bill = lunch_bill();
+        let shares = bill.split_by_shares(&[2, 1]).unwrap();
+        assert_eq!(totals(&shares), [Money::from_cents(1457), Money::from_cents(729)]);
+        for weights in [&[1, 1, 1][..], &[3, 2, 2], &[7, 0, 1], &[0, 0], &[1; 11]] {
+            let shares = bill.split_by_shares(weights).unwrap();
+            assert_eq!(shares.iter().map(|s| s.total).sum::<Money>(), bill.total());
+        }
+        assert_eq!(bill.split_by_shares(&[]), Err(BillingError::NoGuests));
+    }
+
+    #[test]
+    fn a_bill_can_be_paid_with_several_tenders() {
+        let mut bill = lunch_bill();
+        let receipt = bill.record_tender(card(1000)).unwrap();
+        assert_eq!(receipt.applied, Money::from_cents(1000));
+        assert_eq!(receipt.change, Money::ZERO);
+        assert_eq!(receipt.balance, Money::from_cents(1186));
+
+        let voucher = Tender::new(TenderKind::Voucher, Money::from_cents(500), Time::hm(13, 0));
+        assert_eq!(bill.record_tender(voucher).unwrap().balance, Money::from_cents(686));
+        assert_eq!(bill.record_tender(card(686)).unwrap().balance, Money::ZERO);
+        assert_eq!(bill.tenders().len(), 3);
+        assert_eq!(bill.paid(), bill.total());
+        assert!(bill.is_settled());
+    }
+
+    #[test]
+    fn only_cash_can_be_for_more_than_is_owed() {
+        let mut bill = lunch_bill();
+        bill.record_tender(card(1000)).unwrap();
+        assert_eq!(
+            bill.record_tender(card(2000)),
+            Err(BillingError::Overpayment {
+                kind: TenderKind::Card,
+                amount: Money::from_cents(2000),
+                balance: Money::from_cents(1186),
+            })
+        );
+        assert_eq!(bill.record_tender(card(0)), Err(BillingError::InvalidAmount(Money::ZERO)));
+
+        let receipt = bill.record_tender(cash(2000)).unwrap();
+        assert_eq!(receipt.applied, Money::from_cents(1186));
+        assert_eq!(receipt.change, Money::from_cents(814));
+        assert_eq!(receipt.balance, Money::ZERO);
+        // Only what was applied counts as paid
+        assert_eq!(bill.paid(), bill.total());
+    }
+
+    #[test]
+    fn nothing_more_can_be_paid_or_changed_once_the_bill_is_paid() {
+        let config = BillingConfig::new().pricing(PricingRules::house());
+        let mut order = Order::new(OrderId(4), PartyId(4), Time::hm(12, 0));
+        order.add_item(OrderItem::from_menu(&Menu::house(), "soup", &[], 1).unwrap()).unwrap();
+        let mut bill = Bill::for_order(&order, &config);
+        bill.set_tip(Money::from_cents(100)).unwrap();
+        let total = bill.total();
+        bill.record_tender(card(total.cents())).unwrap();
+
+        assert_eq!(bill.record_tender(cash(100)), Err(BillingError::AlreadyPaid));
+        assert_eq!(bill.set_tip(Money::from_cents(2000)), Err(BillingError::AlreadyPaid));
+        assert_eq!(bill.set_tip_percent(1500), Err(BillingError::AlreadyPaid));
+        assert_eq!(bill.redeem_voucher("FRIENDS"), Err(BillingError::AlreadyPaid));
+        assert_eq!(bill.comp(0, "cold"), Err(BillingError::AlreadyPaid));
+        assert_eq!(bill.tip(), Money::from_cents(100));
+        assert_eq!(bill.total(), total);
+        assert_eq!(bill.balance(), Money::ZERO);
+    }
+
+    #[test]
+    fn a_bill_with_nothing_on_it_can_still_be_tipped() {
+        let order = Order::new(OrderId(5), PartyId(5), Time::hm(12, 0));
+        let mut bill = Bill::for_order(&order, &BillingConfig::new());
+        assert!(bill.is_settled());
+        bill.set_tip(Money::from_cents(200)).unwrap();
+        assert_eq!(bill.balance(), Money::from_cents(200));
+    }
+}
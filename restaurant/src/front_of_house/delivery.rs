@@ -0,0 +1,615 @@
+// Delivery orders: getting food to guests who aren't in the restaurant.
+//
+// Addresses are points on the town's street grid, counted in blocks east
+// (x) and north (y) of the restaurant, which sits at (0, 0). Drivers can
+// only go along the streets, so the distance between two points is the
+// number of blocks across plus the number up or down, and each block takes
+// the same number of minutes to drive.
+//
+// An order joins the queue once the kitchen has it ready. Each time
+// `dispatch` is called, every driver who is back at the restaurant takes
+// the oldest waiting order, plus the nearest others within `batch_blocks`
+// of it, up to `max_stops` in all. The driver then goes to whichever stop
+// is closest to where they are, again and again until every stop has been
+// visited (ties go to the order that was queued first). That isn't always
+// the shortest route, but it's quick to work out and always gives the same
+// route for the same orders.
+//
+// A delivery goes from Waiting to Dispatched, then either to Delivered (the
+// order is served) or Failed (nobody was in, say, and the order is voided
+// so the bill can be sorted out).
+
+use std::fmt;
+
+use crate::clock::Time;
+use crate::front_of_house::serving::{Order, OrderBook, OrderError, OrderId, OrderStatus};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Point {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl Point {
+    pub const RESTAURANT: Point = Point { x: 0, y: 0 };
+
+    pub fn new(x: i32, y: i32) -> Point {
+        Point { x, y }
+    }
+
+    // How many blocks it is from here to `other` along the streets
+    pub fn blocks_to(&self, other: Point) -> u32 {
+        self.x.abs_diff(other.x) + self.y.abs_diff(other.y)
+    }
+}
+
+impl fmt::Display for Point {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "({}, {})", self.x, self.y)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Address {
+    pub street: String,
+    pub at: Point,
+}
+
+impl Address {
+    pub fn new(street: &str, x: i32, y: i32) -> Address {
+        Address {
+            street: String::from(street),
+            at: Point::new(x, y),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DeliveryId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct DriverId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeliveryStatus {
+    Waiting,
+    Dispatched,
+    Delivered,
+    Failed,
+}
+
+impl DeliveryStatus {
+    pub fn can_become(&self, next: DeliveryStatus) -> bool {
+        use DeliveryStatus::*;
+        matches!((self, next), (Waiting, Dispatched) | (Dispatched, Delivered | Failed))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delivery {
+    pub id: DeliveryId,
+    pub order: OrderId,
+    pub address: Address,
+    pub queued_at: Time,
+    status: DeliveryStatus,
+    driver: Option<DriverId>,
+    // When the driver should get there, once it's been dispatched
+    eta: Option<Time>,
+    history: Vec<(DeliveryStatus, Time)>,
+    failure: Option<String>,
+}
+
+impl Delivery {
+    pub fn status(&self) -> DeliveryStatus {
+        self.status
+    }
+
+    pub fn driver(&self) -> Option<DriverId> {
+        self.driver
+    }
+
+    pub fn eta(&self) -> Option<Time> {
+        self.eta
+    }
+
+    pub fn history(&self) -> &[(DeliveryStatus, Time)] {
+        &self.history
+    }
+
+    // Why the delivery failed, if it did
+    pub fn failure(&self) -> Option<&str> {
+        self.failure.as_deref()
+    }
+
+    fn transition(&mut self, to: DeliveryStatus, at: Time) -> Result<(), DeliveryError> {
+        if !self.status.can_become(to) {
+            return Err(DeliveryError::IllegalTransition {
+                delivery: self.id,
+                from: self.status,
+                to,
+            });
+        }
+        self.status = to;
+        self.history.push((to, at));
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Driver {
+    pub id: DriverId,
+    pub name: String,
+    // When they're next back at the restaurant and free to go out again
+    pub free_at: Time,
+}
+
+// One trip out by a driver: the stops in the order they're visited, with
+// the time they should reach each one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Run {
+    pub driver: DriverId,
+    pub leaves_at: Time,
+    pub stops: Vec<(DeliveryId, Time)>,
+    pub back_at: Time,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeliveryError {
+    UnknownDelivery(DeliveryId),
+    // Only food the kitchen has finished can go out
+    NotReady { order: OrderId, status: OrderStatus },
+    AlreadyQueued(OrderId),
+    IllegalTransition { delivery: DeliveryId, from: DeliveryStatus, to: DeliveryStatus },
+    Order(OrderError),
+}
+
+impl fmt::Display for DeliveryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeliveryError::UnknownDelivery(id) => write!(f, "there is no delivery {}", id.0),
+            DeliveryError::NotReady { order, status } => {
+                write!(f, "order {} is {:?}, and only ready orders can go out", order.0, status)
+            }
+            DeliveryError::AlreadyQueued(order) => write!(f, "order {} is already out for delivery", order.0),
+            DeliveryError::IllegalTransition { delivery, from, to } => {
+                write!(f, "delivery {} can't go from {:?} to {:?}", delivery.0, from, to)
+            }
+            DeliveryError::Order(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for DeliveryError {}
+
+impl From<OrderError> for DeliveryError {
+    fn from(e: OrderError) -> DeliveryError {
+        DeliveryError::Order(e)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeliveryConfig {
+    pub minutes_per_block: u32,
+    // Time spent at each door handing the food over
+    pub handover_minutes: u32,
+    // How close another order has to be to join a driver's run, in blocks
+    pub batch_blocks: u32,
+    pub max_stops: usize,
+}
+
+impl Default for DeliveryConfig {
+    fn default() -> DeliveryConfig {
+        DeliveryConfig {
+            minutes_per_block: 2,
+            handover_minutes: 2,
+            batch_blocks: 4,
+            max_stops: 3,
+        }
+    }
+}
+
+impl DeliveryConfig {
+    pub fn new() -> DeliveryConfig {
+        DeliveryConfig::default()
+    }
+
+    pub fn minutes_per_block(mut self, minutes: u32) -> DeliveryConfig {
+        self.minutes_per_block = minutes;
+        self
+    }
+
+    pub fn handover_minutes(mut self, minutes: u32) -> DeliveryConfig {
+        self.handover_minutes = minutes;
+        self
+    }
+
+    // As with Guess, a run with no stops makes no sense, so this panics
+    pub fn batching(mut self, blocks: u32, max_stops: usize) -> DeliveryConfig {
+        if max_stops == 0 {
+            panic!("A driver has to be able to take at least one order, got {}.", max_stops);
+        }
+        self.batch_blocks = blocks;
+        self.max_stops = max_stops;
+        self
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Dispatcher {
+    config: DeliveryConfig,
+    drivers: Vec<Driver>,
+    deliveries: Vec<Delivery>,
+}
+
+impl Dispatcher {
+    pub fn new(config: DeliveryConfig) -> Dispatcher {
+        Dispatcher {
+            config,
+            drivers: Vec::new(),
+            deliveries: Vec::new(),
+        }
+    }
+
+    // A driver who starts their shift at `from`
+    pub fn add_driver(&mut self, name: &str, from: Time) -> DriverId {
+        let id = DriverId(self.drivers.len() as u32 + 1);
+        self.drivers.push(Driver {
+            id,
+            name: String::from(name),
+            free_at: from,
+        });
+        id
+    }
+
+    pub fn drivers(&self) -> &[Driver] {
+        &self.drivers
+    }
+
+    pub fn deliveries(&self) -> impl Iterator<Item = &Delivery> {
+        self.deliveries.iter()
+    }
+
+    pub fn get(&self, id: DeliveryId) -> Result<&Delivery, DeliveryError> {
+        self.deliveries
+            .iter()
+            .find(|d| d.id == id)
+            .ok_or(DeliveryError::UnknownDelivery(id))
+    }
+
+    fn get_mut(&mut self, id: DeliveryId) -> Result<&mut Delivery, DeliveryError> {
+        self.deliveries
+            .iter_mut()
+            .find(|d| d.id == id)
+            .ok_or(DeliveryError::UnknownDelivery(id))
+    }
+
+    // Adds a ready order to the queue for the next dispatch
+    pub fn queue(&mut self, order: &Order, address: Address, at: Time) -> Result<DeliveryId, DeliveryError> {
+        if order.status() != OrderStatus::Ready {
+            return Err(DeliveryError::NotReady {
+                order: order.id,
+                status: order.status(),
+            });
+        }
+        if self.deliveries.iter().any(|d| d.order == order.id && d.status != DeliveryStatus::Failed) {
+            return Err(DeliveryError::AlreadyQueued(order.id));
+        }
+        let id = DeliveryId(self.deliveries.len() as u32 + 1);
+        self.deliveries.push(Delivery {
+            id,
+            order: order.id,
+            address,
+            queued_at: at,
+            status: DeliveryStatus::Waiting,
+            driver: None,
+            eta: None,
+            history: vec![(DeliveryStatus::Waiting, at)],
+            failure: None,
+        });
+        Ok(id)
+    }
+
+    fn minutes_for(&self, blocks: u32) -> u32 {
+        blocks * self.config.minutes_per_block
+    }
+
+    // When an order to `address`, queued at `at`, should arrive. This is
+    // worked out by dispatching a copy of everything already waiting with
+    // the new order added, as each driver gets back, so it takes the queue
+    // and batching into account. There's no estimate without any drivers.
+    pub fn estimate(&self, address: &Address, at: Time) -> Option<Time> {
+        let mut trial = self.clone();
+        let probe = DeliveryId(u32::MAX);
+        trial.deliveries.push(Delivery {
+            id: probe,
+            order: OrderId(u32::MAX),
+            address: address.clone(),
+            queued_at: at,
+            status: DeliveryStatus::Waiting,
+            driver: None,
+            eta: None,
+            history: vec![(DeliveryStatus::Waiting, at)],
+            failure: None,
+        });
+        let mut now = at;
+        loop {
+            for run in trial.dispatch(now) {
+                if let Some((_, eta)) = run.stops.iter().find(|(id, _)| *id == probe) {
+                    return Some(*eta);
+                }
+            }
+            // Wait for the next driver to get back. A run can take no time
+            // at all, leaving a driver free again straight away, so `now`
+            // may not move on; every dispatch sends out at least one order
+            // while there's a driver free, so this still comes to an end.
+            now = trial.drivers.iter().map(|d| d.free_at).min()?.max(now);
+        }
+    }
+
+    // Visits the deliveries at these indexes nearest first, starting from
+    // the restaurant
+    fn route(&self, mut stops: Vec<usize>) -> Vec<usize> {
+        let mut here = Point::RESTAURANT;
+        let mut route = Vec::new();
+        while !stops.is_empty() {
+            let next = (0..stops.len())
+                .min_by_key(|&s| {
+                    let delivery = &self.deliveries[stops[s]];
+                    (here.blocks_to(delivery.address.at), delivery.queued_at, delivery.id)
+                })
+                .expect("there's at least one stop left");
+            let stop = stops.remove(next);
+            here = self.deliveries[stop].address.at;
+            route.push(stop);
+        }
+        route
+    }
+
+    // Sends out every free driver with a run of waiting orders
+    pub fn dispatch(&mut self, at: Time) -> Vec<Run> {
+        let mut waiting: Vec<usize> = (0..self.deliveries.len())
+            .filter(|&d| self.deliveries[d].status == DeliveryStatus::Waiting)
+            .collect();
+        waiting.sort_by_key(|&d| (self.deliveries[d].queued_at, self.deliveries[d].id));
+
+        // Whoever has been back longest goes first
+        let mut free: Vec<usize> = (0..self.drivers.len())
+            .filter(|&d| self.drivers[d].free_at <= at)
+            .collect();
+        free.sort_by_key(|&d| (self.drivers[d].free_at, self.drivers[d].id));
+
+        let mut runs = Vec::new();
+        for driver in free {
+            if waiting.is_empty() {
+                break;
+            }
+            let first = waiting.remove(0);
+            let near = self.deliveries[first].address.at;
+            let mut nearby: Vec<usize> = waiting
+                .iter()
+                .copied()
+                .filter(|&d| near.blocks_to(self.deliveries[d].address.at) <= self.config.batch_blocks)
+                .collect();
+            // sort_by_key is stable, so orders the same distance away stay
+            // in queue order
+            nearby.sort_by_key(|&d| near.blocks_to(self.deliveries[d].address.at));
+            nearby.truncate(self.config.max_stops - 1);
+            waiting.retain(|d| !nearby.contains(d));
+
+            let mut batch = vec![first];
+            batch.extend(nearby);
+            let route = self.route(batch);
+
+            let mut here = Point::RESTAURANT;
+            let mut clock = at;
+            let mut stops = Vec::new();
+            for &d in &route {
+                let to = self.deliveries[d].address.at;
+                clock = clock.plus_minutes(self.minutes_for(here.blocks_to(to)));
+                let delivery = &mut self.deliveries[d];
+                delivery
+                    .transition(DeliveryStatus::Dispatched, at)
+                    .expect("only waiting deliveries are dispatched");
+                delivery.driver = Some(self.drivers[driver].id);
+                delivery.eta = Some(clock);
+                stops.push((delivery.id, clock));
+                clock = clock.plus_minutes(self.config.handover_minutes);
+                here = to;
+            }
+            let back_at = clock.plus_minutes(self.minutes_for(here.blocks_to(Point::RESTAURANT)));
+            self.drivers[driver].free_at = back_at;
+            runs.push(Run {
+                driver: self.drivers[driver].id,
+                leaves_at: at,
+                stops,
+                back_at,
+            });
+        }
+        runs
+    }
+
+    // The food reached the guest, so the order has been served
+    pub fn delivered(&mut self, id: DeliveryId, orders: &mut OrderBook, at: Time) -> Result<(), DeliveryError> {
+        let delivery = self.get(id)?;
+        if !delivery.status.can_become(DeliveryStatus::Delivered) {
+            return Err(DeliveryError::IllegalTransition {
+                delivery: id,
+                from: delivery.status,
+                to: DeliveryStatus::Delivered,
+            });
+        }
+        orders.get_mut(delivery.order)?.serve(at)?;
+        self.get_mut(id)?.transition(DeliveryStatus::Delivered, at)
+    }
+
+    // The food couldn't be handed over, so the order is voided
+    pub fn failed(
+        &mut self,
+        id: DeliveryId,
+        orders: &mut OrderBook,
+        reason: &str,
+        at: Time,
+    ) -> Result<(), DeliveryError> {
+        let delivery = self.get(id)?;
+        if !delivery.status.can_become(DeliveryStatus::Failed) {
+            return Err(DeliveryError::IllegalTransition {
+                delivery: id,
+                from: delivery.status,
+                to: DeliveryStatus::Failed,
+            });
+        }
+        orders.get_mut(delivery.order)?.void(at)?;
+        let delivery = self.get_mut(id)?;
+        delivery.failure = Some(String::from(reason));
+        delivery.transition(DeliveryStatus::Failed, at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::back_of_house::menu::Menu;
+    use crate::front_of_house::hosting::PartyId;
+    use crate::front_of_house::serving::OrderItem;
+
+    // An order for soup which the kitchen has just finished
+    fn ready_order(orders: &mut OrderBook, at: Time) -> OrderId {
+        let id = orders.open(PartyId(1), at);
+        let order = orders.get_mut(id).unwrap();
+        order.add_item(OrderItem::from_menu(&Menu::house(), "soup", &[], 1).unwrap()).unwrap();
+        order.send_to_kitchen(at).unwrap();
+        order.start_cooking(at).unwrap();
+        order.mark_ready(at).unwrap();
+        id
+    }
+
+    fn queue(
+        dispatcher: &mut Dispatcher,
+        orders: &mut OrderBook,
+        street: &str,
+        x: i32,
+        y: i32,
+        at: Time,
+    ) -> DeliveryId {
+        let order = ready_order(orders, at);
+        let order = orders.get(order).unwrap();
+        dispatcher.queue(order, Address::new(street, x, y), at).unwrap()
+    }
+
+    #[test]
+    fn nearby_orders_go_out_together_nearest_first() {
+        let mut dispatcher = Dispatcher::new(DeliveryConfig::new());
+        let mut orders = OrderBook::new();
+        let first = dispatcher.add_driver("Ana", Time::hm(18, 0));
+        let second = dispatcher.add_driver("Ben", Time::hm(18, 0));
+        let a = queue(&mut dispatcher, &mut orders, "A Street", 3, 0, Time::hm(18, 0));
+        let b = queue(&mut dispatcher, &mut orders, "B Street", 0, 5, Time::hm(18, 1));
+        let c = queue(&mut dispatcher, &mut orders, "C Street", 5, 0, Time::hm(18, 2));
+        let d = queue(&mut dispatcher, &mut orders, "D Street", 1, 1, Time::hm(18, 3));
+        let e = queue(&mut dispatcher, &mut orders, "E Street", 4, 1, Time::hm(18, 4));
+
+        let runs = dispatcher.dispatch(Time::hm(18, 5));
+        // C and E are both two blocks from A, and three stops is the most
+        // one driver takes, so D is left behind. From A, C and E are two
+        // blocks away again, and C was queued first.
+        assert_eq!(
+            runs[0],
+            Run {
+                driver: first,
+                leaves_at: Time::hm(18, 5),
+                stops: vec![(a, Time::hm(18, 11)), (c, Time::hm(18, 17)), (e, Time::hm(18, 23))],
+                back_at: Time::hm(18, 35),
+            }
+        );
+        // B is too far from anyone else to share a run
+        assert_eq!(runs[1].driver, second);
+        assert_eq!(runs[1].stops, vec![(b, Time::hm(18, 15))]);
+        assert_eq!(runs[1].back_at, Time::hm(18, 27));
+        assert_eq!(runs.len(), 2);
+
+        assert_eq!(dispatcher.get(c).unwrap().eta(), Some(Time::hm(18, 17)));
+        assert_eq!(dispatcher.get(c).unwrap().driver(), Some(first));
+        assert_eq!(dispatcher.get(d).unwrap().status(), DeliveryStatus::Waiting);
+        assert_eq!(dispatcher.dispatch(Time::hm(18, 10)), Vec::new());
+    }
+
+    #[test]
+    fn an_estimate_waits_for_the_queue_and_a_driver() {
+        let mut dispatcher = Dispatcher::new(DeliveryConfig::new());
+        let mut orders = OrderBook::new();
+        let address = Address::new("F Street", 2, 0);
+        assert_eq!(dispatcher.estimate(&address, Time::hm(18, 0)), None);
+
+        dispatcher.add_driver("Ana", Time::hm(18, 0));
+        queue(&mut dispatcher, &mut orders, "A Street", 0, 5, Time::hm(18, 0));
+        queue(&mut dispatcher, &mut orders, "D Street", 1, 1, Time::hm(18, 1));
+        dispatcher.dispatch(Time::hm(18, 5));
+
+        // Ana is back at 18:27, and takes D Street first as it was queued
+        // first and is just as near
+        assert_eq!(dispatcher.estimate(&address, Time::hm(18, 6)), Some(Time::hm(18, 37)));
+        // Estimating doesn't change anything
+        assert_eq!(dispatcher.deliveries().count(), 2);
+        assert_eq!(dispatcher.drivers()[0].free_at, Time::hm(18, 27));
+    }
+
+    #[test]
+    fn runs_that_take_no_time_still_have_an_estimate() {
+        let config = DeliveryConfig::new().minutes_per_block(0).handover_minutes(0).batching(4, 1);
+        let mut dispatcher = Dispatcher::new(config);
+        let mut orders = OrderBook::new();
+        dispatcher.add_driver("Ana", Time::hm(18, 0));
+        queue(&mut dispatcher, &mut orders, "A Street", 1, 0, Time::hm(18, 0));
+        queue(&mut dispatcher, &mut orders, "B Street", 2, 0, Time::hm(18, 0));
+
+        let address = Address::new("C Street", 3, 0);
+        assert_eq!(dispatcher.estimate(&address, Time::hm(18, 0)), Some(Time::hm(18, 0)));
+    }
+
+    #[test]
+    fn deliveries_end_up_delivered_or_failed() {
+        let mut dispatcher = Dispatcher::new(DeliveryConfig::new());
+        let mut orders = OrderBook::new();
+        dispatcher.add_driver("Ana", Time::hm(18, 0));
+        let a = queue(&mut dispatcher, &mut orders, "A Street", 1, 0, Time::hm(18, 0));
+        let b = queue(&mut dispatcher, &mut orders, "B Street", 2, 0, Time::hm(18, 0));
+
+        // Nothing can be handed over before it's gone out
+        let error = dispatcher.delivered(a, &mut orders, Time::hm(18, 1)).unwrap_err();
+        assert_eq!(
+            error,
+            DeliveryError::IllegalTransition {
+                delivery: a,
+                from: DeliveryStatus::Waiting,
+                to: DeliveryStatus::Delivered
+            }
+        );
+
+        dispatcher.dispatch(Time::hm(18, 5));
+        dispatcher.delivered(a, &mut orders, Time::hm(18, 7)).unwrap();
+        dispatcher.failed(b, &mut orders, "nobody was in", Time::hm(18, 11)).unwrap();
+
+        let delivered = dispatcher.get(a).unwrap();
+        assert_eq!(
+            delivered.history(),
+            [
+                (DeliveryStatus::Waiting, Time::hm(18, 0)),
+                (DeliveryStatus::Dispatched, Time::hm(18, 5)),
+                (DeliveryStatus::Delivered, Time::hm(18, 7)),
+            ]
+        );
+        assert_eq!(orders.get(delivered.order).unwrap().status(), OrderStatus::Served);
+
+        let failed = dispatcher.get(b).unwrap();
+        assert_eq!(failed.status(), DeliveryStatus::Failed);
+        assert_eq!(failed.failure(), Some("nobody was in"));
+        let order = failed.order;
+        assert_eq!(orders.get(order).unwrap().status(), OrderStatus::Voided);
+        assert!(dispatcher.delivered(b, &mut orders, Time::hm(18, 12)).is_err());
+
+        // The order was voided, so it can't be sent out again
+        let order = orders.get(order).unwrap();
+        let error = dispatcher.queue(order, Address::new("B Street", 2, 0), Time::hm(18, 12));
+        assert_eq!(error, Err(DeliveryError::NotReady { order: order.id, status: OrderStatus::Voided }));
+    }
+}
@@ -0,0 +1,568 @@
+// The floor is the set of tables in the dining room, along with who is
+// sitting at each one.
+//
+// Some tables can be pushed together to seat a larger party. We record this
+// by marking pairs of tables as adjacent. Any group of free tables which are
+// joined up through adjacent pairs can be combined, so three tables in a line
+// can seat a party even though the two at either end don't touch. No more
+// than MAX_JOINED tables are ever pushed together for one party.
+
+use std::fmt;
+
+use super::waitlist::{Party, PartyId, Priority, Waitlist, WaitlistEntry};
+use crate::clock::Time;
+
+// The most tables that will be pushed together for one party. Beyond this
+// the party is split across the room anyway, and the search for a fit would
+// take far longer on a big floor.
+pub const MAX_JOINED: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct TableId(pub u32);
+
+#[derive(Debug, Clone)]
+pub struct Table {
+    pub id: TableId,
+    pub capacity: u32,
+    adjacent: Vec<TableId>,
+    occupant: Option<PartyId>,
+}
+
+impl Table {
+    pub fn occupant(&self) -> Option<PartyId> {
+        self.occupant
+    }
+
+    pub fn is_free(&self) -> bool {
+        self.occupant.is_none()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SeatingError {
+    NobodyWaiting,
+    // There are people waiting, but none of them fit at the tables which are
+    // free right now
+    NoTableFree,
+    // Even with every table on the floor free and pushed together, this party
+    // could not be seated. `largest` is the most seats we can ever offer.
+    PartyTooLarge { party: PartyId, size: u32, largest: u32 },
+    // A party with nobody in it was put on the waitlist
+    EmptyParty(PartyId),
+    UnknownTable(TableId),
+    // The table is already in use, or was listed twice for the same party
+    TableTaken(TableId),
+}
+
+impl fmt::Display for SeatingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SeatingError::NobodyWaiting => write!(f, "nobody is waiting to be seated"),
+            SeatingError::NoTableFree => write!(f, "no free table fits anyone who is waiting"),
+            SeatingError::PartyTooLarge { party, size, largest } => write!(
+                f,
+                "party {} has {} people, but at most {} can be seated together",
+                party.0, size, largest
+            ),
+            SeatingError::EmptyParty(party) => write!(f, "party {} has nobody in it", party.0),
+            SeatingError::UnknownTable(id) => write!(f, "there is no table {}", id.0),
+            SeatingError::TableTaken(id) => write!(f, "table {} is already taken", id.0),
+        }
+    }
+}
+
+impl std::error::Error for SeatingError {}
+
+// A set of tables which could seat a party, and how many seats that leaves
+// empty
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TableChoice {
+    pub tables: Vec<TableId>,
+    pub wasted_seats: u32,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Floor {
+    tables: Vec<Table>,
+}
+
+impl Floor {
+    pub fn new() -> Floor {
+        Floor { tables: Vec::new() }
+    }
+
+    // Tables are numbered in the order they're added, starting from 1
+    pub fn add_table(&mut self, capacity: u32) -> TableId {
+        let id = TableId(self.tables.len() as u32 + 1);
+        self.tables.push(Table {
+            id,
+            capacity,
+            adjacent: Vec::new(),
+            occupant: None,
+        });
+        id
+    }
+
+    // Records that two tables can be pushed together
+    pub fn join(&mut self, a: TableId, b: TableId) -> Result<(), SeatingError> {
+        let (ia, ib) = (self.index(a)?, self.index(b)?);
+        if ia != ib && !self.tables[ia].adjacent.contains(&b) {
+            self.tables[ia].adjacent.push(b);
+            self.tables[ib].adjacent.push(a);
+        }
+        Ok(())
+    }
+
+    fn index(&self, id: TableId) -> Result<usize, SeatingError> {
+        self.tables
+            .iter()
+            .position(|t| t.id == id)
+            .ok_or(SeatingError::UnknownTable(id))
+    }
+
+    pub fn table(&self, id: TableId) -> Option<&Table> {
+        self.tables.iter().find(|t| t.id == id)
+    }
+
+    pub fn tables(&self) -> &[Table] {
+        &self.tables
+    }
+
+    pub fn free_tables(&self) -> impl Iterator<Item = &Table> {
+        self.tables.iter().filter(|t| t.is_free())
+    }
+
+    pub fn tables_for(&self, party: PartyId) -> Vec<TableId> {
+        self.tables
+            .iter()
+            .filter(|t| t.occupant == Some(party))
+            .map(|t| t.id)
+            .collect()
+    }
+
+    fn neighbours(&self, index: usize) -> impl Iterator<Item = usize> + '_ {
+        self.tables[index]
+            .adjacent
+            .iter()
+            .filter_map(|&id| self.index(id).ok())
+    }
+
+    // Whether table `u` is in `group` or next to one of its tables
+    fn touches(&self, group: &[usize], u: usize) -> bool {
+        group.iter().any(|&i| i == u || self.neighbours(i).any(|n| n == u))
+    }
+
+    // The most people that can ever be seated together, i.e. the capacity of
+    // the biggest group of joined-up tables, whether or not they are free.
+    // Groups are limited to MAX_JOINED tables.
+    pub fn largest_seating(&self) -> u32 {
+        // No group ever has enough seats for a party this big, so every
+        // group is visited
+        let usable = vec![true; self.tables.len()];
+        let mut search = Search {
+            size: u32::MAX,
+            usable: &usable,
+            biggest: u32::MAX,
+            best: None,
+            largest: 0,
+        };
+        for start in 0..self.tables.len() {
+            let extension = self.extension(&[start], start, &usable);
+            self.grow(&mut search, &mut vec![start], self.tables[start].capacity, extension, start);
+        }
+        search.largest
+    }
+
+    // Finds the free table, or group of joined-up free tables, which seats
+    // `size` people with the fewest empty seats. Ties go to the choice using
+    // fewer tables, then to lower numbered tables, so the answer is always
+    // the same for the same floor. Nobody needs a table for a party of no
+    // one, so that always gives None.
+    pub fn best_fit(&self, size: u32) -> Option<TableChoice> {
+        self.best_fit_where(size, |table| table.is_free())
+    }
+
+    // As best_fit, but using only the tables `usable` says yes to, whether
+    // or not anyone is sitting at them. Reservations use this to find tables
+    // for later in the evening.
+    pub fn best_fit_where(&self, size: u32, usable: impl Fn(&Table) -> bool) -> Option<TableChoice> {
+        if size == 0 {
+            return None;
+        }
+        let usable: Vec<bool> = self.tables.iter().map(usable).collect();
+        // spare[i] is how many usable seats there are at table i or later
+        let mut spare = vec![0; self.tables.len() + 1];
+        for i in (0..self.tables.len()).rev() {
+            spare[i] = spare[i + 1] + if usable[i] { self.tables[i].capacity } else { 0 };
+        }
+
+        let mut search = Search {
+            size,
+            usable: &usable,
+            biggest: (0..self.tables.len())
+                .filter(|&i| usable[i])
+                .map(|i| self.tables[i].capacity)
+                .max()
+                .unwrap_or(0),
+            best: None,
+            largest: 0,
+        };
+        for start in 0..self.tables.len() {
+            // Every group is grown from its lowest numbered table, so once
+            // the rest of the floor can't seat the party, nothing later can
+            if spare[start] < size {
+                break;
+            }
+            if !usable[start] {
+                continue;
+            }
+            let extension = self.extension(&[start], start, &usable);
+            self.grow(&mut search, &mut vec![start], self.tables[start].capacity, extension, start);
+            // A single table with no empty seats can't be beaten
+            if matches!(&search.best, Some((0, group)) if group.len() == 1) {
+                break;
+            }
+        }
+
+        search.best.map(|(wasted_seats, group)| TableChoice {
+            tables: group.iter().map(|&i| self.tables[i].id).collect(),
+            wasted_seats,
+        })
+    }
+
+    // The tables a group could grow into next, now that the last table in
+    // it has been added: that table's usable neighbours which are numbered
+    // after `start`, and weren't already in or next to the rest of the group
+    fn extension(&self, group: &[usize], start: usize, usable: &[bool]) -> Vec<usize> {
+        let mut extension: Vec<usize> = Vec::new();
+        let from = *group.last().expect("groups always have a table in them");
+        let before = &group[..group.len() - 1];
+        for n in self.neighbours(from) {
+            if n > start && usable[n] && !self.touches(before, n) && !extension.contains(&n) {
+                extension.push(n);
+            }
+        }
+        extension
+    }
+
+    // Depth first search over groups of joined-up tables, each grown from
+    // its lowest numbered table. Only tables in `extension` are added next,
+    // and a table added later can bring its own new neighbours with it, so
+    // every group is visited exactly once and none need remembering (this
+    // is Wernicke's ESU algorithm for listing connected subgraphs).
+    //
+    // A group stops growing as soon as it has enough seats, since adding
+    // another table could only waste more of them, once it has MAX_JOINED
+    // tables, or once even the biggest tables couldn't fill the seats it's
+    // short of in the tables it has left to add.
+    fn grow(
+        &self,
+        search: &mut Search,
+        group: &mut Vec<usize>,
+        capacity: u32,
+        mut extension: Vec<usize>,
+        start: usize,
+    ) {
+        search.largest = search.largest.max(capacity);
+        if capacity >= search.size {
+            let wasted = capacity - search.size;
+            let mut key = group.clone();
+            key.sort_unstable();
+            let better = match &search.best {
+                None => true,
+                Some((best_wasted, best_group)) => {
+                    (wasted, key.len(), &key) < (*best_wasted, best_group.len(), best_group)
+                }
+            };
+            if better {
+                search.best = Some((wasted, key));
+            }
+            return;
+        }
+        let tables_left = (MAX_JOINED - group.len()) as u32;
+        if tables_left == 0 || capacity.saturating_add(tables_left.saturating_mul(search.biggest)) < search.size {
+            return;
+        }
+
+        while let Some(next) = extension.pop() {
+            group.push(next);
+            let mut bigger = extension.clone();
+            for n in self.extension(group, start, search.usable) {
+                if !bigger.contains(&n) {
+                    bigger.push(n);
+                }
+            }
+            self.grow(search, group, capacity + self.tables[next].capacity, bigger, start);
+            group.pop();
+        }
+    }
+
+    // Sits a party at the given tables. Every table is checked before any of
+    // them are changed, so a table can never end up booked twice, and a
+    // failed call leaves the floor exactly as it was.
+    pub fn occupy(&mut self, tables: &[TableId], party: PartyId) -> Result<(), SeatingError> {
+        let mut indexes = Vec::with_capacity(tables.len());
+        for &id in tables {
+            let i = self.index(id)?;
+            if !self.tables[i].is_free() || indexes.contains(&i) {
+                return Err(SeatingError::TableTaken(id));
+            }
+            indexes.push(i);
+        }
+        for i in indexes {
+            self.tables[i].occupant = Some(party);
+        }
+        Ok(())
+    }
+
+    // Frees up every table the party was sat at, returning which ones
+    pub fn release(&mut self, party: PartyId) -> Vec<TableId> {
+        let mut released = Vec::new();
+        for table in self.tables.iter_mut().filter(|t| t.occupant == Some(party)) {
+            table.occupant = None;
+            released.push(table.id);
+        }
+        released
+    }
+}
+
+// How a search for tables is going. `biggest` is the capacity of the
+// biggest usable table, and `largest` the most seats any group visited has
+// had.
+struct Search<'a> {
+    size: u32,
+    usable: &'a [bool],
+    biggest: u32,
+    best: Option<(u32, Vec<usize>)>,
+    largest: u32,
+}
+
+// A party who has been taken off the waitlist and sat down
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Seating {
+    pub id: PartyId,
+    pub party: Party,
+    pub tables: Vec<TableId>,
+    pub seated_at: Time,
+}
+
+// Picks the waiting party who best fits the tables that are free right now,
+// and sits them down.
+//
+// Parties with a higher priority are always considered first. Among parties
+// with the same priority, the one who leaves the fewest empty seats wins,
+// with ties going to whoever has been waiting longest. This keeps big tables
+// free for big parties, rather than giving them to whoever is at the front.
+//
+// `allowed` says whether a party may be sat at a table, which lets tables be
+// kept back for parties who have booked them.
+pub fn seat_best_fit(
+    waitlist: &mut Waitlist,
+    floor: &mut Floor,
+    now: Time,
+    allowed: impl Fn(PartyId, TableId) -> bool,
+) -> Result<Seating, SeatingError> {
+    if waitlist.is_empty() {
+        return Err(SeatingError::NobodyWaiting);
+    }
+
+    let largest = floor.largest_seating();
+    let mut best: Option<(Priority, PartyId, TableChoice)> = None;
+    for entry in waitlist.iter() {
+        if entry.party.size == 0 || entry.party.size > largest {
+            continue;
+        }
+        let usable = |table: &Table| table.is_free() && allowed(entry.id, table.id);
+        if let Some(choice) = floor.best_fit_where(entry.party.size, usable) {
+            let better = match &best {
+                None => true,
+                Some((priority, _, current)) => {
+                    entry.party.priority > *priority
+                        || (entry.party.priority == *priority
+                            && choice.wasted_seats < current.wasted_seats)
+                }
+            };
+            if better {
+                best = Some((entry.party.priority, entry.id, choice));
+            }
+        }
+    }
+
+    let Some((_, id, choice)) = best else {
+        // Nobody can be seated. If somebody could be once a table frees up,
+        // that's all there is to it. Otherwise nobody waiting can ever be
+        // seated, and the host will need to sort that out by hand.
+        let stuck = |e: &&WaitlistEntry| e.party.size == 0 || e.party.size > largest;
+        return Err(match waitlist.iter().find(|e| !stuck(e)) {
+            Some(_) => SeatingError::NoTableFree,
+            None => {
+                let entry = waitlist.iter().find(stuck).expect("the waitlist isn't empty");
+                if entry.party.size == 0 {
+                    SeatingError::EmptyParty(entry.id)
+                } else {
+                    SeatingError::PartyTooLarge {
+                        party: entry.id,
+                        size: entry.party.size,
+                        largest,
+                    }
+                }
+            }
+        });
+    };
+
+    floor.occupy(&choice.tables, id)?;
+    let party = waitlist
+        .remove(id)
+        .expect("the party was found on the waitlist above");
+    Ok(Seating {
+        id,
+        party,
+        tables: choice.tables,
+        seated_at: now,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::front_of_house::hosting::TableTurnover;
+
+    // Tables of the given sizes, each joined to the next one along
+    fn line(capacities: &[u32]) -> Floor {
+        let mut floor = Floor::new();
+        let ids: Vec<TableId> = capacities.iter().map(|&c| floor.add_table(c)).collect();
+        for pair in ids.windows(2) {
+            floor.join(pair[0], pair[1]).unwrap();
+        }
+        floor
+    }
+
+    fn tables(choice: Option<TableChoice>) -> Option<Vec<u32>> {
+        choice.map(|c| c.tables.iter().map(|t| t.0).collect())
+    }
+
+    fn waiting(sizes: &[u32]) -> Waitlist {
+        let mut waitlist = Waitlist::new(TableTurnover::new(4, 60));
+        for (i, &size) in sizes.iter().enumerate() {
+            waitlist.add(Party::new(&format!("Party {}", i + 1), size, Time::hm(18, i as u32)));
+        }
+        waitlist
+    }
+
+    #[test]
+    fn the_closest_fit_wins() {
+        let floor = line(&[2, 6, 4]);
+        assert_eq!(tables(floor.best_fit(4)), Some(vec![3]));
+        assert_eq!(tables(floor.best_fit(5)), Some(vec![2]));
+        // 2 + 6 and 6 + 4 both leave two seats empty; the lower numbers win
+        assert_eq!(tables(floor.best_fit(10)), Some(vec![2, 3]));
+        assert_eq!(floor.best_fit(12).unwrap().wasted_seats, 0);
+    }
+
+    #[test]
+    fn fewer_tables_win_a_tie() {
+        // Table 3 on its own or tables 1 and 2 together both fit exactly
+        let floor = line(&[2, 2, 4]);
+        assert_eq!(tables(floor.best_fit(4)), Some(vec![3]));
+    }
+
+    #[test]
+    fn tables_join_through_their_neighbours() {
+        // 1 and 3 don't touch, but both touch 2
+        let mut floor = line(&[2, 2, 2]);
+        assert_eq!(tables(floor.best_fit(6)), Some(vec![1, 2, 3]));
+
+        // Without 2, they can't be pushed together
+        floor.occupy(&[TableId(2)], PartyId(9)).unwrap();
+        assert_eq!(floor.best_fit(4), None);
+        // best_fit_where only asks `usable`, so it may count a taken table
+        assert_eq!(tables(floor.best_fit_where(4, |t| t.id != TableId(2))), None);
+        assert_eq!(tables(floor.best_fit_where(4, |t| t.id != TableId(1))), Some(vec![2, 3]));
+    }
+
+    #[test]
+    fn nobody_needs_a_table_for_a_party_of_no_one() {
+        assert_eq!(line(&[2, 4]).best_fit(0), None);
+    }
+
+    #[test]
+    fn no_more_than_max_joined_tables_go_together() {
+        let floor = line(&[2; MAX_JOINED + 1]);
+        assert_eq!(floor.largest_seating(), 2 * MAX_JOINED as u32);
+        assert_eq!(floor.best_fit(2 * MAX_JOINED as u32 + 1), None);
+        assert_eq!(floor.best_fit(2 * MAX_JOINED as u32).unwrap().tables.len(), MAX_JOINED);
+    }
+
+    #[test]
+    fn a_big_floor_is_searched_quickly() {
+        // A 12 by 12 grid of tables for four, each joined to the tables
+        // beside, in front and behind it
+        let mut floor = Floor::new();
+        let ids: Vec<TableId> = (0..144).map(|_| floor.add_table(4)).collect();
+        for i in 0..144 {
+            if i % 12 != 11 {
+                floor.join(ids[i], ids[i + 1]).unwrap();
+            }
+            if i + 12 < 144 {
+                floor.join(ids[i], ids[i + 12]).unwrap();
+            }
+        }
+        let choice = floor.best_fit(14).unwrap();
+        assert_eq!((choice.tables.len(), choice.wasted_seats), (4, 2));
+        assert_eq!(floor.best_fit(17), None);
+    }
+
+    #[test]
+    fn the_best_fitting_party_is_seated() {
+        let mut floor = line(&[4]);
+        let mut waitlist = waiting(&[2, 4, 3]);
+        let seating = seat_best_fit(&mut waitlist, &mut floor, Time::hm(19, 0), |_, _| true).unwrap();
+        assert_eq!(seating.party.size, 4);
+        assert_eq!(seating.tables, vec![TableId(1)]);
+        assert_eq!(waitlist.len(), 2);
+
+        // The table is taken now, and everybody left could sit at it later
+        let error = seat_best_fit(&mut waitlist, &mut floor, Time::hm(19, 5), |_, _| true);
+        assert_eq!(error.unwrap_err(), SeatingError::NoTableFree);
+    }
+
+    #[test]
+    fn a_party_too_large_for_any_tables() {
+        let mut floor = line(&[2, 4]);
+        let mut waitlist = waiting(&[7]);
+        let error = seat_best_fit(&mut waitlist, &mut floor, Time::hm(19, 0), |_, _| true).unwrap_err();
+        assert_eq!(error, SeatingError::PartyTooLarge { party: PartyId(1), size: 7, largest: 6 });
+        assert_eq!(waitlist.len(), 1);
+    }
+
+    #[test]
+    fn a_party_too_large_doesnt_hide_a_full_floor() {
+        let mut floor = line(&[2, 4]);
+        floor.occupy(&[TableId(1), TableId(2)], PartyId(99)).unwrap();
+        let mut waitlist = waiting(&[7, 2]);
+        let error = seat_best_fit(&mut waitlist, &mut floor, Time::hm(19, 0), |_, _| true).unwrap_err();
+        assert_eq!(error, SeatingError::NoTableFree);
+
+        // Nor does it stop anyone else being seated
+        floor.release(PartyId(99));
+        let seating = seat_best_fit(&mut waitlist, &mut floor, Time::hm(19, 5), |_, _| true).unwrap();
+        assert_eq!(seating.party.size, 2);
+    }
+
+    #[test]
+    fn an_empty_party_is_never_seated() {
+        let mut floor = line(&[2]);
+        let mut waitlist = waiting(&[0]);
+        let error = seat_best_fit(&mut waitlist, &mut floor, Time::hm(19, 0), |_, _| true).unwrap_err();
+        assert_eq!(error, SeatingError::EmptyParty(PartyId(1)));
+        assert!(floor.tables()[0].is_free());
+    }
+
+    #[test]
+    fn nobody_waiting() {
+        let mut floor = line(&[2]);
+        let mut waitlist = waiting(&[]);
+        let error = seat_best_fit(&mut waitlist, &mut floor, Time::hm(19, 0), |_, _| true).unwrap_err();
+        assert_eq!(error, SeatingError::NobodyWaiting);
+    }
+}
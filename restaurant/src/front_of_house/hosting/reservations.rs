@@ -0,0 +1,356 @@
+// Reservations: parties who have booked a table for a particular time.
+//
+// Each booking is for a start time and an expected length of stay, and is
+// given the tables it will sit at when it's made. A new booking can only use
+// tables which aren't already booked for any part of its slot, so we never
+// promise the same table to two parties at once. As with walk-ins, tables
+// which can be pushed together can be booked together for a larger party.
+//
+// When a party with a booking turns up they're checked in, which puts them
+// on the waitlist alongside the walk-ins (ahead of anyone with a normal
+// priority), and seat_at_table sits them down at the tables they booked, as
+// soon as those are free. Until then their tables are held: a walk-in won't
+// be given a table which is booked for some time before they would be
+// expected to leave. Parties who are more than NO_SHOW_MINUTES late stop
+// holding their tables.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::floor::{Floor, TableId};
+use super::waitlist::{Party, PartyId, Priority, Waitlist};
+use crate::clock::Time;
+
+// How late a party can be before their tables are given to someone else
+pub const NO_SHOW_MINUTES: u32 = 15;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct ReservationId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReservationStatus {
+    Booked,
+    // The party has arrived, and is on the waitlist under this id
+    CheckedIn(PartyId),
+    Seated(PartyId),
+    Cancelled,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reservation {
+    pub id: ReservationId,
+    pub party: Party,
+    pub start: Time,
+    // How long the party is expected to stay, in minutes
+    pub duration: u32,
+    pub tables: Vec<TableId>,
+    pub status: ReservationStatus,
+}
+
+impl Reservation {
+    pub fn end(&self) -> Time {
+        self.start.plus_minutes(self.duration)
+    }
+
+    // Whether this booking's slot overlaps the time from `start` up to (but
+    // not including) `end`
+    fn overlaps(&self, start: Time, end: Time) -> bool {
+        self.start < end && start < self.end()
+    }
+
+    // Whether the booking still has a claim on its tables
+    fn is_active(&self) -> bool {
+        matches!(self.status, ReservationStatus::Booked | ReservationStatus::CheckedIn(_))
+    }
+
+    fn is_no_show(&self, now: Time) -> bool {
+        self.status == ReservationStatus::Booked && now.minutes_since(self.start) > NO_SHOW_MINUTES
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReservationError {
+    UnknownReservation(ReservationId),
+    // Only bookings nobody has turned up for yet can be changed
+    NotBooked(ReservationId),
+    ZeroDuration,
+    // Bookings can't be made, or moved, to a time that has already gone
+    InThePast { start: Time, now: Time },
+    // Even with every table joined up, this party could not be seated
+    PartyTooLarge { size: u32, largest: u32 },
+    // Every table that could seat the party is booked for some of the slot
+    NoTablesFree { start: Time, end: Time },
+}
+
+impl fmt::Display for ReservationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReservationError::UnknownReservation(id) => write!(f, "there is no reservation {}", id.0),
+            ReservationError::NotBooked(id) => {
+                write!(f, "reservation {} has already checked in or been cancelled", id.0)
+            }
+            ReservationError::ZeroDuration => write!(f, "a reservation has to be for some length of time"),
+            ReservationError::InThePast { start, now } => {
+                write!(f, "a table can't be booked for {}, as it's already {}", start, now)
+            }
+            ReservationError::PartyTooLarge { size, largest } => write!(
+                f,
+                "a party of {} can't be booked, at most {} can be seated together",
+                size, largest
+            ),
+            ReservationError::NoTablesFree { start, end } => {
+                write!(f, "no tables are free from {} to {}", start, end)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReservationError {}
+
+#[derive(Debug, Clone, Default)]
+pub struct ReservationBook {
+    reservations: Vec<Reservation>,
+}
+
+impl ReservationBook {
+    pub fn new() -> ReservationBook {
+        ReservationBook::default()
+    }
+
+    pub fn get(&self, id: ReservationId) -> Option<&Reservation> {
+        self.reservations.iter().find(|r| r.id == id)
+    }
+
+    fn get_mut(&mut self, id: ReservationId) -> Result<&mut Reservation, ReservationError> {
+        self.reservations
+            .iter_mut()
+            .find(|r| r.id == id)
+            .ok_or(ReservationError::UnknownReservation(id))
+    }
+
+    // Every reservation, in order of start time
+    pub fn iter(&self) -> impl Iterator<Item = &Reservation> {
+        let mut sorted: Vec<&Reservation> = self.reservations.iter().collect();
+        sorted.sort_by_key(|r| (r.start, r.id));
+        sorted.into_iter()
+    }
+
+    // Finds tables for a party which aren't booked by anyone else (other
+    // than `ignoring`) for any of the slot, which can't start before `now`
+    fn find_tables(
+        &self,
+        floor: &Floor,
+        size: u32,
+        start: Time,
+        duration: u32,
+        now: Time,
+        ignoring: Option<ReservationId>,
+    ) -> Result<Vec<TableId>, ReservationError> {
+        if duration == 0 {
+            return Err(ReservationError::ZeroDuration);
+        }
+        if start < now {
+            return Err(ReservationError::InThePast { start, now });
+        }
+        let largest = floor.largest_seating();
+        if size > largest {
+            return Err(ReservationError::PartyTooLarge { size, largest });
+        }
+
+        let end = start.plus_minutes(duration);
+        let booked: Vec<TableId> = self
+            .reservations
+            .iter()
+            .filter(|r| Some(r.id) != ignoring && r.is_active() && r.overlaps(start, end))
+            .flat_map(|r| r.tables.iter().copied())
+            .collect();
+        floor
+            .best_fit_where(size, |table| !booked.contains(&table.id))
+            .map(|choice| choice.tables)
+            .ok_or(ReservationError::NoTablesFree { start, end })
+    }
+
+    // Books tables for `party` from `start`, at `now`
+    pub fn book(
+        &mut self,
+        floor: &Floor,
+        party: Party,
+        start: Time,
+        duration: u32,
+        now: Time,
+    ) -> Result<ReservationId, ReservationError> {
+        let tables = self.find_tables(floor, party.size, start, duration, now, None)?;
+        let id = ReservationId(self.reservations.len() as u32 + 1);
+        self.reservations.push(Reservation {
+            id,
+            party,
+            start,
+            duration,
+            tables,
+            status: ReservationStatus::Booked,
+        });
+        Ok(id)
+    }
+
+    // Changes the size, time or length of a booking. The booking may be
+    // moved to different tables. If the change can't be made, the booking is
+    // left as it was.
+    pub fn modify(
+        &mut self,
+        floor: &Floor,
+        id: ReservationId,
+        size: u32,
+        start: Time,
+        duration: u32,
+        now: Time,
+    ) -> Result<(), ReservationError> {
+        let status = self.get_mut(id)?.status;
+        if status != ReservationStatus::Booked {
+            return Err(ReservationError::NotBooked(id));
+        }
+        let tables = self.find_tables(floor, size, start, duration, now, Some(id))?;
+        let reservation = self.get_mut(id)?;
+        reservation.party.size = size;
+        reservation.start = start;
+        reservation.duration = duration;
+        reservation.tables = tables;
+        Ok(())
+    }
+
+    // Cancelled bookings are kept on record, but free up their tables
+    pub fn cancel(&mut self, id: ReservationId) -> Result<&Reservation, ReservationError> {
+        let reservation = self.get_mut(id)?;
+        if reservation.status != ReservationStatus::Booked {
+            return Err(ReservationError::NotBooked(id));
+        }
+        reservation.status = ReservationStatus::Cancelled;
+        Ok(reservation)
+    }
+
+    // The party has arrived, so they join the waitlist ahead of the normal
+    // priority walk-ins
+    pub fn check_in(
+        &mut self,
+        waitlist: &mut Waitlist,
+        id: ReservationId,
+        at: Time,
+    ) -> Result<PartyId, ReservationError> {
+        let reservation = self.get_mut(id)?;
+        if reservation.status != ReservationStatus::Booked {
+            return Err(ReservationError::NotBooked(id));
+        }
+        let mut party = reservation.party.clone().priority(Priority::High);
+        party.arrived = at;
+        let party_id = waitlist.add(party);
+        reservation.status = ReservationStatus::CheckedIn(party_id);
+        Ok(party_id)
+    }
+
+    // Records that a checked in party has been sat down. Does nothing for
+    // walk-ins.
+    pub(super) fn mark_seated(&mut self, party: PartyId) {
+        if let Some(reservation) = self
+            .reservations
+            .iter_mut()
+            .find(|r| r.status == ReservationStatus::CheckedIn(party))
+        {
+            reservation.status = ReservationStatus::Seated(party);
+        }
+    }
+
+    // Bookings nobody has turned up for, more than NO_SHOW_MINUTES after
+    // they were due
+    pub fn no_shows(&self, now: Time) -> impl Iterator<Item = &Reservation> {
+        self.reservations.iter().filter(move |r| r.is_no_show(now))
+    }
+
+    // Whether `party` may be sat at `table` right now, if they're expected
+    // to stay for `stay` minutes. A party who has checked in for a booking
+    // can only have the tables they booked. For anyone else, a table is off
+    // limits if it's booked by someone else for any of that time, unless the
+    // booking is a no-show.
+    pub fn allows(&self, party: PartyId, table: TableId, now: Time, stay: u32) -> bool {
+        if let Some(booking) = self
+            .reservations
+            .iter()
+            .find(|r| r.status == ReservationStatus::CheckedIn(party))
+        {
+            return booking.tables.contains(&table);
+        }
+        let until = now.plus_minutes(stay.max(1));
+        !self.reservations.iter().any(|r| {
+            r.is_active()
+                && !r.is_no_show(now)
+                && r.tables.contains(&table)
+                && r.overlaps(now, until)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{seat_at_table, SeatingError, TableTurnover};
+    use super::*;
+
+    // A table for four, table 1, and a table for six, table 2
+    fn floor() -> Floor {
+        let mut floor = Floor::new();
+        floor.add_table(4);
+        floor.add_table(6);
+        floor
+    }
+
+    #[test]
+    fn a_checked_in_party_sits_at_the_tables_they_booked() {
+        let mut floor = floor();
+        let mut book = ReservationBook::new();
+        let mut waitlist = Waitlist::new(TableTurnover::new(4, 60));
+        let now = Time::hm(17, 0);
+
+        // The table for four is taken when the Khans book, so they get the
+        // table for six, and keep it once the other booking is cancelled
+        let other = book.book(&floor, Party::new("Reyes", 4, now), Time::hm(19, 0), 90, now).unwrap();
+        let khans = book.book(&floor, Party::new("Khan", 4, now), Time::hm(19, 0), 90, now).unwrap();
+        assert_eq!(book.get(khans).unwrap().tables, vec![TableId(2)]);
+        book.cancel(other).unwrap();
+
+        let party = book.check_in(&mut waitlist, khans, Time::hm(18, 55)).unwrap();
+        let seating = seat_at_table(&mut waitlist, &mut book, &mut floor, Time::hm(18, 55)).unwrap();
+        assert_eq!((seating.id, seating.tables), (party, vec![TableId(2)]));
+        assert_eq!(book.get(khans).unwrap().status, ReservationStatus::Seated(party));
+    }
+
+    #[test]
+    fn a_checked_in_party_waits_for_their_own_tables() {
+        let mut floor = floor();
+        let mut book = ReservationBook::new();
+        let mut waitlist = Waitlist::new(TableTurnover::new(4, 60));
+        let now = Time::hm(17, 0);
+        let khans = book.book(&floor, Party::new("Khan", 6, now), Time::hm(19, 0), 90, now).unwrap();
+
+        // Someone is still sat at the table for six. The table for four is
+        // free, but it isn't theirs.
+        floor.occupy(&[TableId(2)], PartyId(99)).unwrap();
+        let party = book.check_in(&mut waitlist, khans, Time::hm(19, 0)).unwrap();
+        let error = seat_at_table(&mut waitlist, &mut book, &mut floor, Time::hm(19, 0)).unwrap_err();
+        assert_eq!(error, SeatingError::NoTableFree);
+        assert!(!book.allows(party, TableId(1), Time::hm(19, 0), 60));
+    }
+
+    #[test]
+    fn bookings_cant_be_made_for_a_time_that_has_gone() {
+        let floor = floor();
+        let mut book = ReservationBook::new();
+        let now = Time::hm(19, 0);
+        let error = book.book(&floor, Party::new("Khan", 4, now), Time::hm(18, 30), 90, now);
+        assert_eq!(error, Err(ReservationError::InThePast { start: Time::hm(18, 30), now }));
+
+        // Right now is fine, but moving it earlier isn't
+        let khans = book.book(&floor, Party::new("Khan", 4, now), now, 90, now).unwrap();
+        let later = Time::hm(19, 30);
+        let error = book.modify(&floor, khans, 4, Time::hm(19, 15), 90, later);
+        assert_eq!(error, Err(ReservationError::InThePast { start: Time::hm(19, 15), now: later }));
+        assert_eq!(book.get(khans).unwrap().start, now);
+    }
+}
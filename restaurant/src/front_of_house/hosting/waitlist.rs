@@ -0,0 +1,319 @@
+// The waitlist holds walk-in parties until a table is ready for them.
+//
+// Parties are seated first come, first served, but a party can be given a
+// higher priority (for example a guest who needs an accessible table) to
+// move them ahead of everyone with a lower priority. Within the same
+// priority, the order people arrived in is kept.
+
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+use crate::back_of_house::allergens::Allergen;
+use crate::clock::Time;
+
+// Each party is given an id when they join the waitlist, so that they can be
+// found again later even if their name isn't unique
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct PartyId(pub u32);
+
+// Variants are listed lowest first, so deriving PartialOrd gives us
+// Normal < High
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum Priority {
+    #[default]
+    Normal,
+    High,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Party {
+    pub name: String,
+    pub size: u32,
+    pub arrived: Time,
+    pub phone: Option<String>,
+    pub notes: Option<String>,
+    pub priority: Priority,
+    // Anything anyone in the party is allergic to
+    pub allergies: Vec<Allergen>,
+}
+
+impl Party {
+    pub fn new(name: &str, size: u32, arrived: Time) -> Party {
+        Party {
+            name: String::from(name),
+            size,
+            arrived,
+            phone: None,
+            notes: None,
+            priority: Priority::Normal,
+            allergies: Vec::new(),
+        }
+    }
+
+    // The optional details can be chained on after creating the party, e.g.
+    // Party::new("Ng", 4, Time::hm(19, 0)).phone("07700 900123")
+    pub fn phone(mut self, phone: &str) -> Party {
+        self.phone = Some(String::from(phone));
+        self
+    }
+
+    pub fn notes(mut self, notes: &str) -> Party {
+        self.notes = Some(String::from(notes));
+        self
+    }
+
+    pub fn priority(mut self, priority: Priority) -> Party {
+        self.priority = priority;
+        self
+    }
+
+    pub fn allergy(mut self, allergen: Allergen) -> Party {
+        if !self.allergies.contains(&allergen) {
+            self.allergies.push(allergen);
+        }
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WaitlistEntry {
+    pub id: PartyId,
+    pub party: Party,
+}
+
+// Keeps track of how long tables have been taking to turn over (from a party
+// sitting down until the table is free again), which is what wait time
+// estimates are based on. Only the most recent turns are kept, so the
+// estimate follows the pace of the current service.
+#[derive(Debug, Clone)]
+pub struct TableTurnover {
+    tables: u32,
+    default_minutes: u32,
+    recent: VecDeque<u32>,
+}
+
+// How many recent table turns are averaged when estimating wait times
+const TURNOVER_WINDOW: usize = 10;
+
+impl TableTurnover {
+    // `default_minutes` is used as the turn time until some real turns have
+    // been recorded
+    pub fn new(tables: u32, default_minutes: u32) -> TableTurnover {
+        TableTurnover {
+            tables: tables.max(1),
+            default_minutes,
+            recent: VecDeque::new(),
+        }
+    }
+
+    pub fn record_turn(&mut self, minutes: u32) {
+        if self.recent.len() == TURNOVER_WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(minutes);
+    }
+
+    pub fn average_turn(&self) -> u32 {
+        if self.recent.is_empty() {
+            return self.default_minutes;
+        }
+        self.recent.iter().sum::<u32>() / self.recent.len() as u32
+    }
+
+    // With `tables` tables each turning over every `average_turn` minutes, a
+    // table frees up every average_turn / tables minutes on average. So the
+    // party at `position` (0 for the front of the queue) should expect to wait
+    // for position + 1 tables to free up.
+    pub fn estimate(&self, position: usize) -> u32 {
+        let turns = position as u32 + 1;
+        (turns * self.average_turn()).div_ceil(self.tables)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Waitlist {
+    entries: Vec<WaitlistEntry>,
+    next_id: u32,
+    no_shows: Vec<WaitlistEntry>,
+    pub turnover: TableTurnover,
+}
+
+impl Waitlist {
+    pub fn new(turnover: TableTurnover) -> Waitlist {
+        Waitlist {
+            entries: Vec::new(),
+            next_id: 1,
+            no_shows: Vec::new(),
+            turnover,
+        }
+    }
+
+    // Adds a party behind everyone with the same or a higher priority, and
+    // returns the id they can be found by later
+    pub fn add(&mut self, party: Party) -> PartyId {
+        let id = PartyId(self.next_id);
+        self.next_id += 1;
+        let position = self
+            .entries
+            .iter()
+            .position(|e| e.party.priority < party.priority)
+            .unwrap_or(self.entries.len());
+        self.entries.insert(position, WaitlistEntry { id, party });
+        id
+    }
+
+    // The party who should be seated next, if anyone is waiting
+    pub fn next(&self) -> Option<&WaitlistEntry> {
+        self.entries.first()
+    }
+
+    pub fn get(&self, id: PartyId) -> Option<&WaitlistEntry> {
+        self.entries.iter().find(|e| e.id == id)
+    }
+
+    // How far from the front of the queue a party is, starting at 0
+    pub fn position(&self, id: PartyId) -> Option<usize> {
+        self.entries.iter().position(|e| e.id == id)
+    }
+
+    // Takes a party off the waitlist, for example because they have been
+    // seated or have decided not to wait
+    pub fn remove(&mut self, id: PartyId) -> Option<Party> {
+        let position = self.position(id)?;
+        Some(self.entries.remove(position).party)
+    }
+
+    // Takes a party off the waitlist because they didn't turn up when their
+    // table was ready. They are kept on record, so no-shows can be reported
+    // on at the end of the night.
+    pub fn mark_no_show(&mut self, id: PartyId) -> Option<&WaitlistEntry> {
+        let position = self.position(id)?;
+        self.no_shows.push(self.entries.remove(position));
+        self.no_shows.last()
+    }
+
+    pub fn no_shows(&self) -> &[WaitlistEntry] {
+        &self.no_shows
+    }
+
+    // How many minutes a party still on the waitlist can expect to wait,
+    // based on their place in the queue and recent table turnover
+    pub fn estimated_wait(&self, id: PartyId) -> Option<u32> {
+        self.position(id).map(|p| self.turnover.estimate(p))
+    }
+
+    // The estimate for a party joining the back of the queue right now, handy
+    // for quoting a wait before taking someone's name
+    pub fn quote(&self) -> u32 {
+        self.turnover.estimate(self.entries.len())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &WaitlistEntry> {
+        self.entries.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn waitlist() -> Waitlist {
+        Waitlist::new(TableTurnover::new(4, 60))
+    }
+
+    fn names(waitlist: &Waitlist) -> Vec<&str> {
+        waitlist.iter().map(|e| e.party.name.as_str()).collect()
+    }
+
+    #[test]
+    fn parties_are_seated_in_the_order_they_arrived() {
+        let mut waitlist = waitlist();
+        let first = waitlist.add(Party::new("Ng", 4, Time::hm(19, 0)));
+        waitlist.add(Party::new("Patel", 2, Time::hm(19, 2)));
+        waitlist.add(Party::new("Okafor", 3, Time::hm(19, 5)));
+        assert_eq!(names(&waitlist), ["Ng", "Patel", "Okafor"]);
+        assert_eq!(waitlist.next().unwrap().id, first);
+    }
+
+    #[test]
+    fn a_higher_priority_goes_ahead_of_everyone_lower() {
+        let mut waitlist = waitlist();
+        waitlist.add(Party::new("Ng", 4, Time::hm(19, 0)));
+        waitlist.add(Party::new("Patel", 2, Time::hm(19, 2)));
+        let access = waitlist.add(Party::new("Silva", 2, Time::hm(19, 5)).priority(Priority::High));
+        // Behind Silva, as they arrived later with the same priority
+        waitlist.add(Party::new("Kim", 5, Time::hm(19, 6)).priority(Priority::High));
+        waitlist.add(Party::new("Lee", 2, Time::hm(19, 7)));
+        assert_eq!(names(&waitlist), ["Silva", "Kim", "Ng", "Patel", "Lee"]);
+        assert_eq!(waitlist.position(access), Some(0));
+    }
+
+    #[test]
+    fn no_shows_are_taken_off_but_kept_on_record() {
+        let mut waitlist = waitlist();
+        let ng = waitlist.add(Party::new("Ng", 4, Time::hm(19, 0)));
+        let patel = waitlist.add(Party::new("Patel", 2, Time::hm(19, 2)));
+        assert_eq!(waitlist.mark_no_show(ng).unwrap().party.name, "Ng");
+        assert_eq!(waitlist.get(ng), None);
+        assert_eq!(waitlist.position(patel), Some(0));
+        assert_eq!(waitlist.len(), 1);
+        assert_eq!(waitlist.no_shows().len(), 1);
+        assert_eq!(waitlist.no_shows()[0].id, ng);
+        assert!(waitlist.mark_no_show(ng).is_none());
+
+        // Ids aren't handed out again
+        assert_eq!(waitlist.add(Party::new("Ng", 4, Time::hm(19, 30))), PartyId(3));
+        assert_eq!(waitlist.remove(patel).unwrap().name, "Patel");
+        assert_eq!(waitlist.remove(patel), None);
+    }
+
+    #[test]
+    fn waits_are_estimated_from_table_turnover() {
+        let mut waitlist = waitlist();
+        // Nobody waiting: one of four tables turning every hour frees up
+        // in 15 minutes
+        assert_eq!(waitlist.quote(), 15);
+        let first = waitlist.add(Party::new("Ng", 4, Time::hm(19, 0)));
+        waitlist.add(Party::new("Patel", 2, Time::hm(19, 2)));
+        let third = waitlist.add(Party::new("Okafor", 3, Time::hm(19, 5)));
+        assert_eq!(waitlist.estimated_wait(first), Some(15));
+        assert_eq!(waitlist.estimated_wait(third), Some(45));
+        assert_eq!(waitlist.quote(), 60);
+
+        // Faster turns since then bring the estimates down, rounding up
+        for minutes in [50, 45, 70] {
+            waitlist.turnover.record_turn(minutes);
+        }
+        assert_eq!(waitlist.turnover.average_turn(), 55);
+        assert_eq!(waitlist.estimated_wait(first), Some(14));
+        assert_eq!(waitlist.estimated_wait(third), Some(42));
+
+        waitlist.remove(first);
+        assert_eq!(waitlist.estimated_wait(first), None);
+        assert_eq!(waitlist.estimated_wait(third), Some(28));
+    }
+
+    #[test]
+    fn only_recent_turns_count() {
+        let mut turnover = TableTurnover::new(2, 60);
+        turnover.record_turn(200);
+        for _ in 0..TURNOVER_WINDOW {
+            turnover.record_turn(40);
+        }
+        assert_eq!(turnover.average_turn(), 40);
+        assert_eq!(turnover.estimate(0), 20);
+        assert_eq!(turnover.estimate(4), 100);
+
+        // A restaurant with no tables is treated as having one
+        assert_eq!(TableTurnover::new(0, 30).estimate(1), 60);
+    }
+}
@@ -0,0 +1,692 @@
+// Discounts: happy hours, combos, vouchers and loyalty cards.
+//
+// The rules are written in a JSON file (pricing.json ships with the crate),
+// in the same way as the menu:
+//
+//   { "rules": [ { "name": "Early bird", "kind": "time_window",
+//       "from": "07:00", "until": "08:30", "percent": "10",
+//       "items": ["breakfast"] } ] }
+//
+// There are four kinds of rule:
+//
+//   time_window  "percent" off items ordered between "from" and "until"
+//   combo        "amount" off each set of the "items" ordered together
+//   voucher      "percent" or "amount" off, once the guest gives us "code"
+//   loyalty      "percent" off for members of the loyalty scheme's "tier"
+//
+// Any rule but a combo can be limited to some menu items with "items".
+// Discount vouchers come off the price before tax, unlike gift vouchers,
+// which are a way of paying (see TenderKind::Voucher).
+//
+// Stacking works on each single item (each breakfast, not each line of the
+// bill), in this order:
+//
+// - Rules are tried from the highest "priority" to the lowest (0 if it's
+//   not given). Rules with the same priority are tried in file order.
+// - Each rule takes its discount off what's left of the price after the
+//   rules before it, so 10% and then 5% comes to 14.5% rather than 15%.
+// - A rule with "stackable": false only applies to items which have no
+//   discount yet, and once it has applied nothing else can be taken off
+//   them. Rules stack unless they say otherwise.
+//
+// The bill keeps a Discount for each rule that fired on each line, with a
+// sentence saying why, so staff can answer "why is this cheaper?".
+
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::clock::Time;
+use crate::front_of_house::billing::BillLine;
+use crate::money::Money;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Off {
+    // In basis points, as elsewhere
+    Percent(u32),
+    Amount(Money),
+}
+
+impl fmt::Display for Off {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Off::Percent(basis_points) => {
+                let (whole, hundredths) = (basis_points / 100, basis_points % 100);
+                match hundredths {
+                    0 => write!(f, "{}% off", whole),
+                    h if h % 10 == 0 => write!(f, "{}.{}% off", whole, h / 10),
+                    h => write!(f, "{}.{:02}% off", whole, h),
+                }
+            }
+            Off::Amount(amount) => write!(f, "{} off", amount),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RuleKind {
+    TimeWindow { from: Time, until: Time, percent: u32 },
+    Combo { items: Vec<String>, amount: Money },
+    Voucher { code: String, off: Off },
+    Loyalty { tier: String, percent: u32 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PricingRule {
+    pub name: String,
+    pub kind: RuleKind,
+    // Menu ids the rule is limited to, or None for everything
+    pub items: Option<Vec<String>>,
+    pub priority: i32,
+    pub stackable: bool,
+}
+
+impl PricingRule {
+    fn covers(&self, menu_id: &str) -> bool {
+        match &self.items {
+            None => true,
+            Some(ids) => ids.iter().any(|id| id == menu_id),
+        }
+    }
+
+    // Why the rule takes something off, for the bill
+    fn reason(&self) -> String {
+        match &self.kind {
+            RuleKind::TimeWindow { from, until, percent } => {
+                format!("{} when ordered between {} and {}", Off::Percent(*percent), from, until)
+            }
+            RuleKind::Combo { items, amount } => {
+                format!("{} when {} are ordered together", Off::Amount(*amount), items.join(" and "))
+            }
+            RuleKind::Voucher { code, off } => format!("{} with voucher {}", off, code),
+            RuleKind::Loyalty { tier, percent } => format!("{} for {} members", Off::Percent(*percent), tier),
+        }
+    }
+}
+
+// What one rule took off one line of a bill. The amount is negative, like a
+// comp, so it can be added straight to the subtotal.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Discount {
+    pub rule: String,
+    pub line: usize,
+    pub amount: Money,
+    pub reason: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PricingError {
+    Io(String),
+    Parse { line: usize, column: usize, message: String },
+    Invalid { path: String, problem: String },
+}
+
+impl fmt::Display for PricingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PricingError::Io(message) => write!(f, "could not read pricing rules: {}", message),
+            PricingError::Parse { line, column, message } => write!(
+                f,
+                "pricing rules are not valid at line {} column {}: {}",
+                line, column, message
+            ),
+            PricingError::Invalid { path, problem } => write!(f, "{}: {}", path, problem),
+        }
+    }
+}
+
+impl std::error::Error for PricingError {}
+
+// As with the menu, these mirror the file. Every kind of rule is read into
+// the same struct, and `validate` checks it has the fields its kind needs.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRules {
+    rules: Vec<RawRule>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawRule {
+    name: String,
+    kind: String,
+    items: Option<Vec<String>>,
+    #[serde(default)]
+    priority: i32,
+    stackable: Option<bool>,
+    from: Option<String>,
+    until: Option<String>,
+    percent: Option<String>,
+    amount: Option<String>,
+    code: Option<String>,
+    tier: Option<String>,
+}
+
+fn invalid(path: &str, problem: String) -> PricingError {
+    PricingError::Invalid {
+        path: String::from(path),
+        problem,
+    }
+}
+
+fn required<T>(path: &str, field: &str, value: Option<T>) -> Result<T, PricingError> {
+    value.ok_or_else(|| invalid(path, format!("needs a \"{}\"", field)))
+}
+
+fn parse_time(path: &str, time: &str) -> Result<Time, PricingError> {
+    time.parse().map_err(|e| invalid(path, format!("{}", e)))
+}
+
+fn parse_amount(path: &str, amount: &str) -> Result<Money, PricingError> {
+    let money: Money = amount
+        .parse()
+        .map_err(|e| invalid(path, format!("amount {}", e)))?;
+    if !money.is_positive() {
+        return Err(invalid(path, format!("amount {} has to be more than nothing", money)));
+    }
+    Ok(money)
+}
+
+// Percentages are written like "12.5". They have at most two decimal
+// places, the same as an amount of money, so they're read in the same way
+// and the number of cents is the number of basis points.
+fn parse_percent(path: &str, percent: &str) -> Result<u32, PricingError> {
+    let scaled: Money = percent
+        .parse()
+        .map_err(|_| invalid(path, format!("\"{}\" is not a percentage", percent)))?;
+    if !scaled.is_positive() || scaled.cents() > 10_000 {
+        return Err(invalid(path, format!("{}% has to be more than 0 and at most 100", percent)));
+    }
+    Ok(scaled.cents() as u32)
+}
+
+fn validate(raw: RawRules) -> Result<PricingRules, PricingError> {
+    let mut names = HashSet::new();
+    let mut codes = HashSet::new();
+    let mut rules = Vec::new();
+
+    for (r, raw_rule) in raw.rules.into_iter().enumerate() {
+        let path = format!("rules[{}] (\"{}\")", r, raw_rule.name);
+        if raw_rule.name.trim().is_empty() {
+            return Err(invalid(&path, String::from("name can't be empty")));
+        }
+        if !names.insert(raw_rule.name.clone()) {
+            return Err(invalid(&path, String::from("name is used by another rule")));
+        }
+        if let Some(items) = &raw_rule.items {
+            if items.is_empty() || items.iter().any(|id| id.trim().is_empty()) {
+                return Err(invalid(&path, String::from("items has to list at least one menu id")));
+            }
+        }
+
+        // Every field a kind of rule uses, so anything else can be rejected
+        let uses: &[&str] = match raw_rule.kind.as_str() {
+            "time_window" => &["from", "until", "percent"],
+            "combo" => &["amount"],
+            "voucher" => &["code", "percent", "amount"],
+            "loyalty" => &["tier", "percent"],
+            other => return Err(invalid(&path, format!("\"{}\" is not a kind of rule", other))),
+        };
+        let given = [
+            ("from", raw_rule.from.is_some()),
+            ("until", raw_rule.until.is_some()),
+            ("percent", raw_rule.percent.is_some()),
+            ("amount", raw_rule.amount.is_some()),
+            ("code", raw_rule.code.is_some()),
+            ("tier", raw_rule.tier.is_some()),
+        ];
+        if let Some((field, _)) = given.iter().find(|(field, set)| *set && !uses.contains(field)) {
+            return Err(invalid(&path, format!("{} rules don't use \"{}\"", raw_rule.kind, field)));
+        }
+
+        let mut items = raw_rule.items;
+        let kind = match raw_rule.kind.as_str() {
+            "time_window" => {
+                let from = parse_time(&path, &required(&path, "from", raw_rule.from)?)?;
+                let until = parse_time(&path, &required(&path, "until", raw_rule.until)?)?;
+                if from >= until {
+                    return Err(invalid(&path, format!("{} is not before {}", from, until)));
+                }
+                let percent = parse_percent(&path, &required(&path, "percent", raw_rule.percent)?)?;
+                RuleKind::TimeWindow { from, until, percent }
+            }
+            "combo" => {
+                let combo = required(&path, "items", items.take())?;
+                if combo.len() < 2 {
+                    return Err(invalid(&path, String::from("a combo needs at least two items")));
+                }
+                let amount = parse_amount(&path, &required(&path, "amount", raw_rule.amount)?)?;
+                RuleKind::Combo { items: combo, amount }
+            }
+            "voucher" => {
+                let code = required(&path, "code", raw_rule.code)?;
+                if code.trim().is_empty() {
+                    return Err(invalid(&path, String::from("code can't be empty")));
+                }
+                if !codes.insert(code.clone()) {
+                    return Err(invalid(&path, String::from("code is used by another voucher")));
+                }
+                let off = match (raw_rule.percent, raw_rule.amount) {
+                    (Some(percent), None) => Off::Percent(parse_percent(&path, &percent)?),
+                    (None, Some(amount)) => Off::Amount(parse_amount(&path, &amount)?),
+                    _ => return Err(invalid(&path, String::from("needs either a \"percent\" or an \"amount\""))),
+                };
+                RuleKind::Voucher { code, off }
+            }
+            _ => {
+                let tier = required(&path, "tier", raw_rule.tier)?;
+                let percent = parse_percent(&path, &required(&path, "percent", raw_rule.percent)?)?;
+                RuleKind::Loyalty { tier, percent }
+            }
+        };
+
+        rules.push(PricingRule {
+            name: raw_rule.name,
+            kind,
+            items,
+            priority: raw_rule.priority,
+            stackable: raw_rule.stackable.unwrap_or(true),
+        });
+    }
+
+    Ok(PricingRules { rules })
+}
+
+// One of an item on the bill, while the rules are being worked through
+struct Unit<'a> {
+    line: usize,
+    menu_id: &'a str,
+    // What's left of its price after the rules so far
+    price: Money,
+    discounted: bool,
+    // A rule that doesn't stack has applied, so nothing else can
+    closed: bool,
+}
+
+impl Unit<'_> {
+    fn can_take(&self, rule: &PricingRule) -> bool {
+        !self.closed && (rule.stackable || !self.discounted) && self.price.is_positive()
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PricingRules {
+    rules: Vec<PricingRule>,
+}
+
+impl PricingRules {
+    // No discounts at all
+    pub fn new() -> PricingRules {
+        PricingRules::default()
+    }
+
+    pub fn rule(mut self, rule: PricingRule) -> PricingRules {
+        self.rules.push(rule);
+        self
+    }
+
+    pub fn from_json(text: &str) -> Result<PricingRules, PricingError> {
+        let raw: RawRules = serde_json::from_str(text).map_err(|e| {
+            let message = e.to_string();
+            let message = message.split(" at line ").next().unwrap_or_default();
+            PricingError::Parse {
+                line: e.line(),
+                column: e.column(),
+                message: String::from(message),
+            }
+        })?;
+        validate(raw)
+    }
+
+    pub fn load(path: &Path) -> Result<PricingRules, PricingError> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| PricingError::Io(format!("{}: {}", path.display(), e)))?;
+        PricingRules::from_json(&text)
+    }
+
+    // The rules that ship with the crate, in pricing.json
+    pub fn house() -> PricingRules {
+        PricingRules::from_json(include_str!("../../pricing.json"))
+            .expect("pricing.json should always be valid")
+    }
+
+    pub fn rules(&self) -> &[PricingRule] {
+        &self.rules
+    }
+
+    pub fn has_voucher(&self, code: &str) -> bool {
+        self.rules
+            .iter()
+            .any(|r| matches!(&r.kind, RuleKind::Voucher { code: c, .. } if c == code))
+    }
+
+    pub fn has_tier(&self, tier: &str) -> bool {
+        self.rules
+            .iter()
+            .any(|r| matches!(&r.kind, RuleKind::Loyalty { tier: t, .. } if t == tier))
+    }
+
+    // Works out the discounts for a bill's lines. `at` is when the order was
+    // placed, `vouchers` the codes the guests have given us, and `loyalty`
+    // the tier of their loyalty card, if they showed one. Comps are taken
+    // into account: a comped item is already free, so it gets no discount.
+    pub fn apply(&self, lines: &[BillLine], at: Time, vouchers: &[String], loyalty: Option<&str>) -> Vec<Discount> {
+        let mut units = Vec::new();
+        for (i, line) in lines.iter().enumerate().filter(|(_, l)| l.comp_of.is_none()) {
+            let comped: u32 = lines
+                .iter()
+                .filter(|l| l.comp_of == Some(i))
+                .map(|l| l.quantity)
+                .sum();
+            for _ in comped..line.quantity {
+                units.push(Unit {
+                    line: i,
+                    menu_id: &line.menu_id,
+                    price: line.unit_price,
+                    discounted: false,
+                    closed: false,
+                });
+            }
+        }
+
+        // sort_by_key is stable, so rules with the same priority keep their
+        // order from the file
+        let mut rules: Vec<&PricingRule> = self.rules.iter().collect();
+        rules.sort_by_key(|r| std::cmp::Reverse(r.priority));
+
+        let mut discounts: Vec<Discount> = Vec::new();
+        for rule in rules {
+            // Which units the rule takes something off, and how much
+            let taken: Vec<(usize, Money)> = match &rule.kind {
+                RuleKind::TimeWindow { from, until, percent } if *from <= at && at < *until => {
+                    percent_off(&units, rule, *percent)
+                }
+                RuleKind::Voucher { code, off } if vouchers.contains(code) => match off {
+                    Off::Percent(percent) => percent_off(&units, rule, *percent),
+                    Off::Amount(amount) => {
+                        let eligible: Vec<usize> = (0..units.len())
+                            .filter(|&u| units[u].can_take(rule) && rule.covers(units[u].menu_id))
+                            .collect();
+                        amount_off(&units, &eligible, *amount)
+                    }
+                },
+                RuleKind::Loyalty { tier, percent } if loyalty == Some(tier.as_str()) => {
+                    percent_off(&units, rule, *percent)
+                }
+                RuleKind::Combo { items, amount } => {
+                    let mut taken = Vec::new();
+                    let mut used = HashSet::new();
+                    // Each set takes the first of each item that's left
+                    'sets: loop {
+                        let mut set = Vec::new();
+                        for id in items {
+                            let found = (0..units.len()).find(|&u| {
+                                !used.contains(&u)
+                                    && !set.contains(&u)
+                                    && units[u].menu_id == id.as_str()
+                                    && units[u].can_take(rule)
+                            });
+                            match found {
+                                Some(u) => set.push(u),
+                                None => break 'sets,
+                            }
+                        }
+                        used.extend(set.iter().copied());
+                        taken.extend(amount_off(&units, &set, *amount));
+                    }
+                    taken
+                }
+                _ => Vec::new(),
+            };
+
+            for (u, off) in taken.into_iter().filter(|(_, off)| off.is_positive()) {
+                let unit = &mut units[u];
+                unit.price -= off;
+                unit.discounted = true;
+                unit.closed |= !rule.stackable;
+                match discounts.iter_mut().find(|d| d.rule == rule.name && d.line == unit.line) {
+                    Some(discount) => discount.amount -= off,
+                    None => discounts.push(Discount {
+                        rule: rule.name.clone(),
+                        line: unit.line,
+                        amount: -off,
+                        reason: rule.reason(),
+                    }),
+                }
+            }
+        }
+        discounts
+    }
+}
+
+// A percentage off every unit the rule covers
+fn percent_off(units: &[Unit], rule: &PricingRule, percent: u32) -> Vec<(usize, Money)> {
+    (0..units.len())
+        .filter(|&u| units[u].can_take(rule) && rule.covers(units[u].menu_id))
+        .map(|u| (u, units[u].price.percent(percent)))
+        .collect()
+}
+
+// An amount off a group of units, shared between them in proportion to
+// their prices. It never takes off more than they cost.
+fn amount_off(units: &[Unit], chosen: &[usize], amount: Money) -> Vec<(usize, Money)> {
+    let left: Money = chosen.iter().map(|&u| units[u].price).sum();
+    let amount = amount.min(left);
+    let weights: Vec<u64> = chosen.iter().map(|&u| units[u].price.cents() as u64).collect();
+    chosen.iter().copied().zip(amount.allocate(&weights)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line(menu_id: &str, cents: i64, quantity: u32) -> BillLine {
+        BillLine {
+            menu_id: String::from(menu_id),
+            description: String::from(menu_id),
+            quantity,
+            unit_price: Money::from_cents(cents),
+            total: Money::from_cents(cents) * quantity,
+            comp_of: None,
+        }
+    }
+
+    fn rules(json: &str) -> PricingRules {
+        PricingRules::from_json(&format!("{{ \"rules\": [ {} ] }}", json)).unwrap()
+    }
+
+    fn codes(codes: &[&str]) -> Vec<String> {
+        codes.iter().map(|c| String::from(*c)).collect()
+    }
+
+    // (rule, line, cents) for each discount, which is easier to compare
+    fn amounts(discounts: &[Discount]) -> Vec<(&str, usize, i64)> {
+        discounts
+            .iter()
+            .map(|d| (d.rule.as_str(), d.line, d.amount.cents()))
+            .collect()
+    }
+
+    const HAPPY_HOUR: &str = r#"{ "name": "Happy hour", "kind": "time_window",
+        "from": "14:00", "until": "17:00", "percent": "20", "items": ["salad"] }"#;
+
+    #[test]
+    fn a_time_window_only_applies_inside_it() {
+        let rules = rules(HAPPY_HOUR);
+        let lines = [line("salad", 600, 1), line("soup", 525, 1)];
+        let inside = rules.apply(&lines, Time::hm(15, 30), &[], None);
+        assert_eq!(amounts(&inside), [("Happy hour", 0, -120)]);
+
+        assert_eq!(rules.apply(&lines, Time::hm(14, 0), &[], None).len(), 1);
+        assert!(rules.apply(&lines, Time::hm(13, 59), &[], None).is_empty());
+        // The window ends as "until" starts
+        assert!(rules.apply(&lines, Time::hm(17, 0), &[], None).is_empty());
+    }
+
+    #[test]
+    fn a_combo_is_shared_between_its_items_by_price() {
+        let rules = PricingRules::house();
+        let lines = [line("breakfast", 850, 1), line("soup", 525, 2)];
+        let discounts = rules.apply(&lines, Time::hm(12, 0), &[], None);
+        // 1.75 shared 850 to 525, and only one set was ordered
+        assert_eq!(
+            amounts(&discounts),
+            [("Soup and breakfast", 0, -108), ("Soup and breakfast", 1, -67)]
+        );
+
+        let lines = [line("breakfast", 850, 2), line("soup", 525, 2)];
+        let discounts = rules.apply(&lines, Time::hm(12, 0), &[], None);
+        assert_eq!(discounts.iter().map(|d| d.amount.cents()).sum::<i64>(), -350);
+
+        assert!(rules.apply(&[line("soup", 525, 3)], Time::hm(12, 0), &[], None).is_empty());
+    }
+
+    #[test]
+    fn vouchers_take_a_percentage_or_an_amount_off() {
+        let rules = rules(
+            r#"{ "name": "Ten percent", "kind": "voucher", "code": "TEN", "percent": "10" },
+               { "name": "Fiver", "kind": "voucher", "code": "FIVER", "amount": "5.00" }"#,
+        );
+        let lines = [line("soup", 525, 1), line("salad", 600, 1)];
+        assert!(rules.apply(&lines, Time::hm(12, 0), &[], None).is_empty());
+
+        let discounts = rules.apply(&lines, Time::hm(12, 0), &codes(&["TEN"]), None);
+        assert_eq!(amounts(&discounts), [("Ten percent", 0, -53), ("Ten percent", 1, -60)]);
+
+        // 5.00 shared 525 to 600
+        let discounts = rules.apply(&lines, Time::hm(12, 0), &codes(&["FIVER"]), None);
+        assert_eq!(amounts(&discounts), [("Fiver", 0, -233), ("Fiver", 1, -267)]);
+    }
+
+    #[test]
+    fn a_voucher_never_takes_off_more_than_the_items_cost() {
+        let rules = rules(r#"{ "name": "Big voucher", "kind": "voucher", "code": "BIG", "amount": "8.00" }"#);
+        let discounts = rules.apply(&[line("soup", 525, 1)], Time::hm(12, 0), &codes(&["BIG"]), None);
+        assert_eq!(amounts(&discounts), [("Big voucher", 0, -525)]);
+    }
+
+    #[test]
+    fn loyalty_discounts_depend_on_the_tier() {
+        let rules = PricingRules::house();
+        let lines = [line("breakfast", 850, 1)];
+        let gold = rules.apply(&lines, Time::hm(12, 0), &[], Some("gold"));
+        assert_eq!(amounts(&gold), [("Gold members", 0, -85)]);
+        let silver = rules.apply(&lines, Time::hm(12, 0), &[], Some("silver"));
+        assert_eq!(amounts(&silver), [("Silver members", 0, -43)]);
+        assert!(rules.apply(&lines, Time::hm(12, 0), &[], Some("bronze")).is_empty());
+    }
+
+    #[test]
+    fn stacked_rules_take_off_what_is_left() {
+        let rules = PricingRules::house();
+        // 10% of 8.50, then 10% of the 7.65 left
+        let discounts = rules.apply(&[line("breakfast", 850, 1)], Time::hm(8, 0), &[], Some("gold"));
+        assert_eq!(amounts(&discounts), [("Early bird", 0, -85), ("Gold members", 0, -77)]);
+    }
+
+    #[test]
+    fn a_rule_that_does_not_stack_wins_if_it_comes_first() {
+        let staff = r#"{ "name": "Staff", "kind": "voucher", "code": "STAFF",
+            "percent": "50", "stackable": false, "priority": 5 }"#;
+        let lines = [line("salad", 600, 1)];
+        let staff_first = rules(&format!("{}, {}", HAPPY_HOUR, staff));
+        let discounts = staff_first.apply(&lines, Time::hm(15, 0), &codes(&["STAFF"]), None);
+        assert_eq!(amounts(&discounts), [("Staff", 0, -300)]);
+
+        // Once happy hour has taken something off, the staff discount can't
+        let happy_hour_first = rules(&format!(
+            "{}, {}",
+            HAPPY_HOUR.replace("\"items\"", "\"priority\": 9, \"items\""),
+            staff
+        ));
+        let discounts = happy_hour_first.apply(&lines, Time::hm(15, 0), &codes(&["STAFF"]), None);
+        assert_eq!(amounts(&discounts), [("Happy hour", 0, -120)]);
+    }
+
+    #[test]
+    fn comped_items_get_no_discount() {
+        let rules = PricingRules::house();
+        let mut comp = line("breakfast", -850, 1);
+        comp.comp_of = Some(0);
+        let lines = [line("breakfast", 850, 2), comp];
+        let discounts = rules.apply(&lines, Time::hm(12, 0), &[], Some("gold"));
+        assert_eq!(amounts(&discounts), [("Gold members", 0, -85)]);
+    }
+
+    #[test]
+    fn every_discount_says_why() {
+        let rules = PricingRules::house();
+        let lines = [line("breakfast", 850, 1), line("soup", 525, 1), line("salad", 600, 1)];
+        let discounts = rules.apply(&lines, Time::hm(14, 30), &codes(&["WELCOME5"]), Some("silver"));
+        // The combo doesn't stack, and the voucher takes the rest of the
+        // salad, so there's nothing left for the loyalty discount
+        let reasons: Vec<(usize, &str)> = discounts.iter().map(|d| (d.line, d.reason.as_str())).collect();
+        assert_eq!(discounts[3].amount, Money::from_cents(-480));
+        assert_eq!(
+            reasons,
+            [
+                (0, "1.75 off when breakfast and soup are ordered together"),
+                (1, "1.75 off when breakfast and soup are ordered together"),
+                (2, "20% off when ordered between 14:00 and 17:00"),
+                (2, "5.00 off with voucher WELCOME5"),
+            ]
+        );
+    }
+
+    #[test]
+    fn the_bill_shows_the_reasons_against_each_line() {
+        use crate::back_of_house::menu::Menu;
+        use crate::front_of_house::billing::{Bill, BillingConfig};
+        use crate::front_of_house::hosting::PartyId;
+        use crate::front_of_house::serving::{Order, OrderId, OrderItem};
+
+        let menu = Menu::house();
+        let mut order = Order::new(OrderId(1), PartyId(1), Time::hm(7, 30));
+        order.add_item(OrderItem::from_menu(&menu, "breakfast", &["Rye"], 1).unwrap()).unwrap();
+        order.add_item(OrderItem::from_menu(&menu, "salad", &["Ranch"], 1).unwrap()).unwrap();
+        let mut bill = Bill::for_order(&order, &BillingConfig::new().pricing(PricingRules::house()));
+        bill.set_loyalty("gold").unwrap();
+
+        let reasons = |line| bill.discounts_on(line).map(|d| d.reason.clone()).collect::<Vec<_>>();
+        assert_eq!(
+            reasons(0),
+            ["10% off when ordered between 07:00 and 08:30", "10% off for gold members"]
+        );
+        assert_eq!(reasons(1), ["10% off for gold members"]);
+        // 8.50 - 0.85 - 0.77, and 6.00 - 0.60
+        assert_eq!(bill.subtotal(), Money::from_cents(1228));
+    }
+
+    #[test]
+    fn percentages_are_written_out_in_full() {
+        assert_eq!(Off::Percent(1250).to_string(), "12.5% off");
+        assert_eq!(Off::Percent(1275).to_string(), "12.75% off");
+        assert_eq!(Off::Percent(1000).to_string(), "10% off");
+    }
+
+    #[test]
+    fn bad_rules_are_reported_with_where_they_are() {
+        let error = PricingRules::from_json(
+            r#"{ "rules": [ { "name": "Fine", "kind": "loyalty", "tier": "gold", "percent": "10" },
+                            { "name": "Too generous", "kind": "voucher", "code": "ALL", "percent": "150" } ] }"#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "rules[1] (\"Too generous\"): 150% has to be more than 0 and at most 100"
+        );
+
+        let error = PricingRules::from_json(r#"{ "rules": [ { "name": "Odd", "kind": "raffle" } ] }"#).unwrap_err();
+        assert_eq!(error.to_string(), "rules[0] (\"Odd\"): \"raffle\" is not a kind of rule");
+
+        let error = PricingRules::from_json(
+            r#"{ "rules": [ { "name": "Lunch", "kind": "time_window", "from": "14:00",
+                               "until": "12:00", "percent": "10" } ] }"#,
+        )
+        .unwrap_err();
+        assert_eq!(error.to_string(), "rules[0] (\"Lunch\"): 14:00 is not before 12:00");
+    }
+}
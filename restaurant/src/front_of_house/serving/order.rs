@@ -0,0 +1,410 @@
+// An order moves through a fixed set of steps between being taken and being
+// paid for:
+//
+//   Placed -> SentToKitchen -> Cooking -> Ready -> Served -> Paid
+//
+// An order can be Cancelled up until the kitchen starts cooking it. After
+// that the food has been made, so the order has to be Voided instead (which
+// writes it off). Paid, Cancelled and Voided are final.
+//
+// The status is stored as an enum, and every change goes through a single
+// `transition` method which checks it against the list of allowed moves.
+// Anything else is rejected with an OrderError, so an order can't, for
+// example, be served before it has been cooked.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::back_of_house::menu::{Menu, MenuError};
+use crate::clock::Time;
+use crate::front_of_house::hosting::PartyId;
+use crate::money::Money;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct OrderId(pub u32);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum OrderStatus {
+    Placed,
+    SentToKitchen,
+    Cooking,
+    Ready,
+    Served,
+    Paid,
+    Cancelled,
+    Voided,
+}
+
+impl OrderStatus {
+    // Whether an order is allowed to move from this status to `next`
+    pub fn can_become(&self, next: OrderStatus) -> bool {
+        use OrderStatus::*;
+        matches!(
+            (self, next),
+            (Placed, SentToKitchen)
+                | (SentToKitchen, Cooking)
+                | (Cooking, Ready)
+                | (Ready, Served)
+                | (Served, Paid)
+                | (Placed | SentToKitchen, Cancelled)
+                | (Cooking | Ready | Served, Voided)
+        )
+    }
+
+    pub fn is_final(&self) -> bool {
+        matches!(self, OrderStatus::Paid | OrderStatus::Cancelled | OrderStatus::Voided)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OrderError {
+    IllegalTransition { order: OrderId, from: OrderStatus, to: OrderStatus },
+    // Items can only be added or removed while the order is still Placed
+    NotEditable { order: OrderId, status: OrderStatus },
+    NoSuchItem { order: OrderId, index: usize },
+    EmptyOrder(OrderId),
+    UnknownOrder(OrderId),
+}
+
+impl fmt::Display for OrderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OrderError::IllegalTransition { order, from, to } => write!(
+                f,
+                "order {} can't go from {:?} to {:?}",
+                order.0, from, to
+            ),
+            OrderError::NotEditable { order, status } => write!(
+                f,
+                "order {} is {:?} and can no longer be changed",
+                order.0, status
+            ),
+            OrderError::NoSuchItem { order, index } => {
+                write!(f, "order {} has no item {}", order.0, index)
+            }
+            OrderError::EmptyOrder(order) => write!(f, "order {} has nothing on it", order.0),
+            OrderError::UnknownOrder(order) => write!(f, "there is no order {}", order.0),
+        }
+    }
+}
+
+impl std::error::Error for OrderError {}
+
+// A line on an order. The name and price are copied from the menu when the
+// item is ordered, so later changes to the menu don't alter existing orders.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrderItem {
+    pub menu_id: String,
+    pub name: String,
+    pub modifiers: Vec<String>,
+    pub quantity: u32,
+    // The price of one item, including any modifiers
+    pub unit_price: Money,
+}
+
+impl OrderItem {
+    pub fn from_menu(
+        menu: &Menu,
+        id: &str,
+        modifiers: &[&str],
+        quantity: u32,
+    ) -> Result<OrderItem, MenuError> {
+        let item = menu.item(id)?;
+        if quantity == 0 {
+            return Err(MenuError::ZeroQuantity(item.name.clone()));
+        }
+        let modifiers: Vec<String> = modifiers.iter().map(|m| String::from(*m)).collect();
+        let unit_price = item.price_with(&modifiers)?;
+        Ok(OrderItem {
+            menu_id: item.id.clone(),
+            name: item.name.clone(),
+            modifiers,
+            quantity,
+            unit_price,
+        })
+    }
+
+    pub fn total(&self) -> Money {
+        self.unit_price * self.quantity
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Order {
+    pub id: OrderId,
+    pub party: PartyId,
+    items: Vec<OrderItem>,
+    status: OrderStatus,
+    // Every status the order has been in, and when it got there
+    history: Vec<(OrderStatus, Time)>,
+}
+
+impl Order {
+    pub fn new(id: OrderId, party: PartyId, placed_at: Time) -> Order {
+        Order {
+            id,
+            party,
+            items: Vec::new(),
+            status: OrderStatus::Placed,
+            history: vec![(OrderStatus::Placed, placed_at)],
+        }
+    }
+
+    pub fn status(&self) -> OrderStatus {
+        self.status
+    }
+
+    pub fn items(&self) -> &[OrderItem] {
+        &self.items
+    }
+
+    pub fn history(&self) -> &[(OrderStatus, Time)] {
+        &self.history
+    }
+
+    pub fn add_item(&mut self, item: OrderItem) -> Result<(), OrderError> {
+        self.check_editable()?;
+        self.items.push(item);
+        Ok(())
+    }
+
+    pub fn remove_item(&mut self, index: usize) -> Result<OrderItem, OrderError> {
+        self.check_editable()?;
+        if index >= self.items.len() {
+            return Err(OrderError::NoSuchItem {
+                order: self.id,
+                index,
+            });
+        }
+        Ok(self.items.remove(index))
+    }
+
+    fn check_editable(&self) -> Result<(), OrderError> {
+        if self.status != OrderStatus::Placed {
+            return Err(OrderError::NotEditable {
+                order: self.id,
+                status: self.status,
+            });
+        }
+        Ok(())
+    }
+
+    // The one place where the status changes, so every change is checked
+    fn transition(&mut self, to: OrderStatus, at: Time) -> Result<(), OrderError> {
+        if !self.status.can_become(to) {
+            return Err(OrderError::IllegalTransition {
+                order: self.id,
+                from: self.status,
+                to,
+            });
+        }
+        self.status = to;
+        self.history.push((to, at));
+        Ok(())
+    }
+
+    pub fn send_to_kitchen(&mut self, at: Time) -> Result<(), OrderError> {
+        if self.items.is_empty() {
+            return Err(OrderError::EmptyOrder(self.id));
+        }
+        self.transition(OrderStatus::SentToKitchen, at)
+    }
+
+    pub fn start_cooking(&mut self, at: Time) -> Result<(), OrderError> {
+        self.transition(OrderStatus::Cooking, at)
+    }
+
+    pub fn mark_ready(&mut self, at: Time) -> Result<(), OrderError> {
+        self.transition(OrderStatus::Ready, at)
+    }
+
+    pub fn serve(&mut self, at: Time) -> Result<(), OrderError> {
+        self.transition(OrderStatus::Served, at)
+    }
+
+    pub fn pay(&mut self, at: Time) -> Result<(), OrderError> {
+        self.transition(OrderStatus::Paid, at)
+    }
+
+    pub fn cancel(&mut self, at: Time) -> Result<(), OrderError> {
+        self.transition(OrderStatus::Cancelled, at)
+    }
+
+    pub fn void(&mut self, at: Time) -> Result<(), OrderError> {
+        self.transition(OrderStatus::Voided, at)
+    }
+}
+
+// Every order taken during a service, which hands out order ids
+#[derive(Debug, Clone, Default)]
+pub struct OrderBook {
+    orders: Vec<Order>,
+}
+
+impl OrderBook {
+    pub fn new() -> OrderBook {
+        OrderBook { orders: Vec::new() }
+    }
+
+    pub fn open(&mut self, party: PartyId, at: Time) -> OrderId {
+        let id = OrderId(self.orders.len() as u32 + 1);
+        self.orders.push(Order::new(id, party, at));
+        id
+    }
+
+    pub fn get(&self, id: OrderId) -> Result<&Order, OrderError> {
+        self.orders
+            .iter()
+            .find(|o| o.id == id)
+            .ok_or(OrderError::UnknownOrder(id))
+    }
+
+    pub fn get_mut(&mut self, id: OrderId) -> Result<&mut Order, OrderError> {
+        self.orders
+            .iter_mut()
+            .find(|o| o.id == id)
+            .ok_or(OrderError::UnknownOrder(id))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Order> {
+        self.orders.iter()
+    }
+
+    pub fn for_party(&self, party: PartyId) -> impl Iterator<Item = &Order> {
+        self.orders.iter().filter(move |o| o.party == party)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn nobody_orders_none_of_something() {
+        let error = OrderItem::from_menu(&Menu::house(), "soup", &[], 0).unwrap_err();
+        assert_eq!(error, MenuError::ZeroQuantity(String::from("Soup of the day")));
+        let item = OrderItem::from_menu(&Menu::house(), "soup", &["No bread"], 3).unwrap();
+        assert_eq!(item.total(), item.unit_price * 3);
+    }
+
+    fn soup_order() -> Order {
+        let mut order = Order::new(OrderId(7), PartyId(3), Time::hm(12, 0));
+        order.add_item(OrderItem::from_menu(&Menu::house(), "soup", &[], 2).unwrap()).unwrap();
+        order
+    }
+
+    fn illegal(from: OrderStatus, to: OrderStatus) -> Result<(), OrderError> {
+        Err(OrderError::IllegalTransition { order: OrderId(7), from, to })
+    }
+
+    #[test]
+    fn an_order_goes_from_placed_to_paid() {
+        use OrderStatus::*;
+        let mut order = soup_order();
+        assert_eq!(order.status(), Placed);
+        order.send_to_kitchen(Time::hm(12, 1)).unwrap();
+        order.start_cooking(Time::hm(12, 2)).unwrap();
+        order.mark_ready(Time::hm(12, 10)).unwrap();
+        order.serve(Time::hm(12, 12)).unwrap();
+        order.pay(Time::hm(12, 40)).unwrap();
+        assert_eq!(order.status(), Paid);
+        assert!(order.status().is_final());
+        assert_eq!(
+            order.history(),
+            [
+                (Placed, Time::hm(12, 0)),
+                (SentToKitchen, Time::hm(12, 1)),
+                (Cooking, Time::hm(12, 2)),
+                (Ready, Time::hm(12, 10)),
+                (Served, Time::hm(12, 12)),
+                (Paid, Time::hm(12, 40)),
+            ]
+        );
+    }
+
+    #[test]
+    fn steps_cant_be_skipped_or_taken_backwards() {
+        use OrderStatus::*;
+        let mut order = soup_order();
+        assert_eq!(order.serve(Time::hm(12, 1)), illegal(Placed, Served));
+        assert_eq!(order.start_cooking(Time::hm(12, 1)), illegal(Placed, Cooking));
+        order.send_to_kitchen(Time::hm(12, 1)).unwrap();
+        order.start_cooking(Time::hm(12, 2)).unwrap();
+        assert_eq!(order.pay(Time::hm(12, 3)), illegal(Cooking, Paid));
+        assert_eq!(order.send_to_kitchen(Time::hm(12, 3)), illegal(Cooking, SentToKitchen));
+        order.mark_ready(Time::hm(12, 10)).unwrap();
+        assert_eq!(order.start_cooking(Time::hm(12, 11)), illegal(Ready, Cooking));
+
+        // A refused step changes nothing
+        assert_eq!(order.status(), Ready);
+        assert_eq!(order.history().len(), 4);
+        assert_eq!(
+            illegal(Ready, Cooking).unwrap_err().to_string(),
+            "order 7 can't go from Ready to Cooking"
+        );
+    }
+
+    #[test]
+    fn nothing_happens_to_an_order_once_its_final() {
+        use OrderStatus::*;
+        let mut paid = soup_order();
+        for step in [Order::send_to_kitchen, Order::start_cooking, Order::mark_ready, Order::serve, Order::pay] {
+            step(&mut paid, Time::hm(12, 30)).unwrap();
+        }
+        assert_eq!(paid.void(Time::hm(12, 45)), illegal(Paid, Voided));
+        assert_eq!(paid.pay(Time::hm(12, 45)), illegal(Paid, Paid));
+
+        let mut cancelled = soup_order();
+        cancelled.cancel(Time::hm(12, 5)).unwrap();
+        assert_eq!(cancelled.send_to_kitchen(Time::hm(12, 6)), illegal(Cancelled, SentToKitchen));
+        assert_eq!(cancelled.void(Time::hm(12, 6)), illegal(Cancelled, Voided));
+
+        let mut voided = soup_order();
+        voided.send_to_kitchen(Time::hm(12, 1)).unwrap();
+        voided.start_cooking(Time::hm(12, 2)).unwrap();
+        // Too late to cancel once it's cooking, but it can be written off
+        assert_eq!(voided.cancel(Time::hm(12, 3)), illegal(Cooking, Cancelled));
+        voided.void(Time::hm(12, 3)).unwrap();
+        assert_eq!(voided.mark_ready(Time::hm(12, 10)), illegal(Voided, Ready));
+        assert_eq!(voided.cancel(Time::hm(12, 10)), illegal(Voided, Cancelled));
+    }
+
+    #[test]
+    fn items_can_only_change_before_the_kitchen_has_the_order() {
+        let mut order = soup_order();
+        let salad = OrderItem::from_menu(&Menu::house(), "salad", &["Ranch"], 1).unwrap();
+        order.add_item(salad.clone()).unwrap();
+        assert_eq!(order.remove_item(5), Err(OrderError::NoSuchItem { order: OrderId(7), index: 5 }));
+        assert_eq!(order.remove_item(1), Ok(salad.clone()));
+
+        order.send_to_kitchen(Time::hm(12, 1)).unwrap();
+        let not_editable = OrderError::NotEditable {
+            order: OrderId(7),
+            status: OrderStatus::SentToKitchen,
+        };
+        assert_eq!(order.add_item(salad), Err(not_editable.clone()));
+        assert_eq!(order.remove_item(0), Err(not_editable));
+        assert_eq!(order.items().len(), 1);
+    }
+
+    #[test]
+    fn an_empty_order_cant_go_to_the_kitchen() {
+        let mut order = Order::new(OrderId(8), PartyId(3), Time::hm(12, 0));
+        assert_eq!(order.send_to_kitchen(Time::hm(12, 1)), Err(OrderError::EmptyOrder(OrderId(8))));
+        assert_eq!(order.status(), OrderStatus::Placed);
+        order.cancel(Time::hm(12, 2)).unwrap();
+    }
+
+    #[test]
+    fn the_order_book_hands_out_ids() {
+        let mut book = OrderBook::new();
+        let first = book.open(PartyId(1), Time::hm(12, 0));
+        let second = book.open(PartyId(2), Time::hm(12, 5));
+        let third = book.open(PartyId(1), Time::hm(12, 30));
+        assert_eq!((first, second, third), (OrderId(1), OrderId(2), OrderId(3)));
+        let ids: Vec<OrderId> = book.for_party(PartyId(1)).map(|o| o.id).collect();
+        assert_eq!(ids, [first, third]);
+        assert_eq!(book.get(OrderId(9)).unwrap_err(), OrderError::UnknownOrder(OrderId(9)));
+    }
+}
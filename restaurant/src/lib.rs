@@ -5,6 +5,15 @@
 // lib.rs (and main.rs) are known as crate roots, and are where the Rust
 // compiler will start.
 
+// Types shared by the whole crate, like the time of day, live in their own
+// files. See tidy_restaurant for more on splitting a crate up this way.
+pub mod clock;
+pub mod money;
+pub mod printing;
+pub mod server;
+pub mod simulation;
+pub mod storage;
+
 // We can use modules to group related functions. Modules can be
 // defined inline, or in the file system.
 mod front_of_house {
@@ -13,14 +22,122 @@ mod front_of_house {
     // are known as siblings (defined at the same level). In relation
     // to front_of_house, they are considered to be children.
     pub mod hosting {
-        pub fn add_to_waitlist() {}
-        pub fn seat_at_table() {}
+        // An inline module can still have children in the file system. As
+        // hosting is defined inside front_of_house in lib.rs, the compiler
+        // looks for this one in src/front_of_house/hosting/waitlist.rs
+        mod floor;
+        mod reservations;
+        mod waitlist;
+
+        pub use floor::{Floor, Seating, SeatingError, Table, TableChoice, TableId};
+        pub use reservations::{
+            Reservation, ReservationBook, ReservationError, ReservationId, ReservationStatus,
+            NO_SHOW_MINUTES,
+        };
+        pub use waitlist::{Party, PartyId, Priority, TableTurnover, Waitlist, WaitlistEntry};
+        use crate::clock::Time;
+
+        // Puts a walk-in party on the waitlist, returning the id they can be
+        // looked up by when their table is ready
+        pub fn add_to_waitlist(waitlist: &mut Waitlist, party: Party) -> PartyId {
+            waitlist.add(party)
+        }
+
+        // Sits the best fitting waiting party at a free table (or tables
+        // pushed together). See floor.rs for how the party is chosen.
+        //
+        // Parties with a booking join the waitlist when they check in, so
+        // they're seated along with the walk-ins. Tables booked for later
+        // are kept back from anyone who wouldn't be finished in time, going
+        // by how long tables have been taking to turn over.
+        pub fn seat_at_table(
+            waitlist: &mut Waitlist,
+            reservations: &mut ReservationBook,
+            floor: &mut Floor,
+            now: Time,
+        ) -> Result<Seating, SeatingError> {
+            let stay = waitlist.turnover.average_turn();
+            let seating = floor::seat_best_fit(waitlist, floor, now, |party, table| {
+                reservations.allows(party, table, now, stay)
+            })?;
+            reservations.mark_seated(seating.id);
+            Ok(seating)
+        }
+
+        // Frees up a party's tables once they leave, and records how long
+        // they stayed so that wait estimates keep up with the evening
+        pub fn clear_table(
+            waitlist: &mut Waitlist,
+            floor: &mut Floor,
+            seating: &Seating,
+            now: Time,
+        ) -> Vec<TableId> {
+            waitlist.turnover.record_turn(now.minutes_since(seating.seated_at));
+            floor.release(seating.id)
+        }
     }
 
+    // Bills, taxes and splitting live in src/front_of_house/billing.rs, and
+    // the discounts that can come off them in src/front_of_house/pricing.rs
+    pub mod billing;
+    pub mod pricing;
+
+    // Drivers, routes and delivery times, in src/front_of_house/delivery.rs
+    pub mod delivery;
+
     pub mod serving {
-        fn take_order() {}
-        fn serve_order() {}
-        fn take_payment() {}
+        // As with hosting, the bulk of the code lives in the file system, at
+        // src/front_of_house/serving/order.rs
+        mod order;
+
+        pub use order::{Order, OrderBook, OrderError, OrderId, OrderItem, OrderStatus};
+
+        use super::billing::{Bill, BillingError, Tender, TenderReceipt};
+        use super::hosting::PartyId;
+        use crate::clock::Time;
+
+        // Opens a new order for a party. It stays in the Placed state, so
+        // more items can be added, until it is sent to the kitchen.
+        pub fn take_order(
+            orders: &mut OrderBook,
+            party: PartyId,
+            items: Vec<OrderItem>,
+            at: Time,
+        ) -> Result<OrderId, OrderError> {
+            let id = orders.open(party, at);
+            let order = orders.get_mut(id)?;
+            for item in items {
+                order.add_item(item)?;
+            }
+            Ok(id)
+        }
+
+        pub fn serve_order(orders: &mut OrderBook, id: OrderId, at: Time) -> Result<(), OrderError> {
+            orders.get_mut(id)?.serve(at)
+        }
+
+        // Records a payment (cash, card or voucher) against an order's bill.
+        // A bill can be settled with several tenders; once it is, the order
+        // moves on to Paid.
+        pub fn take_payment(
+            orders: &mut OrderBook,
+            bill: &mut Bill,
+            tender: Tender,
+        ) -> Result<TenderReceipt, BillingError> {
+            let order = orders.get_mut(bill.order)?;
+            if !order.status().can_become(OrderStatus::Paid) {
+                return Err(BillingError::Order(OrderError::IllegalTransition {
+                    order: order.id,
+                    from: order.status(),
+                    to: OrderStatus::Paid,
+                }));
+            }
+            let receipt = bill.record_tender(tender)?;
+            if bill.is_settled() {
+                order.pay(tender.at)?;
+            }
+            Ok(receipt)
+        }
     }
 }
 
@@ -33,14 +150,105 @@ pub fn eat_at_restaurant() {
     // it as a public module. Similarly, to reference and use functions, they
     // must be public functions
 
+    let mut waitlist = hosting::Waitlist::new(hosting::TableTurnover::new(12, 45));
+    let arrived = clock::Time::hm(19, 0);
+
     // Absolute_path
-    crate::front_of_house::hosting::add_to_waitlist();
+    crate::front_of_house::hosting::add_to_waitlist(
+        &mut waitlist,
+        hosting::Party::new("Patel", 4, arrived).phone("07700 900123"),
+    );
 
     // Relative path
-    front_of_house::hosting::add_to_waitlist();
+    front_of_house::hosting::add_to_waitlist(
+        &mut waitlist,
+        hosting::Party::new("Okafor", 2, arrived)
+            .notes("Needs step-free access")
+            .priority(hosting::Priority::High),
+    );
+
+    for entry in waitlist.iter() {
+        println!(
+            "{} (party of {}) should wait about {} minutes",
+            entry.party.name,
+            entry.party.size,
+            waitlist.estimated_wait(entry.id).unwrap_or_default()
+        );
+    }
+
+    // Two tables for two which can be pushed together, and a table for six
+    let mut floor = hosting::Floor::new();
+    let t1 = floor.add_table(2);
+    let t2 = floor.add_table(2);
+    floor.add_table(6);
+    floor.join(t1, t2).expect("both tables were just added");
+
+    // A party this big can never be seated, even with every table joined up
+    hosting::add_to_waitlist(&mut waitlist, hosting::Party::new("Book club", 12, arrived));
+
+    // The table for six is booked from half past seven, so there's no room
+    // for a second party of six at eight until we move them to nine
+    let mut reservations = hosting::ReservationBook::new();
+    let booked = reservations
+        .book(&floor, hosting::Party::new("Nakamura", 6, arrived), clock::Time::hm(19, 30), 90, arrived)
+        .expect("the table for six is free all evening");
+    let late = hosting::Party::new("Dubois", 6, arrived).phone("07700 900456");
+    match reservations.book(&floor, late.clone(), clock::Time::hm(20, 0), 90, arrived) {
+        Ok(_) => println!("Booked Dubois at 20:00"),
+        Err(e) => println!("Couldn't book Dubois: {}", e),
+    }
+    let moved = reservations
+        .book(&floor, late, clock::Time::hm(21, 0), 90, arrived)
+        .expect("the table for six is free again by nine");
+    reservations
+        .modify(&floor, moved, 5, clock::Time::hm(21, 15), 75, arrived)
+        .expect("there's still room at quarter past nine");
+    if let Ok(cancelled) = reservations.cancel(moved) {
+        println!("{} cancelled their booking", cancelled.party.name);
+    }
+
+    // Keep seating people until we run out of tables or guests. Nobody can
+    // have the table for six, as it's needed for the Nakamuras before they
+    // would be finished.
+    let seated_at = clock::Time::hm(19, 10);
+    loop {
+        match hosting::seat_at_table(&mut waitlist, &mut reservations, &mut floor, seated_at) {
+            Ok(seating) => {
+                println!("Seated {} at {:?}", seating.party.name, seating.tables);
+                hosting::clear_table(&mut waitlist, &mut floor, &seating, clock::Time::hm(20, 5));
+            }
+            Err(e) => {
+                println!("Stopped seating: {}", e);
+                break;
+            }
+        }
+    }
+
+    // The Nakamuras arrive a little early for their booking
+    let here = clock::Time::hm(19, 25);
+    reservations
+        .check_in(&mut waitlist, booked, here)
+        .expect("the booking hasn't been checked in yet");
+    match hosting::seat_at_table(&mut waitlist, &mut reservations, &mut floor, here) {
+        Ok(seating) => println!("Seated {} at {:?}", seating.party.name, seating.tables),
+        Err(e) => println!("{}", e),
+    }
 }
 
-fn deliver_order() {}
+// Sends an order the kitchen has finished out for delivery. It waits in the
+// queue until the next dispatch finds it a driver.
+//
+// The `use std::fmt::Result` further down replaces the usual Result in this
+// module, so we have to name the standard one in full.
+pub fn deliver_order(
+    dispatcher: &mut delivery::Dispatcher,
+    orders: &serving::OrderBook,
+    order: serving::OrderId,
+    address: delivery::Address,
+    at: clock::Time,
+) -> std::result::Result<delivery::DeliveryId, delivery::DeliveryError> {
+    dispatcher.queue(orders.get(order)?, address, at)
+}
 
 mod back_of_house {
     // We can use the `super` keyword to access code defined outside
@@ -48,12 +256,82 @@ mod back_of_house {
     // can make it easier to move code around without needing to update
     // any references by hand
 
-    fn fix_incorrect_order() {
-        cook_order();
-        super::deliver_order();
+    // Flags an item a guest sent back and cooks one of it again as a rush
+    // job. If a bill is given, that one is comped on it. Nothing is sent to
+    // the kitchen or taken off the bill unless both can be done, and every
+    // remake is written to the log for the end-of-day report.
+    //
+    // The new plate goes out to the table like any other, so unlike the
+    // original outline there's nothing here for deliver_order to do.
+    pub fn fix_incorrect_order(
+        kitchen: &mut Kitchen,
+        remakes: &mut RemakeLog,
+        order: &Order,
+        item: usize,
+        reason: RemakeReason,
+        comp_on: Option<&mut Bill>,
+        at: Time,
+    ) -> Result<RemakeId, RemakeError> {
+        if let Some(bill) = &comp_on {
+            if bill.order != order.id {
+                return Err(RemakeError::WrongBill {
+                    order: order.id,
+                    bill_for: bill.order,
+                });
+            }
+            bill.can_comp(item)?;
+        }
+        let ticket = kitchen.remake(order, item)?;
+        let comped = match comp_on {
+            Some(bill) => Some(bill.comp(item, &reason.to_string())?),
+            None => None,
+        };
+        Ok(remakes.record(order, item, reason, at, ticket, comped))
     }
 
-    fn cook_order() {}
+    // Hands an order that has been sent to the kitchen over to the stations
+    // to cook. The order only moves on to Cooking once every item on it has
+    // found a station.
+    pub fn cook_order(
+        kitchen: &mut Kitchen,
+        order: &mut Order,
+        at: Time,
+    ) -> Result<TicketId, KitchenError> {
+        if !order.status().can_become(OrderStatus::Cooking) {
+            return Err(KitchenError::Order(OrderError::IllegalTransition {
+                order: order.id,
+                from: order.status(),
+                to: OrderStatus::Cooking,
+            }));
+        }
+        let ticket = kitchen.submit(order)?;
+        order.start_cooking(at)?;
+        Ok(ticket)
+    }
+
+    // The full menu, with prices, lives in src/back_of_house/menu.rs, the
+    // seasonal fruit calendar in src/back_of_house/produce.rs, and the
+    // stations and their tickets in src/back_of_house/kitchen.rs. Remakes
+    // are logged in src/back_of_house/remakes.rs, and what's left in the
+    // stores is tracked in src/back_of_house/inventory.rs, with the
+    // allergens each dish contains in src/back_of_house/allergens.rs and
+    // what it costs us to make in src/back_of_house/costing.rs.
+    pub mod allergens;
+    pub mod costing;
+    pub mod inventory;
+    pub mod kitchen;
+    pub mod menu;
+    pub mod produce;
+    pub mod remakes;
+
+    use allergens::{AllergenError, AllergenGuide, DietaryInfo};
+    use crate::clock::{Date, Time};
+    use crate::serving::{Order, OrderError, OrderItem, OrderStatus};
+    use crate::billing::Bill;
+    use kitchen::{Kitchen, KitchenError, TicketId};
+    use remakes::{RemakeError, RemakeId, RemakeLog, RemakeReason};
+    use menu::{Menu, MenuError};
+    use produce::{Hemisphere, NothingInSeason, ProduceCalendar, Season};
 
     // Structs and enums can also be made public
     pub struct Breakfast {
@@ -68,10 +346,56 @@ mod back_of_house {
         // facing function which creates an instance of it if the struct is to
         // be used outside of the back_of_house module
         pub fn summer(toast: &str) -> Breakfast {
-            Breakfast {
+            Breakfast::for_season(Season::Summer, toast)
+                .expect("the standard calendar always has fruit in summer")
+        }
+
+        // The same idea generalised to any season, using the standard
+        // produce calendar
+        pub fn for_season(season: Season, toast: &str) -> Result<Breakfast, NothingInSeason> {
+            let calendar = ProduceCalendar::standard(Hemisphere::Northern);
+            let fruit = calendar.fruit_for_season(season)?;
+            Ok(Breakfast {
                 toast: String::from(toast),
-                seasonal_fruit: String::from("peaches"),
-            }
+                seasonal_fruit: String::from(fruit),
+            })
+        }
+
+        // Picks the fruit that's in season on a particular day. If nothing
+        // is, we return an error rather than making up a fruit.
+        pub fn for_date(date: Date, toast: &str) -> Result<Breakfast, NothingInSeason> {
+            Breakfast::for_date_with(&ProduceCalendar::standard(Hemisphere::Northern), date, toast)
+        }
+
+        // As for_date, but with a calendar of our choosing (for example, one
+        // for a restaurant in the southern hemisphere)
+        pub fn for_date_with(
+            calendar: &ProduceCalendar,
+            date: Date,
+            toast: &str,
+        ) -> Result<Breakfast, NothingInSeason> {
+            let fruit = calendar.fruit_for(date)?;
+            Ok(Breakfast {
+                toast: String::from(toast),
+                seasonal_fruit: String::from(fruit),
+            })
+        }
+
+        // A getter lets people see the fruit without being able to change
+        // it, so the field itself can stay private
+        pub fn seasonal_fruit(&self) -> &str {
+            &self.seasonal_fruit
+        }
+
+        // Turns the meal into a line on an order, with the chosen toast
+        // as a modifier
+        pub fn order_item(&self, menu: &Menu) -> Result<OrderItem, MenuError> {
+            OrderItem::from_menu(menu, "breakfast", &[&self.toast], 1)
+        }
+
+        // What's in the meal, going by its recipe and the toast chosen
+        pub fn dietary(&self, guide: &AllergenGuide) -> Result<DietaryInfo, AllergenError> {
+            guide.info("breakfast", std::slice::from_ref(&self.toast))
         }
     }
 
@@ -80,6 +404,21 @@ mod back_of_house {
         Soup,
         Salad
     }
+
+    impl Appetizer {
+        // Where this appetizer can be found in the menu catalog
+        pub fn menu_id(&self) -> &'static str {
+            match self {
+                Appetizer::Soup => "soup",
+                Appetizer::Salad => "salad",
+            }
+        }
+
+        // What's in the appetizer itself, before any options are chosen
+        pub fn dietary(&self, guide: &AllergenGuide) -> Result<DietaryInfo, AllergenError> {
+            guide.info(self.menu_id(), &[])
+        }
+    }
 }
 
 pub fn eat_at_restaurant_2() {
@@ -94,8 +433,205 @@ pub fn eat_at_restaurant_2() {
     // to see or modify the seasonal fruit that comes with the meal
     // meal.seasonal_fruit = String::from("blueberries");
 
+    // We can read it through a getter method though
+    println!("It comes with {}", meal.seasonal_fruit());
+
+    // The fruit can also be picked for a particular date. In March nothing is
+    // in season, unless the restaurant is in the southern hemisphere.
+    let march = clock::Date::new(2024, 3, 14).expect("14th March is a real date");
+    match back_of_house::Breakfast::for_date(march, "Rye") {
+        Ok(breakfast) => println!("On {} it comes with {}", march, breakfast.seasonal_fruit()),
+        Err(e) => println!("{}", e),
+    }
+    let southern = produce::ProduceCalendar::standard(produce::Hemisphere::Southern);
+    if let Ok(breakfast) = back_of_house::Breakfast::for_date_with(&southern, march, "Rye") {
+        println!("In the southern hemisphere it comes with {}", breakfast.seasonal_fruit());
+    }
+
     let order_1 = back_of_house::Appetizer::Soup;
     let order_2 = back_of_house::Appetizer::Salad;
+
+    // Look everything up on the menu to get its price
+    let mut menu = menu::Menu::house();
+    let items = vec![
+        meal.order_item(&menu).expect("wheat toast is on the menu"),
+        serving::OrderItem::from_menu(&menu, order_1.menu_id(), &["Crusty roll"], 1)
+            .expect("soup comes with a crusty roll"),
+        serving::OrderItem::from_menu(&menu, order_2.menu_id(), &["Walnut oil"], 2)
+            .expect("salad comes with walnut oil dressing"),
+    ];
+    for item in &items {
+        println!("{} x{} {:?}: {}", item.name, item.quantity, item.modifiers, item.total());
+    }
+
+    // Salad needs a dressing choosing, so this is rejected
+    if let Err(e) = serving::OrderItem::from_menu(&menu, order_2.menu_id(), &[], 1) {
+        println!("{}", e);
+    }
+
+    // What's in everything, worked out from the recipes
+    let guide = allergens::AllergenGuide::house();
+    if let Ok(info) = meal.dietary(&guide) {
+        println!("Breakfast with {} toast: {}", meal.toast, info);
+    }
+    for appetizer in [&order_1, &order_2] {
+        if let Ok(info) = appetizer.dietary(&guide) {
+            println!("{}: {}", appetizer.menu_id(), info);
+        }
+    }
+    let query: allergens::Query = "vegan and nut-free".parse().expect("a valid query");
+    for found in guide.matching(&menu, &query).expect("every house dish has a recipe") {
+        println!("Vegan and nut-free: {} (avoid {:?})", found.name, found.avoid);
+    }
+
+    // A guest at another table has a nut allergy, so the walnut oil dressing
+    // is blocked until the order is changed
+    let allergic = hosting::Party::new("Moreau", 2, clock::Time::hm(9, 0))
+        .allergy(allergens::Allergen::TreeNuts);
+    let salad = |dressing| serving::OrderItem::from_menu(&menu, order_2.menu_id(), &[dressing], 1);
+    let mut theirs = vec![salad("Walnut oil").expect("walnut oil is in stock")];
+    if let Err(e) = guide.check(&allergic, &theirs) {
+        println!("{}", e);
+        theirs = vec![salad("Vinaigrette").expect("vinaigrette is in stock")];
+    }
+    if guide.check(&allergic, &theirs).is_ok() {
+        println!("{} can have the salad with {}", allergic.name, theirs[0].modifiers.join(", "));
+    }
+
+    // What the order costs us to make, and which dishes on the menu don't
+    // make the 75% margin we aim for
+    let costing = costing::Costing::house();
+    let cost = costing.order(&items).expect("every house dish has a recipe");
+    print!("{}", cost);
+    let report = costing.report(&menu, 7500).expect("every house dish is costed");
+    for line in report.flagged() {
+        println!("Below target: {} keeps {} of {}", line.name, line.margin(), line.price);
+    }
+
+    // Put the order through, and follow it from the kitchen to the till
+    let mut orders = serving::OrderBook::new();
+    let party = hosting::PartyId(1);
+    let at = clock::Time::hm(9, 30);
+    let id = serving::take_order(&mut orders, party, items, at).expect("a new order is editable");
+
+    // Trying to serve food which hasn't been cooked yet is rejected
+    if let Err(e) = serving::serve_order(&mut orders, id, at) {
+        println!("{}", e);
+    }
+
+    let order = orders.get_mut(id).expect("the order was just taken");
+    order.send_to_kitchen(at).expect("the order has items");
+
+    // Each station cooks on its own thread. A kitchen minute lasts a
+    // millisecond here, so the demo doesn't keep us waiting. We're nearly
+    // out of walnut oil, so that it runs out during the demo.
+    let stores = inventory::Inventory::house().stock("walnut oil", inventory::Unit::Millilitres, 60, 30);
+    let routing = kitchen::Routing::house();
+    let mut kitchen = kitchen::Kitchen::new(routing.clone(), std::time::Duration::from_millis(1))
+        .with_inventory(stores);
+    let ticket = back_of_house::cook_order(&mut kitchen, order, at.plus_minutes(2))
+        .expect("everything on the order has a station");
+
+    // The ticket printer at the pass uses narrow paper
+    let printed = printing::KitchenTicket::new(ticket, order, &routing, at.plus_minutes(2));
+    print!("{}", printed.render(printing::Format::Thermal(printing::Paper::Narrow)));
+    for (ticket, age) in kitchen.ticket_ages() {
+        println!("Ticket {} has been open {} minutes", ticket.0, age);
+    }
+    let cooked = kitchen.wait_for(ticket).expect("the ticket was just sent");
+    for (station, item) in &cooked.finished {
+        println!("{:?} finished {}", station, item);
+    }
+    println!("Ticket {} was ready after about {} minutes", ticket.0, cooked.age_minutes);
+    order.mark_ready(at.plus_minutes(12)).expect("cooking orders can be ready");
+    serving::serve_order(&mut orders, id, at.plus_minutes(13)).expect("ready orders can be served");
+
+    // Add 8% sales tax and a 12.5% service charge, then a tip on top. The
+    // house discounts take 1.75 off for having soup with the breakfast.
+    let config = billing::BillingConfig::new()
+        .tax(billing::TaxRule::new("Sales tax", 800))
+        .service_charge(1250)
+        .pricing(pricing::PricingRules::house());
+    let mut bill = billing::Bill::for_order(orders.get(id).expect("the order exists"), &config);
+    bill.set_tip(money::Money::from_cents(300)).expect("the tip isn't negative");
+
+    // They're silver members, and one of them has a voucher which isn't ours
+    bill.set_loyalty("silver").expect("silver is a loyalty tier");
+    if let Err(e) = bill.redeem_voucher("HALFPRICE") {
+        println!("{}", e);
+    }
+    for discount in bill.discounts() {
+        let line = &bill.lines()[discount.line];
+        println!("{} on {}: {} ({})", discount.rule, line.description, discount.amount, discount.reason);
+    }
+
+    // The salads came out with the wrong dressing. They're remade ahead of
+    // everything else in the kitchen, and one of them is taken off the bill.
+    let mut remakes = remakes::RemakeLog::new();
+    let remake = back_of_house::fix_incorrect_order(
+        &mut kitchen,
+        &mut remakes,
+        orders.get(id).expect("the order exists"),
+        2,
+        remakes::RemakeReason::WrongItem,
+        Some(&mut bill),
+        at.plus_minutes(15),
+    )
+    .expect("the salad is on the order and the bill");
+    let ticket = remakes.entries()[0].ticket;
+    kitchen.wait_for(ticket).expect("the remake has a ticket");
+    remakes.resolve(remake, at.plus_minutes(19)).expect("the remake was just logged");
+    print!("{}", remakes.report());
+
+    // Cooking and remaking the salads used up the walnut oil. Once the menu
+    // is updated, nobody else can order it.
+    let stores = kitchen.inventory_mut().expect("the kitchen was given stores");
+    for alert in stores.take_alerts() {
+        println!("Stock alert: {}", alert);
+    }
+    stores.apply_to(&mut menu);
+    if let Err(e) = serving::OrderItem::from_menu(&menu, order_2.menu_id(), &["Walnut oil"], 1) {
+        println!("{}", e);
+    }
+
+    // The counts are saved at closing time and read back in the morning
+    let path = std::env::temp_dir().join("restaurant-stock.json");
+    stores.save(&path).expect("the temp directory is writable");
+    let reopened = inventory::Inventory::load(&path, inventory::Recipes::house())
+        .expect("the file was just saved");
+    if let Some(eggs) = reopened.level("eggs") {
+        println!("{} eggs left for tomorrow", eggs.on_hand);
+    }
+    println!("Subtotal {}, total {}", bill.subtotal(), bill.total());
+
+    // Three ways to split it. Whichever we pick, not a penny goes missing.
+    let show = |shares: Vec<billing::Share>| {
+        shares.iter().map(|s| s.total.to_string()).collect::<Vec<_>>().join(" + ")
+    };
+    println!("Split three ways: {}", show(bill.split_evenly(3).expect("three guests")));
+    let by_item = bill
+        .split_by_item(2, &[vec![0], vec![1], vec![0, 1]])
+        .expect("every line has a guest");
+    println!("Split by item: {}", show(by_item));
+    println!("Split 3:1: {}", show(bill.split_by_shares(&[3, 1]).expect("two guests")));
+
+    // Pay part with a voucher, and the rest in cash
+    let paid_at = at.plus_minutes(40);
+    let voucher = billing::Tender::new(billing::TenderKind::Voucher, money::Money::from_cents(1000), paid_at);
+    serving::take_payment(&mut orders, &mut bill, voucher).expect("the voucher is less than the bill");
+    let cash = billing::Tender::new(billing::TenderKind::Cash, money::Money::from_cents(5000), paid_at);
+    match serving::take_payment(&mut orders, &mut bill, cash) {
+        Ok(receipt) => println!("Paid {}, change due {}", receipt.applied, receipt.change),
+        Err(e) => println!("{}", e),
+    }
+    println!("Order {:?} is now {:?}", id, orders.get(id).map(|o| o.status()));
+
+    // The guests asked to see how it would have split three ways
+    let order = orders.get(id).expect("the order exists");
+    let receipt = printing::Receipt::new(order, &bill)
+        .expect("the bill was made from this order")
+        .split(bill.split_evenly(3).expect("three guests"));
+    print!("{}", receipt.render(printing::Format::Thermal(printing::Paper::Wide)));
 }
 
 // To avoid writing out paths, we can make use of the `use` keyword to
@@ -108,7 +644,133 @@ pub fn eat_at_restaurant_2() {
 use crate::front_of_house::hosting;
 
 pub fn eat_at_restaurant_3() {
-    hosting::add_to_waitlist();
+    let mut waitlist = hosting::Waitlist::new(hosting::TableTurnover::new(12, 45));
+    let id = hosting::add_to_waitlist(
+        &mut waitlist,
+        hosting::Party::new("Silva", 3, clock::Time::hm(18, 30)),
+    );
+
+    // Nobody answered when we called them, so they're marked as a no-show
+    waitlist.turnover.record_turn(50);
+    if let Some(entry) = waitlist.mark_no_show(id) {
+        println!("{} didn't show up", entry.party.name);
+    }
+
+    // What if a busy Saturday had a second cook on the grill, and another
+    // table for four? Using the same seed means the same guests turn up.
+    let saturday = simulation::SimulationConfig::new(7).arrivals_per_hour(20.0);
+    println!("{}", simulation::simulate(&saturday));
+    let busier = saturday
+        .clone()
+        .cooks(kitchen::Station::Grill, 2)
+        .tables(&[2, 2, 2, 4, 4, 4, 6], &[(0, 1), (1, 2)]);
+    println!("{}", simulation::simulate(&busier));
+
+    // Keep the evening in a log file, so it can be picked up again if the
+    // program stops. Each open starts from an empty restaurant and replays
+    // the log on top of it.
+    let fresh = || {
+        let mut floor = hosting::Floor::new();
+        floor.add_table(2);
+        floor.add_table(4);
+        let config = billing::BillingConfig::new().tax(billing::TaxRule::new("Sales tax", 800));
+        storage::State::new(floor, hosting::TableTurnover::new(12, 45), config)
+    };
+    let path = std::env::temp_dir().join("restaurant-evening.log");
+    let _ = std::fs::remove_file(&path);
+
+    let mut store = storage::Store::open(&path, fresh()).expect("the temp dir is writable");
+    let at = clock::Time::hm(12, 0);
+    let party = store
+        .add_party(hosting::Party::new("Haddad", 2, at))
+        .expect("the log is writable");
+    store.seat(at.plus_minutes(2)).expect("a table for two is free");
+    let item = serving::OrderItem::from_menu(&menu::Menu::house(), "breakfast", &["Rye"], 2)
+        .expect("breakfast comes with rye toast");
+    let order = store
+        .open_order(party, vec![item], at.plus_minutes(5))
+        .expect("the party is seated");
+    store
+        .move_order(order, serving::OrderStatus::SentToKitchen, at.plus_minutes(6))
+        .expect("new orders can be sent to the kitchen");
+    drop(store);
+
+    // Pretend the program died half way through writing the next record
+    if let Ok(mut file) = std::fs::OpenOptions::new().append(true).open(&path) {
+        use std::io::Write;
+        let _ = file.write_all(br#"{"type":"order_moved","order":"#);
+    }
+
+    match storage::Store::open(&path, fresh()) {
+        Ok(store) => {
+            let state = store.state();
+            println!(
+                "Recovered {} seated party and {} order ({} bytes of a torn write dropped)",
+                state.seatings().len(),
+                state.orders().iter().count(),
+                store.recovered_bytes()
+            );
+            if let Ok(bill) = state.bill(order) {
+                println!("Haddad's bill so far: {}", bill.total());
+            }
+        }
+        Err(e) => println!("Couldn't recover the evening: {}", e),
+    }
+
+    // Four soups going out for delivery, with two drivers. Addresses are
+    // counted in blocks from the restaurant.
+    let mut orders = serving::OrderBook::new();
+    let mut dispatcher = delivery::Dispatcher::new(delivery::DeliveryConfig::new());
+    let at = clock::Time::hm(18, 0);
+    dispatcher.add_driver("Ines", at);
+    dispatcher.add_driver("Tom", at);
+    let soup = serving::OrderItem::from_menu(&menu::Menu::house(), "soup", &[], 1).expect("soup is on the menu");
+    let addresses = [
+        delivery::Address::new("4 Mill Lane", 3, 1),
+        delivery::Address::new("12 Quay Street", -5, 2),
+        delivery::Address::new("The Old Forge", 4, 3),
+        delivery::Address::new("7 Mill Lane", 3, 2),
+    ];
+    let mut deliveries = Vec::new();
+    for (i, address) in addresses.into_iter().enumerate() {
+        let party = hosting::PartyId(i as u32 + 10);
+        let id = serving::take_order(&mut orders, party, vec![soup.clone()], at).expect("a new order is editable");
+
+        // Delivery orders still go through the kitchen like any other
+        let order = orders.get_mut(id).expect("the order was just taken");
+        order.send_to_kitchen(at).expect("the order has soup on it");
+        order.start_cooking(at).expect("the order was sent to the kitchen");
+        order.mark_ready(at.plus_minutes(8)).expect("the soup is cooking");
+        if let Some(eta) = dispatcher.estimate(&address, at.plus_minutes(8)) {
+            println!("{} should have their soup by {}", address.street, eta);
+        }
+        deliveries.push(deliver_order(&mut dispatcher, &orders, id, address, at.plus_minutes(8)).expect("the soup is ready"));
+    }
+
+    // The three orders to the east go together, and Quay Street on its own
+    for run in dispatcher.dispatch(at.plus_minutes(8)) {
+        let stops: Vec<String> = run
+            .stops
+            .iter()
+            .map(|(id, eta)| {
+                let street = dispatcher.get(*id).map(|d| d.address.street.as_str()).unwrap_or("?");
+                format!("{} at {}", street, eta)
+            })
+            .collect();
+        println!("Driver {} takes {}, back at {}", run.driver.0, stops.join(", "), run.back_at);
+    }
+
+    // Nobody answers at the Old Forge
+    let forge = deliveries[2];
+    dispatcher.delivered(deliveries[0], &mut orders, at.plus_minutes(19)).expect("it was dispatched");
+    if let Err(e) = dispatcher.delivered(deliveries[0], &mut orders, at.plus_minutes(20)) {
+        println!("{}", e);
+    }
+    dispatcher.failed(forge, &mut orders, "nobody home", at.plus_minutes(25)).expect("it was dispatched");
+    for delivery in dispatcher.deliveries() {
+        let order = orders.get(delivery.order).map(|o| o.status());
+        println!("{}: {:?} (order {:?})", delivery.address.street, delivery.status(), order);
+    }
 }
 
 // When importing other items like structs & enums, by convention it is
@@ -116,6 +778,7 @@ pub fn eat_at_restaurant_3() {
 // module
 use std::collections::HashMap;
 
+#[allow(dead_code)] // Only here to show off the import
 fn use_hashmap() {
     let mut map = HashMap::new();
     map.insert(1, 2);
@@ -126,7 +789,10 @@ fn use_hashmap() {
 // prevent compiler errors
 
 // The other way around this would be to provide an alias
+// (These two are only here as an example, so allow them to go unused)
+#[allow(unused_imports)]
 use std::fmt::Result;
+#[allow(unused_imports)]
 use std::io::Result as IoResult;
 
 // We can also use `use` to alter the public facing API of a library
@@ -134,6 +800,11 @@ use std::io::Result as IoResult;
 // rather than going through front_of_house
 pub use crate::front_of_house::hosting as pub_hosting;
 
+// Re-exporting doesn't have to involve renaming. Serving is made public under
+// its own name, so callers can use restaurant::serving::take_order
+pub use crate::front_of_house::{billing, delivery, pricing, serving};
+pub use crate::back_of_house::{allergens, costing, inventory, kitchen, menu, produce, remakes};
+
 // If we need to use more than one part of an external crate, we could have
 // a separate line for each reference
 // use std::cmp::Ordering;
@@ -0,0 +1,441 @@
+// A command line tool for the front of house, so staff can run the evening
+// without writing any Rust. Each run makes one change (or prints something)
+// and saves it to the same log the HTTP server uses. The two can't have the
+// log open at the same time, though: stop the server before using this on
+// its log (the Store refuses to open a log another program has open).
+//
+// A crate can have a library and a binary. This one, in main.rs, is named
+// after the package, so `cargo run -- waitlist list` runs it. It uses the
+// library like any other crate would, through `restaurant::`.
+//
+// As with minigrep in the book, parsing the arguments is kept apart from
+// acting on them: Config::build turns the arguments into a Command, and run
+// carries it out, returning any error for main to report.
+
+use std::env;
+use std::error::Error;
+use std::path::PathBuf;
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use restaurant::allergens::{self, AllergenGuide, Query};
+use restaurant::billing::{Tender, TenderKind};
+use restaurant::clock::Time;
+use restaurant::costing::Costing;
+use restaurant::menu::Menu;
+use restaurant::money::Money;
+use restaurant::printing::{Format, Paper, Receipt};
+use restaurant::pub_hosting::{Party, PartyId, Priority};
+use restaurant::serving::{OrderId, OrderItem, OrderStatus};
+use restaurant::storage::{State, Store};
+
+const USAGE: &str = "\
+Usage: restaurant [--log FILE] [--at HH:MM] COMMAND
+
+Commands:
+  waitlist add NAME SIZE [--phone NUMBER] [--notes TEXT] [--priority high]
+                         [--allergies nuts,shellfish]
+  waitlist list
+  waitlist remove PARTY
+  seat                                   seat the next party who fits
+  clear PARTY                            the party has left their table
+  order new PARTY
+  order add-item ORDER MENU_ID [MODIFIER...] [--quantity N] [--confirm-allergy]
+  order send ORDER
+  order mark ORDER cooking|ready|served|cancelled|voided
+  order cost ORDER                       what the order cost to make
+  bill show ORDER [--format thermal32|thermal48|json|html]
+  bill split ORDER GUESTS [--format ...]
+  bill pay ORDER cash|card|voucher AMOUNT   a gift voucher pays like cash
+  bill tip ORDER AMOUNT
+  bill voucher ORDER CODE                a discount voucher, e.g. WELCOME5
+  bill loyalty ORDER TIER                the guests' loyalty card tier
+  bill explain ORDER                     which discounts apply, and why
+  menu find QUERY                        e.g. vegan and nut-free
+  menu margins [--target PERCENT]        flags dishes below the target (75)
+  report
+
+The log defaults to $RESTAURANT_LOG, or restaurant.log. Times default to
+the current time (in UTC, as std doesn't know about time zones).";
+
+enum Command {
+    WaitlistAdd(Party),
+    WaitlistList,
+    WaitlistRemove(PartyId),
+    Seat,
+    Clear(PartyId),
+    OrderNew(PartyId),
+    OrderAddItem {
+        order: OrderId,
+        menu_id: String,
+        modifiers: Vec<String>,
+        quantity: u32,
+        confirmed: bool,
+    },
+    OrderSend(OrderId),
+    OrderMark(OrderId, OrderStatus),
+    OrderCost(OrderId),
+    BillShow(OrderId, Format),
+    BillSplit { order: OrderId, guests: usize, format: Format },
+    BillPay { order: OrderId, kind: TenderKind, amount: Money },
+    BillTip(OrderId, Money),
+    BillVoucher(OrderId, String),
+    BillLoyalty(OrderId, String),
+    BillExplain(OrderId),
+    MenuFind(Query),
+    // The target margin, in basis points
+    MenuMargins(u32),
+    Report,
+}
+
+struct Config {
+    log: PathBuf,
+    at: Time,
+    command: Command,
+}
+
+// The time of day right now, from the system clock
+fn now() -> Time {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    Time::from_minutes((seconds / 60 % (24 * 60)) as u32)
+}
+
+// Removes `--name` from the arguments, returning whether it was there
+fn take_flag(args: &mut Vec<String>, name: &str) -> bool {
+    let before = args.len();
+    args.retain(|a| a != name);
+    args.len() != before
+}
+
+// Removes `--name VALUE` from the arguments, wherever it appears
+fn take_option(args: &mut Vec<String>, name: &str) -> Result<Option<String>, String> {
+    let Some(index) = args.iter().position(|a| a == name) else {
+        return Ok(None);
+    };
+    if index + 1 >= args.len() {
+        return Err(format!("{} needs a value", name));
+    }
+    let value = args.remove(index + 1);
+    args.remove(index);
+    Ok(Some(value))
+}
+
+fn parse<T: std::str::FromStr>(value: &str, what: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("\"{}\" is not a valid {}", value, what))
+}
+
+impl Config {
+    fn build(mut args: Vec<String>) -> Result<Config, String> {
+        let log = match take_option(&mut args, "--log")? {
+            Some(log) => PathBuf::from(log),
+            None => PathBuf::from(
+                env::var("RESTAURANT_LOG").unwrap_or_else(|_| String::from("restaurant.log")),
+            ),
+        };
+        let at = match take_option(&mut args, "--at")? {
+            Some(at) => at.parse().map_err(|e| format!("{}", e))?,
+            None => now(),
+        };
+        let phone = take_option(&mut args, "--phone")?;
+        let notes = take_option(&mut args, "--notes")?;
+        let priority = take_option(&mut args, "--priority")?;
+        let quantity = take_option(&mut args, "--quantity")?;
+        let allergies = take_option(&mut args, "--allergies")?;
+        let confirmed = take_flag(&mut args, "--confirm-allergy");
+        let target = take_option(&mut args, "--target")?;
+        let format = match take_option(&mut args, "--format")?.as_deref() {
+            None | Some("thermal48") => Format::Thermal(Paper::Wide),
+            Some("thermal32") => Format::Thermal(Paper::Narrow),
+            Some("json") => Format::Json,
+            Some("html") => Format::Html,
+            Some(other) => return Err(format!("\"{}\" is not a receipt format", other)),
+        };
+
+        let words: Vec<&str> = args.iter().map(|a| a.as_str()).collect();
+        let party = |id: &str| parse(id, "party id").map(PartyId);
+        let order = |id: &str| parse(id, "order id").map(OrderId);
+
+        let command = match words.as_slice() {
+            ["waitlist", "add", name, size] => {
+                let mut party = Party::new(name, parse(size, "party size")?, at);
+                party.phone = phone;
+                party.notes = notes;
+                if let Some(allergies) = allergies {
+                    party.allergies = allergens::parse_allergies(&allergies).map_err(|e| e.to_string())?;
+                }
+                party.priority = match priority.as_deref() {
+                    None | Some("normal") => Priority::Normal,
+                    Some("high") => Priority::High,
+                    Some(other) => return Err(format!("\"{}\" is not a priority, use normal or high", other)),
+                };
+                Command::WaitlistAdd(party)
+            }
+            ["waitlist", "list"] => Command::WaitlistList,
+            ["waitlist", "remove", id] => Command::WaitlistRemove(party(id)?),
+            ["seat"] => Command::Seat,
+            ["clear", id] => Command::Clear(party(id)?),
+            ["order", "new", id] => Command::OrderNew(party(id)?),
+            ["order", "add-item", id, menu_id, modifiers @ ..] => Command::OrderAddItem {
+                order: order(id)?,
+                menu_id: String::from(*menu_id),
+                modifiers: modifiers.iter().map(|m| String::from(*m)).collect(),
+                quantity: match quantity {
+                    Some(quantity) => parse(&quantity, "quantity")?,
+                    None => 1,
+                },
+                confirmed,
+            },
+            ["order", "send", id] => Command::OrderSend(order(id)?),
+            ["order", "mark", id, status] => {
+                let status = match *status {
+                    "cooking" => OrderStatus::Cooking,
+                    "ready" => OrderStatus::Ready,
+                    "served" => OrderStatus::Served,
+                    "cancelled" => OrderStatus::Cancelled,
+                    "voided" => OrderStatus::Voided,
+                    other => return Err(format!("\"{}\" is not a status an order can be marked as", other)),
+                };
+                Command::OrderMark(order(id)?, status)
+            }
+            ["order", "cost", id] => Command::OrderCost(order(id)?),
+            ["bill", "show", id] => Command::BillShow(order(id)?, format),
+            ["bill", "split", id, guests] => Command::BillSplit {
+                order: order(id)?,
+                guests: parse(guests, "number of guests")?,
+                format,
+            },
+            ["bill", "pay", id, kind, amount] => Command::BillPay {
+                order: order(id)?,
+                kind: match *kind {
+                    "cash" => TenderKind::Cash,
+                    "card" => TenderKind::Card,
+                    "voucher" => TenderKind::Voucher,
+                    other => return Err(format!("\"{}\" is not a way to pay, use cash, card or voucher", other)),
+                },
+                amount: amount.parse().map_err(|e| format!("{}", e))?,
+            },
+            ["bill", "tip", id, tip] => {
+                Command::BillTip(order(id)?, tip.parse().map_err(|e| format!("{}", e))?)
+            }
+            ["bill", "voucher", id, code] => Command::BillVoucher(order(id)?, String::from(*code)),
+            ["bill", "loyalty", id, tier] => Command::BillLoyalty(order(id)?, String::from(*tier)),
+            ["bill", "explain", id] => Command::BillExplain(order(id)?),
+            ["menu", "find", query @ ..] if !query.is_empty() => {
+                Command::MenuFind(query.join(" ").parse().map_err(|e| format!("{}", e))?)
+            }
+            ["menu", "margins"] => {
+                let target: u32 = match target {
+                    Some(target) => parse(&target, "percentage")?,
+                    None => 75,
+                };
+                if target > 100 {
+                    return Err(String::from("the target margin can't be more than 100%"));
+                }
+                Command::MenuMargins(target * 100)
+            }
+            ["report"] => Command::Report,
+            [] => return Err(String::from("no command given")),
+            _ => return Err(format!("\"{}\" is not a command", words.join(" "))),
+        };
+        Ok(Config { log, at, command })
+    }
+}
+
+fn report(state: &State) -> Result<(), Box<dyn Error>> {
+    let waitlist = state.waitlist();
+    let waiting: u32 = waitlist.iter().map(|e| e.party.size).sum();
+    println!("Waiting: {} parties ({} guests), quoting {} minutes", waitlist.len(), waiting, waitlist.quote());
+
+    let seats: u32 = state.floor().tables().iter().map(|t| t.capacity).sum();
+    let in_use = state.floor().tables().iter().filter(|t| !t.is_free()).count();
+    let seated: u32 = state.seatings().iter().map(|s| s.party.size).sum();
+    println!(
+        "Seated: {} parties ({} guests) at {} of {} tables, {} seats",
+        state.seatings().len(),
+        seated,
+        in_use,
+        state.floor().tables().len(),
+        seats
+    );
+
+    let mut statuses: Vec<(OrderStatus, usize)> = Vec::new();
+    let (mut takings, mut tips, mut outstanding) = (Money::ZERO, Money::ZERO, Money::ZERO);
+    for order in state.orders().iter() {
+        match statuses.iter_mut().find(|(status, _)| *status == order.status()) {
+            Some((_, count)) => *count += 1,
+            None => statuses.push((order.status(), 1)),
+        }
+        let bill = state.bill(order.id)?;
+        takings += bill.paid();
+        if bill.is_settled() {
+            tips += bill.tip();
+        } else if !order.status().is_final() {
+            outstanding += bill.balance();
+        }
+    }
+    let statuses: Vec<String> = statuses.iter().map(|(status, n)| format!("{} {:?}", n, status)).collect();
+    println!("Orders: {} ({})", state.orders().iter().count(), statuses.join(", "));
+    println!("Takings: {} (including {} in tips), {} still to pay", takings, tips, outstanding);
+    Ok(())
+}
+
+fn run(config: Config) -> Result<(), Box<dyn Error>> {
+    let mut store = Store::open(&config.log, State::house())?;
+    if store.recovered_bytes() > 0 {
+        eprintln!("Dropped a half-written change from the end of {}", config.log.display());
+    }
+    let at = config.at;
+
+    match config.command {
+        Command::WaitlistAdd(party) => {
+            let name = party.name.clone();
+            let id = store.add_party(party)?;
+            let wait = store.state().waitlist().estimated_wait(id).unwrap_or_default();
+            println!("Added {} as party {}, about {} minutes to wait", name, id.0, wait);
+        }
+        Command::WaitlistList => {
+            let waitlist = store.state().waitlist();
+            if waitlist.is_empty() {
+                println!("Nobody is waiting");
+            }
+            for entry in waitlist.iter() {
+                println!(
+                    "{:>3}  {:<20} {:>2} people  since {}  {:?}  ~{} min",
+                    entry.id.0,
+                    entry.party.name,
+                    entry.party.size,
+                    entry.party.arrived,
+                    entry.party.priority,
+                    waitlist.estimated_wait(entry.id).unwrap_or_default()
+                );
+            }
+        }
+        Command::WaitlistRemove(party) => {
+            store.remove_party(party)?;
+            println!("Removed party {} from the waitlist", party.0);
+        }
+        Command::Seat => {
+            let seating = store.seat(at)?;
+            let tables: Vec<String> = seating.tables.iter().map(|t| t.0.to_string()).collect();
+            println!("Seated {} (party {}) at table {}", seating.party.name, seating.id.0, tables.join(" + "));
+        }
+        Command::Clear(party) => {
+            store.clear_table(party, at)?;
+            println!("Cleared party {}'s table", party.0);
+        }
+        Command::OrderNew(party) => {
+            let order = store.open_order(party, Vec::new(), at)?;
+            println!("Opened order {} for party {}", order.0, party.0);
+        }
+        Command::OrderAddItem { order, menu_id, modifiers, quantity, confirmed } => {
+            let modifiers: Vec<&str> = modifiers.iter().map(|m| m.as_str()).collect();
+            let item = OrderItem::from_menu(&Menu::house(), &menu_id, &modifiers, quantity)?;
+            // Staff have to check with the guest before anything they're
+            // allergic to goes through
+            let party = store.state().orders().get(order)?.party;
+            let seating = store.state().seatings().iter().find(|s| s.id == party);
+            if let Some(seating) = seating.filter(|_| !confirmed) {
+                AllergenGuide::house().check(&seating.party, std::slice::from_ref(&item))?;
+            }
+            let (quantity, name, price) = (item.quantity, item.name.clone(), item.unit_price);
+            store.add_item(order, item)?;
+            println!("Added {} x {} at {} each", quantity, name, price);
+        }
+        Command::OrderSend(order) => {
+            store.move_order(order, OrderStatus::SentToKitchen, at)?;
+            println!("Sent order {} to the kitchen", order.0);
+        }
+        Command::OrderMark(order, status) => {
+            store.move_order(order, status, at)?;
+            println!("Order {} is now {:?}", order.0, status);
+        }
+        Command::OrderCost(order) => {
+            let order = store.state().orders().get(order)?;
+            print!("{}", Costing::house().order(order.items())?);
+        }
+        Command::BillShow(order, format) => {
+            let bill = store.state().bill(order)?;
+            let order = store.state().orders().get(order)?;
+            print!("{}", Receipt::new(order, &bill)?.render(format));
+        }
+        Command::BillSplit { order, guests, format } => {
+            let bill = store.state().bill(order)?;
+            let order = store.state().orders().get(order)?;
+            let receipt = Receipt::new(order, &bill)?.split(bill.split_evenly(guests)?);
+            print!("{}", receipt.render(format));
+        }
+        Command::BillPay { order, kind, amount } => {
+            let receipt = store.pay(order, Tender::new(kind, amount, at))?;
+            println!("Took {} by {:?}", receipt.applied, kind);
+            if receipt.change.is_positive() {
+                println!("Change due: {}", receipt.change);
+            }
+            println!("Left to pay: {}", receipt.balance);
+        }
+        Command::BillTip(order, tip) => {
+            store.set_tip(order, tip)?;
+            println!("Tip set to {}, the total is now {}", tip, store.state().bill(order)?.total());
+        }
+        Command::BillVoucher(order, code) => {
+            let before = store.state().bill(order)?.subtotal();
+            store.redeem_voucher(order, &code)?;
+            let after = store.state().bill(order)?.subtotal();
+            println!("Voucher {} takes {} off, the subtotal is now {}", code, before - after, after);
+        }
+        Command::BillLoyalty(order, tier) => {
+            store.set_loyalty(order, &tier)?;
+            println!("Added the {} loyalty card to order {}", tier, order.0);
+        }
+        Command::BillExplain(order) => {
+            let bill = store.state().bill(order)?;
+            if bill.discounts().is_empty() {
+                println!("No discounts apply to order {}", order.0);
+            }
+            for (i, line) in bill.lines().iter().enumerate() {
+                println!("{} x {}: {}", line.quantity, line.description, line.total);
+                for discount in bill.discounts_on(i) {
+                    println!("  {} {}: {}", discount.amount, discount.rule, discount.reason);
+                }
+            }
+        }
+        Command::MenuFind(query) => {
+            let found = AllergenGuide::house().matching(&Menu::house(), &query)?;
+            if found.is_empty() {
+                println!("Nothing on the menu suits that");
+            }
+            for item in found {
+                if item.avoid.is_empty() {
+                    println!("{}", item.name);
+                } else {
+                    println!("{} (not with {})", item.name, item.avoid.join(" or "));
+                }
+            }
+        }
+        Command::MenuMargins(target) => {
+            print!("{}", Costing::house().report(&Menu::house(), target)?);
+        }
+        Command::Report => report(store.state())?,
+    }
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.iter().any(|a| a == "--help" || a == "-h") {
+        println!("{}", USAGE);
+        return;
+    }
+    let config = Config::build(args).unwrap_or_else(|e| {
+        eprintln!("Problem parsing arguments: {}\n\n{}", e, USAGE);
+        process::exit(2);
+    });
+    if let Err(e) = run(config) {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,210 @@
+// An exact amount of money.
+//
+// Floating point numbers can't represent most decimal amounts exactly (0.1 +
+// 0.2 is not quite 0.3), and those tiny errors add up over a night's bills.
+// Instead we count whole cents (or pennies) in an integer, so adding and
+// subtracting prices is always exact. Amounts can be negative, which is
+// useful for discounts and refunds.
+
+use std::fmt;
+use std::iter::Sum;
+use std::ops::{Add, AddAssign, Mul, Neg, Sub, SubAssign};
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+// Stored as a plain number of cents when saved
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Money {
+    cents: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseMoneyError(String);
+
+impl fmt::Display for ParseMoneyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "\"{}\" is not a valid amount of money, expected something like 4.50", self.0)
+    }
+}
+
+impl std::error::Error for ParseMoneyError {}
+
+impl Money {
+    pub const ZERO: Money = Money { cents: 0 };
+
+    pub fn from_cents(cents: i64) -> Money {
+        Money { cents }
+    }
+
+    pub fn cents(&self) -> i64 {
+        self.cents
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.cents < 0
+    }
+
+    pub fn is_positive(&self) -> bool {
+        self.cents > 0
+    }
+
+    // A percentage of this amount, given in basis points (hundredths of a
+    // percent, so 1250 is 12.5%). Half cents are rounded away from zero,
+    // which is how a cash register would round them.
+    pub fn percent(&self, basis_points: u32) -> Money {
+        let scaled = i128::from(self.cents) * i128::from(basis_points);
+        let half = if scaled < 0 { -5_000 } else { 5_000 };
+        Money::from_cents(((scaled + half) / 10_000) as i64)
+    }
+
+    // Splits this amount into parts in proportion to `weights`, so that the
+    // parts always add back up to exactly this amount. Everyone first gets
+    // their share rounded down, then any cents left over go one at a time
+    // to whoever lost the most to rounding (earliest first on a tie).
+    // If every weight is zero, the amount is shared equally instead.
+    pub fn allocate(&self, weights: &[u64]) -> Vec<Money> {
+        if weights.is_empty() {
+            return Vec::new();
+        }
+        let equal = vec![1; weights.len()];
+        let weights = if weights.iter().all(|&w| w == 0) {
+            &equal
+        } else {
+            weights
+        };
+        let total_weight: i128 = weights.iter().map(|&w| i128::from(w)).sum();
+
+        // Work on the size of the amount, and put the sign back at the end
+        let cents = i128::from(self.cents.abs());
+        let exact: Vec<i128> = weights.iter().map(|&w| cents * i128::from(w)).collect();
+        let mut parts: Vec<i128> = exact.iter().map(|e| e / total_weight).collect();
+
+        // Rounding down loses less than a cent per part, so there are always
+        // fewer cents left over than there are parts
+        let leftover = cents - parts.iter().sum::<i128>();
+        let mut by_remainder: Vec<usize> = (0..weights.len()).collect();
+        by_remainder.sort_by(|&a, &b| {
+            (exact[b] % total_weight)
+                .cmp(&(exact[a] % total_weight))
+                .then(a.cmp(&b))
+        });
+        for &i in by_remainder.iter().take(leftover as usize) {
+            parts[i] += 1;
+        }
+
+        let sign = if self.cents < 0 { -1 } else { 1 };
+        parts
+            .into_iter()
+            .map(|p| Money::from_cents(sign * p as i64))
+            .collect()
+    }
+}
+
+// Parses amounts like "4", "4.5" or "-12.05". More than two decimal places is
+// rejected rather than rounded, so a typo in a price list doesn't quietly
+// change a price.
+impl FromStr for Money {
+    type Err = ParseMoneyError;
+
+    fn from_str(s: &str) -> Result<Money, ParseMoneyError> {
+        let error = || ParseMoneyError(String::from(s));
+        let trimmed = s.trim();
+        let (negative, digits) = match trimmed.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, trimmed),
+        };
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+
+        let all_digits = |part: &str| part.chars().all(|c| c.is_ascii_digit());
+        if whole.is_empty() || !all_digits(whole) || !all_digits(fraction) || fraction.len() > 2 {
+            return Err(error());
+        }
+        if digits.ends_with('.') {
+            return Err(error());
+        }
+
+        let whole: i64 = whole.parse().map_err(|_| error())?;
+        let fraction: i64 = match fraction.len() {
+            0 => 0,
+            1 => fraction.parse::<i64>().map_err(|_| error())? * 10,
+            _ => fraction.parse().map_err(|_| error())?,
+        };
+        let cents = whole
+            .checked_mul(100)
+            .and_then(|c| c.checked_add(fraction))
+            .ok_or_else(error)?;
+        Ok(Money {
+            cents: if negative { -cents } else { cents },
+        })
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.cents < 0 { "-" } else { "" };
+        let cents = self.cents.unsigned_abs();
+        let text = format!("{}{}.{:02}", sign, cents / 100, cents % 100);
+        // Let callers pad amounts into columns with e.g. {:>8}
+        f.pad(&text)
+    }
+}
+
+// Implementing the operator traits lets us write `price * 2 + tip` rather
+// than calling methods by hand
+impl Add for Money {
+    type Output = Money;
+
+    fn add(self, other: Money) -> Money {
+        Money::from_cents(self.cents + other.cents)
+    }
+}
+
+impl AddAssign for Money {
+    fn add_assign(&mut self, other: Money) {
+        self.cents += other.cents;
+    }
+}
+
+impl Sub for Money {
+    type Output = Money;
+
+    fn sub(self, other: Money) -> Money {
+        Money::from_cents(self.cents - other.cents)
+    }
+}
+
+impl SubAssign for Money {
+    fn sub_assign(&mut self, other: Money) {
+        self.cents -= other.cents;
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Money {
+        Money::from_cents(-self.cents)
+    }
+}
+
+impl Mul<u32> for Money {
+    type Output = Money;
+
+    fn mul(self, quantity: u32) -> Money {
+        Money::from_cents(self.cents * i64::from(quantity))
+    }
+}
+
+impl Sum for Money {
+    fn sum<I: Iterator<Item = Money>>(iter: I) -> Money {
+        iter.fold(Money::ZERO, |total, m| total + m)
+    }
+}
+
+impl<'a> Sum<&'a Money> for Money {
+    fn sum<I: Iterator<Item = &'a Money>>(iter: I) -> Money {
+        iter.copied().sum()
+    }
+}
@@ -0,0 +1,856 @@
+// Receipts for guests and tickets for the kitchen, ready to print or send.
+//
+// Each can be rendered in a few formats:
+//
+// - Thermal: plain text for a receipt printer, laid out to fit a roll of
+//   paper 32 or 48 characters wide. Long names wrap onto the next line.
+// - Json: for another program (a tablet, or a printer with its own layout)
+// - Html: for email, or for showing on a screen
+//
+// Receipts and tickets are built from the same Order (and for receipts,
+// Bill) the rest of the restaurant uses, so what's printed always matches
+// what was charged.
+
+use std::fmt::{self, Write};
+
+use serde_json::{json, Value};
+
+use crate::back_of_house::kitchen::{Routing, Station, TicketId};
+use crate::clock::Time;
+use crate::front_of_house::billing::{Bill, Share};
+use crate::front_of_house::serving::{Order, OrderId};
+use crate::money::Money;
+
+// The two common widths of receipt printer paper, 58mm and 80mm
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Paper {
+    Narrow,
+    Wide,
+}
+
+impl Paper {
+    pub fn columns(&self) -> usize {
+        match self {
+            Paper::Narrow => 32,
+            Paper::Wide => 48,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PrintError {
+    // The bill given with an order was made from a different order
+    WrongBill { bill: OrderId, order: OrderId },
+}
+
+impl fmt::Display for PrintError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PrintError::WrongBill { bill, order } => {
+                write!(f, "the bill is for order {}, not order {}", bill.0, order.0)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PrintError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Thermal(Paper),
+    Json,
+    Html,
+}
+
+// Splits `text` into lines at most `width` characters long, breaking
+// between words where possible
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let width = width.max(1);
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    for word in text.split_whitespace() {
+        let mut word: Vec<char> = word.chars().collect();
+        // A word too long for a line of its own is broken up
+        while word.len() > width {
+            if !line.is_empty() {
+                lines.push(std::mem::take(&mut line));
+            }
+            lines.push(word.drain(..width).collect());
+        }
+        let word: String = word.into_iter().collect();
+        if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > width {
+            lines.push(std::mem::take(&mut line));
+        }
+        if !line.is_empty() {
+            line.push(' ');
+        }
+        line += &word;
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+// A line of text, starting `indent` characters in, with an amount lined up
+// against the right hand edge. If the text doesn't fit, the rest wraps onto
+// following lines, indented a little further.
+fn row(out: &mut String, indent: usize, left: &str, right: &str, width: usize) {
+    let room = width.saturating_sub(indent + right.chars().count() + 1);
+    // wrap puts single spaces between words, so the first line it gives back
+    // is always the start of `text`
+    let text = left.split_whitespace().collect::<Vec<_>>().join(" ");
+    let first = wrap(&text, room).swap_remove(0);
+    let line = format!("{:indent$}{:<room$} {}", "", first, right);
+    let _ = writeln!(out, "{}", line.trim_end());
+    let rest = &text[first.len()..];
+    let indent = (indent + 2).min(width / 2);
+    for line in wrap(rest, width - indent) {
+        if !line.is_empty() {
+            let _ = writeln!(out, "{:indent$}{}", "", line);
+        }
+    }
+}
+
+fn rule(out: &mut String, width: usize, c: char) {
+    let _ = writeln!(out, "{}", c.to_string().repeat(width));
+}
+
+fn centred(out: &mut String, text: &str, width: usize) {
+    for line in wrap(text, width) {
+        let _ = writeln!(out, "{}", format!("{:^width$}", line).trim_end());
+    }
+}
+
+// Makes text safe to put inside HTML
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped += "&amp;",
+            '<' => escaped += "&lt;",
+            '>' => escaped += "&gt;",
+            '"' => escaped += "&quot;",
+            '\'' => escaped += "&#39;",
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn station_name(station: Option<Station>) -> &'static str {
+    match station {
+        Some(Station::Grill) => "Grill",
+        Some(Station::Fryer) => "Fryer",
+        Some(Station::Cold) => "Cold",
+        None => "Unrouted",
+    }
+}
+
+// One line of a receipt, with the modifiers chosen for it and any
+// discounts taken off it
+struct ReceiptLine {
+    quantity: u32,
+    description: String,
+    modifiers: Vec<String>,
+    discounts: Vec<(String, Money)>,
+    total: Money,
+}
+
+pub struct Receipt<'a> {
+    title: String,
+    order: &'a Order,
+    bill: &'a Bill,
+    split: Vec<Share>,
+}
+
+impl<'a> Receipt<'a> {
+    // The order and bill are looked up separately, so check they belong
+    // together before printing one as if it were the other's
+    pub fn new(order: &'a Order, bill: &'a Bill) -> Result<Receipt<'a>, PrintError> {
+        if bill.order != order.id {
+            return Err(PrintError::WrongBill {
+                bill: bill.order,
+                order: order.id,
+            });
+        }
+        Ok(Receipt {
+            title: String::from("The Restaurant"),
+            order,
+            bill,
+            split: Vec::new(),
+        })
+    }
+
+    pub fn title(mut self, title: &str) -> Receipt<'a> {
+        self.title = String::from(title);
+        self
+    }
+
+    // Adds a section showing what each guest owes
+    pub fn split(mut self, shares: Vec<Share>) -> Receipt<'a> {
+        self.split = shares;
+        self
+    }
+
+    fn lines(&self) -> Vec<ReceiptLine> {
+        // Bill lines follow the order's items, with any comps added on the end
+        self.bill
+            .lines()
+            .iter()
+            .enumerate()
+            .map(|(i, line)| ReceiptLine {
+                quantity: line.quantity,
+                description: line.description.clone(),
+                modifiers: match line.comp_of {
+                    None => self
+                        .order
+                        .items()
+                        .get(i)
+                        .map(|item| item.modifiers.clone())
+                        .unwrap_or_default(),
+                    Some(_) => Vec::new(),
+                },
+                discounts: self
+                    .bill
+                    .discounts_on(i)
+                    .map(|d| (d.rule.clone(), d.amount))
+                    .collect(),
+                total: line.total,
+            })
+            .collect()
+    }
+
+    // Everything between the items and the grand total
+    fn charges(&self) -> Vec<(String, Money)> {
+        let mut charges = vec![(String::from("Subtotal"), self.bill.subtotal())];
+        for (name, amount) in self.bill.taxes() {
+            charges.push((name.clone(), *amount));
+        }
+        if self.bill.service_charge() != Money::ZERO {
+            charges.push((String::from("Service charge"), self.bill.service_charge()));
+        }
+        charges.push((String::from("Tip"), self.bill.tip()));
+        charges
+    }
+
+    fn payments(&self) -> Vec<(String, Money)> {
+        self.bill
+            .tenders()
+            .iter()
+            .map(|tender| (format!("{:?}", tender.kind), tender.amount))
+            .collect()
+    }
+
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Thermal(paper) => self.thermal(paper.columns()),
+            Format::Json => self.json(),
+            Format::Html => self.html(),
+        }
+    }
+
+    fn thermal(&self, width: usize) -> String {
+        let mut out = String::new();
+        centred(&mut out, &self.title, width);
+        row(&mut out, 0, &format!("Order {}", self.order.id.0), &format!("Party {}", self.order.party.0), width);
+        rule(&mut out, width, '-');
+        for line in self.lines() {
+            row(&mut out, 0, &format!("{} x {}", line.quantity, line.description), &line.total.to_string(), width);
+            for modifier in &line.modifiers {
+                row(&mut out, 4, &format!("+ {}", modifier), "", width);
+            }
+            for (rule, amount) in &line.discounts {
+                row(&mut out, 4, rule, &amount.to_string(), width);
+            }
+        }
+        rule(&mut out, width, '-');
+        for (name, amount) in self.charges() {
+            row(&mut out, 0, &name, &amount.to_string(), width);
+        }
+        rule(&mut out, width, '=');
+        row(&mut out, 0, "TOTAL", &self.bill.total().to_string(), width);
+        rule(&mut out, width, '=');
+        for (kind, amount) in self.payments() {
+            row(&mut out, 0, &format!("Paid by {}", kind), &amount.to_string(), width);
+        }
+        row(&mut out, 0, "Balance", &self.bill.balance().to_string(), width);
+        if !self.split.is_empty() {
+            rule(&mut out, width, '-');
+            centred(&mut out, &format!("Split between {} guests", self.split.len()), width);
+            for share in &self.split {
+                row(&mut out, 0, &format!("Guest {}", share.guest + 1), &share.total.to_string(), width);
+                row(&mut out, 2, "Items", &share.items.to_string(), width);
+                row(&mut out, 2, "Tax, service & tip", &share.extras.to_string(), width);
+            }
+        }
+        rule(&mut out, width, '-');
+        centred(&mut out, "Thank you!", width);
+        out
+    }
+
+    fn json(&self) -> String {
+        let pairs = |pairs: Vec<(String, Money)>| -> Vec<Value> {
+            pairs
+                .into_iter()
+                .map(|(name, amount)| json!({ "name": name, "amount": amount.to_string() }))
+                .collect()
+        };
+        let lines: Vec<Value> = self
+            .lines()
+            .into_iter()
+            .map(|line| {
+                json!({
+                    "quantity": line.quantity,
+                    "description": line.description,
+                    "modifiers": line.modifiers,
+                    "discounts": pairs(line.discounts),
+                    "total": line.total.to_string(),
+                })
+            })
+            .collect();
+        let split: Vec<Value> = self
+            .split
+            .iter()
+            .map(|share| {
+                json!({
+                    "guest": share.guest + 1,
+                    "items": share.items.to_string(),
+                    "extras": share.extras.to_string(),
+                    "total": share.total.to_string(),
+                })
+            })
+            .collect();
+        let receipt = json!({
+            "title": self.title,
+            "order": self.order.id.0,
+            "party": self.order.party.0,
+            "lines": lines,
+            "charges": pairs(self.charges()),
+            "total": self.bill.total().to_string(),
+            "payments": pairs(self.payments()),
+            "balance": self.bill.balance().to_string(),
+            "split": split,
+        });
+        serde_json::to_string_pretty(&receipt).expect("a receipt is always valid JSON")
+    }
+
+    fn html(&self) -> String {
+        let mut out = String::new();
+        let row = |out: &mut String, class: &str, left: &str, right: &str| {
+            let _ = writeln!(
+                out,
+                "    <tr class=\"{}\"><td>{}</td><td class=\"amount\">{}</td></tr>",
+                class,
+                escape(left),
+                escape(right)
+            );
+        };
+        let _ = writeln!(out, "<section class=\"receipt\">");
+        let _ = writeln!(out, "  <h1>{}</h1>", escape(&self.title));
+        let _ = writeln!(out, "  <p>Order {}, party {}</p>", self.order.id.0, self.order.party.0);
+        let _ = writeln!(out, "  <table>");
+        for line in self.lines() {
+            row(&mut out, "item", &format!("{} x {}", line.quantity, line.description), &line.total.to_string());
+            for modifier in &line.modifiers {
+                row(&mut out, "modifier", &format!("+ {}", modifier), "");
+            }
+            for (rule, amount) in &line.discounts {
+                row(&mut out, "discount", rule, &amount.to_string());
+            }
+        }
+        for (name, amount) in self.charges() {
+            row(&mut out, "charge", &name, &amount.to_string());
+        }
+        row(&mut out, "total", "Total", &self.bill.total().to_string());
+        for (kind, amount) in self.payments() {
+            row(&mut out, "payment", &format!("Paid by {}", kind), &amount.to_string());
+        }
+        row(&mut out, "balance", "Balance", &self.bill.balance().to_string());
+        let _ = writeln!(out, "  </table>");
+        if !self.split.is_empty() {
+            let _ = writeln!(out, "  <h2>Split between {} guests</h2>", self.split.len());
+            let _ = writeln!(out, "  <table class=\"split\">");
+            for share in &self.split {
+                row(&mut out, "share", &format!("Guest {}", share.guest + 1), &share.total.to_string());
+            }
+            let _ = writeln!(out, "  </table>");
+        }
+        let _ = writeln!(out, "</section>");
+        out
+    }
+}
+
+// What the kitchen needs to cook an order: every item, with its modifiers,
+// grouped by the station that cooks it
+pub struct KitchenTicket<'a> {
+    ticket: TicketId,
+    order: &'a Order,
+    routing: &'a Routing,
+    at: Time,
+}
+
+impl<'a> KitchenTicket<'a> {
+    pub fn new(ticket: TicketId, order: &'a Order, routing: &'a Routing, at: Time) -> KitchenTicket<'a> {
+        KitchenTicket {
+            ticket,
+            order,
+            routing,
+            at,
+        }
+    }
+
+    // Stations in the order they appear in Station::ALL, then anything the
+    // routing doesn't know about
+    fn by_station(&self) -> Vec<(Option<Station>, Vec<usize>)> {
+        let mut groups: Vec<(Option<Station>, Vec<usize>)> = Vec::new();
+        let stations = Station::ALL.iter().map(|&s| Some(s)).chain([None]);
+        for station in stations {
+            let items: Vec<usize> = (0..self.order.items().len())
+                .filter(|&i| {
+                    self.routing.lookup(&self.order.items()[i].menu_id).map(|(s, _)| s) == station
+                })
+                .collect();
+            if !items.is_empty() {
+                groups.push((station, items));
+            }
+        }
+        groups
+    }
+
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Thermal(paper) => self.thermal(paper.columns()),
+            Format::Json => self.json(),
+            Format::Html => self.html(),
+        }
+    }
+
+    fn thermal(&self, width: usize) -> String {
+        let mut out = String::new();
+        row(&mut out, 0, &format!("TICKET {}", self.ticket.0), &self.at.to_string(), width);
+        row(&mut out, 0, &format!("Order {}", self.order.id.0), &format!("Party {}", self.order.party.0), width);
+        for (station, items) in self.by_station() {
+            rule(&mut out, width, '-');
+            centred(&mut out, &station_name(station).to_uppercase(), width);
+            for i in items {
+                let item = &self.order.items()[i];
+                row(&mut out, 0, &format!("{} x {}", item.quantity, item.name), "", width);
+                for modifier in &item.modifiers {
+                    row(&mut out, 4, &format!("+ {}", modifier), "", width);
+                }
+            }
+        }
+        rule(&mut out, width, '=');
+        out
+    }
+
+    fn json(&self) -> String {
+        let stations: Vec<Value> = self
+            .by_station()
+            .into_iter()
+            .map(|(station, items)| {
+                let items: Vec<Value> = items
+                    .into_iter()
+                    .map(|i| {
+                        let item = &self.order.items()[i];
+                        json!({
+                            "quantity": item.quantity,
+                            "name": item.name,
+                            "modifiers": item.modifiers,
+                        })
+                    })
+                    .collect();
+                json!({ "station": station_name(station), "items": items })
+            })
+            .collect();
+        let ticket = json!({
+            "ticket": self.ticket.0,
+            "order": self.order.id.0,
+            "party": self.order.party.0,
+            "at": self.at.to_string(),
+            "stations": stations,
+        });
+        serde_json::to_string_pretty(&ticket).expect("a ticket is always valid JSON")
+    }
+
+    fn html(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "<section class=\"ticket\">");
+        let _ = writeln!(
+            out,
+            "  <h1>Ticket {}</h1>\n  <p>Order {}, party {}, at {}</p>",
+            self.ticket.0, self.order.id.0, self.order.party.0, self.at
+        );
+        for (station, items) in self.by_station() {
+            let _ = writeln!(out, "  <h2>{}</h2>\n  <ul>", station_name(station));
+            for i in items {
+                let item = &self.order.items()[i];
+                let _ = write!(out, "    <li>{} x {}", item.quantity, escape(&item.name));
+                if !item.modifiers.is_empty() {
+                    let modifiers: Vec<String> = item.modifiers.iter().map(|m| escape(m)).collect();
+                    let _ = write!(out, " <span class=\"modifiers\">+ {}</span>", modifiers.join(", + "));
+                }
+                let _ = writeln!(out, "</li>");
+            }
+            let _ = writeln!(out, "  </ul>");
+        }
+        let _ = writeln!(out, "</section>");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::back_of_house::menu::Menu;
+    use crate::front_of_house::billing::{BillingConfig, TaxRule, Tender, TenderKind};
+    use crate::front_of_house::hosting::PartyId;
+    use crate::front_of_house::pricing::PricingRules;
+    use crate::front_of_house::serving::{self, OrderBook, OrderItem};
+
+    // Breakfast on sourdough with extra fruit, two soups with a roll and a
+    // salad: enough for modifiers, a combo discount and two stations. One
+    // of the soups is comped, which gives a line too long for narrow paper.
+    fn order() -> OrderBook {
+        let menu = Menu::house();
+        let items = vec![
+            OrderItem::from_menu(&menu, "breakfast", &["Sourdough", "Extra fruit"], 1).unwrap(),
+            OrderItem::from_menu(&menu, "soup", &["Crusty roll"], 2).unwrap(),
+            OrderItem::from_menu(&menu, "salad", &["Walnut oil"], 1).unwrap(),
+        ];
+        let mut orders = OrderBook::new();
+        serving::take_order(&mut orders, PartyId(3), items, Time::hm(12, 10)).unwrap();
+        orders
+    }
+
+    fn bill(order: &Order) -> Bill {
+        let config = BillingConfig::new()
+            .tax(TaxRule::new("Sales tax", 800))
+            .service_charge(1250)
+            .pricing(PricingRules::house());
+        let mut bill = Bill::for_order(order, &config);
+        bill.comp(1, "arrived cold").unwrap();
+        bill.set_tip(Money::from_cents(300)).unwrap();
+        bill.record_tender(Tender::new(TenderKind::Card, Money::from_cents(2000), Time::hm(12, 50)))
+            .unwrap();
+        bill
+    }
+
+    fn receipt(format: Format) -> String {
+        let orders = order();
+        let order = orders.iter().next().unwrap();
+        let bill = bill(order);
+        let shares = bill.split_evenly(2).unwrap();
+        Receipt::new(order, &bill).unwrap().title("Fish & Chips <by the sea>").split(shares).render(format)
+    }
+
+    fn ticket(format: Format) -> String {
+        let orders = order();
+        let order = orders.iter().next().unwrap();
+        KitchenTicket::new(TicketId(7), order, &Routing::house(), Time::hm(12, 12)).render(format)
+    }
+
+    #[test]
+    fn receipt_on_narrow_paper() {
+        let expected = r#"   Fish & Chips <by the sea>
+Order 1                  Party 3
+--------------------------------
+1 x Breakfast              10.50
+    + Sourdough
+    + Extra fruit
+    Soup and breakfast     -1.11
+2 x Soup of the day        12.00
+    + Crusty roll
+    Soup and breakfast     -0.64
+1 x Garden salad            6.40
+    + Walnut oil
+1 x Comp: Soup of the day  -6.00
+  (arrived cold)
+--------------------------------
+Subtotal                   21.15
+Sales tax                   1.69
+Service charge              2.64
+Tip                         3.00
+================================
+TOTAL                      28.48
+================================
+Paid by Card               20.00
+Balance                     8.48
+--------------------------------
+     Split between 2 guests
+Guest 1                    14.25
+  Items                    10.58
+  Tax, service & tip        3.67
+Guest 2                    14.23
+  Items                    10.57
+  Tax, service & tip        3.66
+--------------------------------
+           Thank you!
+"#;
+        assert_eq!(receipt(Format::Thermal(Paper::Narrow)), expected);
+    }
+
+    #[test]
+    fn receipt_on_wide_paper() {
+        let expected = r#"           Fish & Chips <by the sea>
+Order 1                                  Party 3
+------------------------------------------------
+1 x Breakfast                              10.50
+    + Sourdough
+    + Extra fruit
+    Soup and breakfast                     -1.11
+2 x Soup of the day                        12.00
+    + Crusty roll
+    Soup and breakfast                     -0.64
+1 x Garden salad                            6.40
+    + Walnut oil
+1 x Comp: Soup of the day (arrived cold)   -6.00
+------------------------------------------------
+Subtotal                                   21.15
+Sales tax                                   1.69
+Service charge                              2.64
+Tip                                         3.00
+================================================
+TOTAL                                      28.48
+================================================
+Paid by Card                               20.00
+Balance                                     8.48
+------------------------------------------------
+             Split between 2 guests
+Guest 1                                    14.25
+  Items                                    10.58
+  Tax, service & tip                        3.67
+Guest 2                                    14.23
+  Items                                    10.57
+  Tax, service & tip                        3.66
+------------------------------------------------
+                   Thank you!
+"#;
+        assert_eq!(receipt(Format::Thermal(Paper::Wide)), expected);
+    }
+
+    #[test]
+    fn receipt_as_json() {
+        let expected = r#"{
+  "balance": "8.48",
+  "charges": [
+    {
+      "amount": "21.15",
+      "name": "Subtotal"
+    },
+    {
+      "amount": "1.69",
+      "name": "Sales tax"
+    },
+    {
+      "amount": "2.64",
+      "name": "Service charge"
+    },
+    {
+      "amount": "3.00",
+      "name": "Tip"
+    }
+  ],
+  "lines": [
+    {
+      "description": "Breakfast",
+      "discounts": [
+        {
+          "amount": "-1.11",
+          "name": "Soup and breakfast"
+        }
+      ],
+      "modifiers": [
+        "Sourdough",
+        "Extra fruit"
+      ],
+      "quantity": 1,
+      "total": "10.50"
+    },
+    {
+      "description": "Soup of the day",
+      "discounts": [
+        {
+          "amount": "-0.64",
+          "name": "Soup and breakfast"
+        }
+      ],
+      "modifiers": [
+        "Crusty roll"
+      ],
+      "quantity": 2,
+      "total": "12.00"
+    },
+    {
+      "description": "Garden salad",
+      "discounts": [],
+      "modifiers": [
+        "Walnut oil"
+      ],
+      "quantity": 1,
+      "total": "6.40"
+    },
+    {
+      "description": "Comp: Soup of the day (arrived cold)",
+      "discounts": [],
+      "modifiers": [],
+      "quantity": 1,
+      "total": "-6.00"
+    }
+  ],
+  "order": 1,
+  "party": 3,
+  "payments": [
+    {
+      "amount": "20.00",
+      "name": "Card"
+    }
+  ],
+  "split": [
+    {
+      "extras": "3.67",
+      "guest": 1,
+      "items": "10.58",
+      "total": "14.25"
+    },
+    {
+      "extras": "3.66",
+      "guest": 2,
+      "items": "10.57",
+      "total": "14.23"
+    }
+  ],
+  "title": "Fish & Chips <by the sea>",
+  "total": "28.48"
+}"#;
+        assert_eq!(receipt(Format::Json), expected);
+    }
+
+    #[test]
+    fn receipt_as_html() {
+        let expected = r#"<section class="receipt">
+  <h1>Fish &amp; Chips &lt;by the sea&gt;</h1>
+  <p>Order 1, party 3</p>
+  <table>
+    <tr class="item"><td>1 x Breakfast</td><td class="amount">10.50</td></tr>
+    <tr class="modifier"><td>+ Sourdough</td><td class="amount"></td></tr>
+    <tr class="modifier"><td>+ Extra fruit</td><td class="amount"></td></tr>
+    <tr class="discount"><td>Soup and breakfast</td><td class="amount">-1.11</td></tr>
+    <tr class="item"><td>2 x Soup of the day</td><td class="amount">12.00</td></tr>
+    <tr class="modifier"><td>+ Crusty roll</td><td class="amount"></td></tr>
+    <tr class="discount"><td>Soup and breakfast</td><td class="amount">-0.64</td></tr>
+    <tr class="item"><td>1 x Garden salad</td><td class="amount">6.40</td></tr>
+    <tr class="modifier"><td>+ Walnut oil</td><td class="amount"></td></tr>
+    <tr class="item"><td>1 x Comp: Soup of the day (arrived cold)</td><td class="amount">-6.00</td></tr>
+    <tr class="charge"><td>Subtotal</td><td class="amount">21.15</td></tr>
+    <tr class="charge"><td>Sales tax</td><td class="amount">1.69</td></tr>
+    <tr class="charge"><td>Service charge</td><td class="amount">2.64</td></tr>
+    <tr class="charge"><td>Tip</td><td class="amount">3.00</td></tr>
+    <tr class="total"><td>Total</td><td class="amount">28.48</td></tr>
+    <tr class="payment"><td>Paid by Card</td><td class="amount">20.00</td></tr>
+    <tr class="balance"><td>Balance</td><td class="amount">8.48</td></tr>
+  </table>
+  <h2>Split between 2 guests</h2>
+  <table class="split">
+    <tr class="share"><td>Guest 1</td><td class="amount">14.25</td></tr>
+    <tr class="share"><td>Guest 2</td><td class="amount">14.23</td></tr>
+  </table>
+</section>
+"#;
+        assert_eq!(receipt(Format::Html), expected);
+    }
+
+    #[test]
+    fn ticket_on_narrow_paper() {
+        let expected = r#"TICKET 7                   12:12
+Order 1                  Party 3
+--------------------------------
+             GRILL
+1 x Breakfast
+    + Sourdough
+    + Extra fruit
+2 x Soup of the day
+    + Crusty roll
+--------------------------------
+              COLD
+1 x Garden salad
+    + Walnut oil
+================================
+"#;
+        assert_eq!(ticket(Format::Thermal(Paper::Narrow)), expected);
+    }
+
+    #[test]
+    fn ticket_as_json() {
+        let expected = r#"{
+  "at": "12:12",
+  "order": 1,
+  "party": 3,
+  "stations": [
+    {
+      "items": [
+        {
+          "modifiers": [
+            "Sourdough",
+            "Extra fruit"
+          ],
+          "name": "Breakfast",
+          "quantity": 1
+        },
+        {
+          "modifiers": [
+            "Crusty roll"
+          ],
+          "name": "Soup of the day",
+          "quantity": 2
+        }
+      ],
+      "station": "Grill"
+    },
+    {
+      "items": [
+        {
+          "modifiers": [
+            "Walnut oil"
+          ],
+          "name": "Garden salad",
+          "quantity": 1
+        }
+      ],
+      "station": "Cold"
+    }
+  ],
+  "ticket": 7
+}"#;
+        assert_eq!(ticket(Format::Json), expected);
+    }
+
+    #[test]
+    fn ticket_as_html() {
+        let expected = r#"<section class="ticket">
+  <h1>Ticket 7</h1>
+  <p>Order 1, party 3, at 12:12</p>
+  <h2>Grill</h2>
+  <ul>
+    <li>1 x Breakfast <span class="modifiers">+ Sourdough, + Extra fruit</span></li>
+    <li>2 x Soup of the day <span class="modifiers">+ Crusty roll</span></li>
+  </ul>
+  <h2>Cold</h2>
+  <ul>
+    <li>1 x Garden salad <span class="modifiers">+ Walnut oil</span></li>
+  </ul>
+</section>
+"#;
+        assert_eq!(ticket(Format::Html), expected);
+    }
+
+    #[test]
+    fn a_receipt_needs_the_bill_for_its_order() {
+        let mut orders = order();
+        let other = orders.open(PartyId(4), Time::hm(12, 30));
+        let bill = bill(orders.iter().next().unwrap());
+        let error = Receipt::new(orders.get(other).unwrap(), &bill).err().unwrap();
+        assert_eq!(error, PrintError::WrongBill { bill: OrderId(1), order: other });
+    }
+}
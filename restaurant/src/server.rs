@@ -0,0 +1,889 @@
+// A small HTTP server, so the waitlist, seating, orders and bills can be
+// driven from other programs (a tablet at the host stand, say) by sending
+// JSON back and forth.
+//
+// As with the web server at the end of the book, it's built straight on
+// std's TcpListener, and reads and writes HTTP by hand, and each connection
+// is handed to one of a fixed pool of worker threads. Reading a request
+// happens on the worker, so a slow or idle client only holds up its own
+// worker, and gives up after READ_TIMEOUT. The Store sits behind a Mutex,
+// which a worker takes only once it has the whole request, so changes still
+// reach the log one at a time, in a single, clear order.
+//
+// Every change is saved through the Store, so the server picks up where it
+// left off after a restart. Times are given as "HH:MM" and amounts of money
+// as strings like "12.50", as that's how they're written everywhere else.
+//
+//   GET    /waitlist                       the waiting parties, in order
+//   POST   /waitlist                       {"name", "size", "at", "phone"?, "notes"?, "priority"?,
+//                                           "allergies"?: ["Peanuts", ...]}
+//   DELETE /waitlist/{party}               the party gave up waiting
+//   GET    /seatings                       parties sat at a table
+//   POST   /seatings                       {"at"} seats the next party who fits
+//   POST   /seatings/{party}/clear         {"at"} the party has left
+//   GET    /orders                         every order
+//   POST   /orders                         {"party", "at", "items": [item, ...]}
+//   GET    /orders/{order}
+//   POST   /orders/{order}/items           {"menu_id", "modifiers"?, "quantity"?, "confirm_allergy"?}
+//   DELETE /orders/{order}/items/{index}
+//   POST   /orders/{order}/status          {"to": "SentToKitchen", "at"}
+//   GET    /orders/{order}/bill
+//   PUT    /orders/{order}/bill/tip        {"tip"}
+//   POST   /orders/{order}/bill/vouchers   {"code"} a discount voucher
+//   PUT    /orders/{order}/bill/loyalty    {"tier"} the guests' loyalty card
+//   POST   /orders/{order}/bill/payments   {"kind": "Cash", "amount", "at"}
+//   GET    /orders/{order}/bill/split?guests=3
+//
+// Successful requests get 200 OK (201 Created when something new was added,
+// or 204 No Content when there's nothing to say). Failures get a body of
+// {"error": "..."} and one of:
+//
+//   400 Bad Request         the request itself doesn't make sense
+//   404 Not Found           there's no such path, party, order, item,
+//                           voucher or loyalty tier
+//   405 Method Not Allowed  the path exists, but not for that method
+//   409 Conflict            the request is fine, but can't be done right now
+//                           (nobody fits the free tables, the bill is paid,
+//                           an item has something the party is allergic to
+//                           and "confirm_allergy" wasn't set...)
+//   408 Request Timeout     the request didn't all arrive in time
+//   413 Payload Too Large   the body is bigger than MAX_BODY
+//   431 Header Too Large    the request line and headers are bigger than
+//                           MAX_HEADERS
+//   500 Internal Error      the log couldn't be written, or a dish's recipe
+//                           is missing
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{Shutdown, SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::{mpsc, Arc, Mutex, MutexGuard};
+use std::thread;
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::back_of_house::allergens::{Allergen, AllergenError, AllergenGuide};
+use crate::back_of_house::menu::{Menu, MenuError};
+use crate::clock::Time;
+use crate::front_of_house::billing::{Bill, BillingError, Share, Tender, TenderKind};
+use crate::front_of_house::hosting::{Party, PartyId, Priority, ReservationError, Seating, SeatingError};
+use crate::front_of_house::serving::{Order, OrderError, OrderId, OrderItem, OrderStatus};
+use crate::money::Money;
+use crate::storage::{Store, StoreError};
+
+// The largest request body we'll read, in bytes
+pub const MAX_BODY: usize = 64 * 1024;
+
+// The most we'll read of the request line and headers together, in bytes
+pub const MAX_HEADERS: usize = 8 * 1024;
+
+// How long a client gets to send each part of its request, unless the
+// server is told otherwise
+pub const READ_TIMEOUT: Duration = Duration::from_secs(5);
+
+// How many connections are handled at once, unless the server is told
+// otherwise
+pub const WORKERS: usize = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApiError {
+    pub status: u16,
+    pub message: String,
+}
+
+impl ApiError {
+    fn new(status: u16, message: impl fmt::Display) -> ApiError {
+        ApiError {
+            status,
+            message: message.to_string(),
+        }
+    }
+
+    fn bad_request(message: impl fmt::Display) -> ApiError {
+        ApiError::new(400, message)
+    }
+
+    fn not_found(message: impl fmt::Display) -> ApiError {
+        ApiError::new(404, message)
+    }
+
+    fn conflict(message: impl fmt::Display) -> ApiError {
+        ApiError::new(409, message)
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} {}: {}", self.status, reason(self.status), self.message)
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+// Each of the library's errors is given the status code that best describes
+// it, so the same kind of problem always gets the same code
+impl From<SeatingError> for ApiError {
+    fn from(e: SeatingError) -> ApiError {
+        match e {
+            SeatingError::UnknownTable(_) => ApiError::not_found(e),
+            SeatingError::NobodyWaiting
+            | SeatingError::NoTableFree
+            | SeatingError::PartyTooLarge { .. }
+            | SeatingError::EmptyParty(_)
+            | SeatingError::TableTaken(_) => ApiError::conflict(e),
+        }
+    }
+}
+
+impl From<ReservationError> for ApiError {
+    fn from(e: ReservationError) -> ApiError {
+        match e {
+            ReservationError::UnknownReservation(_) => ApiError::not_found(e),
+            ReservationError::ZeroDuration | ReservationError::InThePast { .. } => ApiError::bad_request(e),
+            ReservationError::NotBooked(_)
+            | ReservationError::PartyTooLarge { .. }
+            | ReservationError::NoTablesFree { .. } => ApiError::conflict(e),
+        }
+    }
+}
+
+impl From<OrderError> for ApiError {
+    fn from(e: OrderError) -> ApiError {
+        match e {
+            OrderError::UnknownOrder(_) | OrderError::NoSuchItem { .. } => ApiError::not_found(e),
+            OrderError::IllegalTransition { .. }
+            | OrderError::NotEditable { .. }
+            | OrderError::EmptyOrder(_) => ApiError::conflict(e),
+        }
+    }
+}
+
+impl From<BillingError> for ApiError {
+    fn from(e: BillingError) -> ApiError {
+        match e {
+            BillingError::Order(e) => ApiError::from(e),
+            BillingError::InvalidAmount(_)
+            | BillingError::NegativeTip(_)
+            | BillingError::NoGuests
+            | BillingError::BadAssignment { .. } => ApiError::bad_request(e),
+            BillingError::AlreadyPaid
+            | BillingError::Overpayment { .. }
+            | BillingError::CantComp { .. }
+            | BillingError::VoucherUsed(_) => ApiError::conflict(e),
+            BillingError::UnknownVoucher(_) | BillingError::UnknownTier(_) => ApiError::not_found(e),
+        }
+    }
+}
+
+impl From<StoreError> for ApiError {
+    fn from(e: StoreError) -> ApiError {
+        match e {
+            StoreError::Io(_)
+            | StoreError::Corrupt { .. }
+            | StoreError::UnsupportedSchema { .. }
+            | StoreError::Locked(_) => ApiError::new(500, e),
+            StoreError::UnknownParty(_) | StoreError::NotWaiting(_) => ApiError::not_found(e),
+            StoreError::Seating(e) => ApiError::from(e),
+            StoreError::Reservation(e) => ApiError::from(e),
+            StoreError::Order(e) => ApiError::from(e),
+            StoreError::Billing(e) => ApiError::from(e),
+        }
+    }
+}
+
+// A dish without a recipe, or a recipe using an ingredient we know nothing
+// about, is a mistake in the house data rather than in the request
+impl From<AllergenError> for ApiError {
+    fn from(e: AllergenError) -> ApiError {
+        match e {
+            AllergenError::UnknownTag(_) => ApiError::bad_request(e),
+            AllergenError::Conflict { .. } => ApiError::conflict(e),
+            AllergenError::NoRecipe(_) | AllergenError::UnknownIngredient(_) => ApiError::new(500, e),
+        }
+    }
+}
+
+// Menu errors only come from the items in a request body. Asking for
+// something that's run out is fine in itself, it just can't be had now.
+impl From<MenuError> for ApiError {
+    fn from(e: MenuError) -> ApiError {
+        match e {
+            MenuError::Unavailable { .. } => ApiError::conflict(e),
+            _ => ApiError::bad_request(e),
+        }
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        408 => "Request Timeout",
+        409 => "Conflict",
+        413 => "Payload Too Large",
+        431 => "Request Header Fields Too Large",
+        _ => "Internal Server Error",
+    }
+}
+
+#[derive(Debug)]
+struct Request {
+    method: String,
+    path: String,
+    query: String,
+    body: Vec<u8>,
+}
+
+impl Request {
+    // Reads the JSON body into `T`. An empty body is read as {}, so requests
+    // whose fields are all optional can leave it off.
+    fn json<T: DeserializeOwned>(&self) -> Result<T, ApiError> {
+        let body: &[u8] = if self.body.is_empty() { b"{}" } else { &self.body };
+        serde_json::from_slice(body).map_err(|e| ApiError::bad_request(format!("invalid body: {}", e)))
+    }
+
+    fn query(&self, name: &str) -> Option<&str> {
+        self.query
+            .split('&')
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value)
+    }
+}
+
+struct Response {
+    status: u16,
+    body: Option<Value>,
+}
+
+impl Response {
+    fn ok(body: Value) -> Response {
+        Response { status: 200, body: Some(body) }
+    }
+
+    fn created(body: Value) -> Response {
+        Response { status: 201, body: Some(body) }
+    }
+
+    fn no_content() -> Response {
+        Response { status: 204, body: None }
+    }
+}
+
+impl From<ApiError> for Response {
+    fn from(e: ApiError) -> Response {
+        Response {
+            status: e.status,
+            body: Some(json!({ "error": e.message })),
+        }
+    }
+}
+
+// A client that stops sending part way through gets a timeout; anything
+// else that goes wrong while reading means the request was broken
+fn read_error(e: io::Error) -> ApiError {
+    match e.kind() {
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => {
+            ApiError::new(408, "the request took too long to arrive")
+        }
+        _ => ApiError::bad_request("malformed HTTP request"),
+    }
+}
+
+// Reads one line of the request line and headers. The reader's limit is
+// what's left of MAX_HEADERS, so running into it means they're too big.
+fn read_header_line(reader: &mut io::Take<BufReader<&TcpStream>>) -> Result<String, ApiError> {
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(read_error)?;
+    if !line.ends_with('\n') {
+        if reader.limit() == 0 {
+            return Err(ApiError::new(
+                431,
+                format!("the request line and headers can be at most {} bytes", MAX_HEADERS),
+            ));
+        }
+        return Err(ApiError::bad_request("malformed HTTP request"));
+    }
+    Ok(line)
+}
+
+fn read_request(stream: &TcpStream) -> Result<Request, ApiError> {
+    let mut reader = BufReader::new(stream).take(MAX_HEADERS as u64);
+
+    // The request line, e.g. "POST /waitlist HTTP/1.1"
+    let line = read_header_line(&mut reader)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Err(ApiError::bad_request("malformed HTTP request"));
+    };
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let mut request = Request {
+        method: String::from(method),
+        path: String::from(path),
+        query: String::from(query),
+        body: Vec::new(),
+    };
+
+    // Headers run until a blank line. The only one we need is the length of
+    // the body.
+    let mut length = 0;
+    loop {
+        let header = read_header_line(&mut reader)?;
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| ApiError::bad_request("Content-Length isn't a number"))?;
+            }
+        }
+    }
+    if length > MAX_BODY {
+        return Err(ApiError::new(413, format!("bodies can be at most {} bytes", MAX_BODY)));
+    }
+    request.body = vec![0; length];
+    reader.set_limit(length as u64);
+    reader.read_exact(&mut request.body).map_err(read_error)?;
+    Ok(request)
+}
+
+fn write_response(mut stream: &TcpStream, response: Response) -> io::Result<()> {
+    let body = response.body.map(|b| b.to_string()).unwrap_or_default();
+    let mut text = format!("HTTP/1.1 {} {}\r\n", response.status, reason(response.status));
+    if !body.is_empty() {
+        text += "Content-Type: application/json\r\n";
+    }
+    text += &format!("Content-Length: {}\r\nConnection: close\r\n\r\n{}", body.len(), body);
+    stream.write_all(text.as_bytes())?;
+    stream.flush()
+}
+
+// The bodies requests can send. Times and money are read from strings.
+fn time<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Time, D::Error> {
+    let text = String::deserialize(d)?;
+    text.parse().map_err(serde::de::Error::custom)
+}
+
+fn money<'de, D: serde::Deserializer<'de>>(d: D) -> Result<Money, D::Error> {
+    let text = String::deserialize(d)?;
+    text.parse().map_err(serde::de::Error::custom)
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NewParty {
+    name: String,
+    size: u32,
+    #[serde(deserialize_with = "time")]
+    at: Time,
+    phone: Option<String>,
+    notes: Option<String>,
+    #[serde(default)]
+    priority: Priority,
+    #[serde(default)]
+    allergies: Vec<Allergen>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct At {
+    #[serde(deserialize_with = "time")]
+    at: Time,
+}
+
+fn one() -> u32 {
+    1
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NewItem {
+    menu_id: String,
+    #[serde(default)]
+    modifiers: Vec<String>,
+    #[serde(default = "one")]
+    quantity: u32,
+    // The guest has been told what's in it, and wants it anyway
+    #[serde(default)]
+    confirm_allergy: bool,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NewOrder {
+    party: u32,
+    #[serde(deserialize_with = "time")]
+    at: Time,
+    #[serde(default)]
+    items: Vec<NewItem>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct StatusChange {
+    to: OrderStatus,
+    #[serde(deserialize_with = "time")]
+    at: Time,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Tip {
+    #[serde(deserialize_with = "money")]
+    tip: Money,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Voucher {
+    code: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Loyalty {
+    tier: String,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Payment {
+    kind: TenderKind,
+    #[serde(deserialize_with = "money")]
+    amount: Money,
+    #[serde(deserialize_with = "time")]
+    at: Time,
+}
+
+// And what we send back. These are built by hand, rather than deriving
+// Serialize on the library's types, so the API can stay the same while the
+// library changes underneath it.
+fn seating_json(seating: &Seating) -> Value {
+    json!({
+        "party": seating.id.0,
+        "name": seating.party.name,
+        "size": seating.party.size,
+        "tables": seating.tables.iter().map(|t| t.0).collect::<Vec<_>>(),
+        "seated_at": seating.seated_at.to_string(),
+    })
+}
+
+fn order_json(order: &Order) -> Value {
+    let items: Vec<Value> = order
+        .items()
+        .iter()
+        .map(|item| {
+            json!({
+                "menu_id": item.menu_id,
+                "name": item.name,
+                "modifiers": item.modifiers,
+                "quantity": item.quantity,
+                "unit_price": item.unit_price.to_string(),
+                "total": item.total().to_string(),
+            })
+        })
+        .collect();
+    let total: Money = order.items().iter().map(|item| item.total()).sum();
+    json!({
+        "id": order.id.0,
+        "party": order.party.0,
+        "status": order.status(),
+        "items": items,
+        "total": total.to_string(),
+    })
+}
+
+fn bill_json(bill: &Bill) -> Value {
+    let lines: Vec<Value> = bill
+        .lines()
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            let discounts: Vec<Value> = bill
+                .discounts_on(i)
+                .map(|d| json!({ "rule": d.rule, "amount": d.amount.to_string(), "reason": d.reason }))
+                .collect();
+            json!({
+                "description": line.description,
+                "quantity": line.quantity,
+                "unit_price": line.unit_price.to_string(),
+                "total": line.total.to_string(),
+                "discounts": discounts,
+            })
+        })
+        .collect();
+    let taxes: Vec<Value> = bill
+        .taxes()
+        .iter()
+        .map(|(name, amount)| json!({ "name": name, "amount": amount.to_string() }))
+        .collect();
+    json!({
+        "order": bill.order.0,
+        "lines": lines,
+        "subtotal": bill.subtotal().to_string(),
+        "taxes": taxes,
+        "service_charge": bill.service_charge().to_string(),
+        "tip": bill.tip().to_string(),
+        "total": bill.total().to_string(),
+        "paid": bill.paid().to_string(),
+        "balance": bill.balance().to_string(),
+        "settled": bill.is_settled(),
+    })
+}
+
+fn share_json(share: &Share) -> Value {
+    json!({
+        "guest": share.guest,
+        "items": share.items.to_string(),
+        "extras": share.extras.to_string(),
+        "total": share.total.to_string(),
+    })
+}
+
+fn id(segment: &str, what: &str) -> Result<u32, ApiError> {
+    segment
+        .parse()
+        .map_err(|_| ApiError::not_found(format!("\"{}\" is not a valid {} id", segment, what)))
+}
+
+type Job = Box<dyn FnOnce() + Send + 'static>;
+
+// A fixed number of threads, each taking jobs off a shared channel, like the
+// ThreadPool in the book's final chapter. Dropping it lets the threads
+// finish whatever they're doing, then waits for them.
+struct Workers {
+    sender: Option<mpsc::Sender<Job>>,
+    threads: Vec<thread::JoinHandle<()>>,
+}
+
+impl Workers {
+    fn new(count: usize) -> Workers {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let threads = (0..count)
+            .map(|_| {
+                let receiver = Arc::clone(&receiver);
+                thread::spawn(move || loop {
+                    // The lock is let go as soon as a job has been taken, so
+                    // the other workers can take the next one
+                    let job = receiver.lock().expect("a worker panicked while waiting").recv();
+                    match job {
+                        Ok(job) => job(),
+                        Err(_) => break,
+                    }
+                })
+            })
+            .collect();
+        Workers {
+            sender: Some(sender),
+            threads,
+        }
+    }
+
+    fn run(&self, job: impl FnOnce() + Send + 'static) {
+        if let Some(sender) = &self.sender {
+            sender.send(Box::new(job)).expect("the workers outlive the sender");
+        }
+    }
+}
+
+impl Drop for Workers {
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+    }
+}
+
+// What every worker shares. Only the store changes, so only it is locked.
+struct Shared {
+    store: Mutex<Store>,
+    menu: Menu,
+    allergens: AllergenGuide,
+}
+
+// One request's view of the restaurant, while the worker holds the lock on
+// the store
+struct Handler<'a> {
+    store: &'a mut Store,
+    menu: &'a Menu,
+    allergens: &'a AllergenGuide,
+}
+
+fn handle_connection(shared: &Shared, stream: TcpStream, timeout: Duration) {
+    let timeouts = stream
+        .set_read_timeout(Some(timeout))
+        .and_then(|_| stream.set_write_timeout(Some(timeout)));
+    if timeouts.is_err() {
+        return;
+    }
+    let response = match read_request(&stream) {
+        Ok(request) => {
+            let mut store = shared.store.lock().expect("a request panicked while changing the store");
+            let mut handler = Handler {
+                store: &mut store,
+                menu: &shared.menu,
+                allergens: &shared.allergens,
+            };
+            handler.route(&request).unwrap_or_else(Response::from)
+        }
+        Err(e) => Response::from(e),
+    };
+    // If the client has gone away there's nobody to tell, so just move on
+    if write_response(&stream, response).is_err() {
+        return;
+    }
+    // A request we turned down may not have been read to the end. Closing
+    // with some of it unread would reset the connection, and the client
+    // could lose the response, so read (and ignore) what's left first.
+    if stream.shutdown(Shutdown::Write).is_ok() {
+        let _ = io::copy(&mut (&stream).take(MAX_BODY as u64), &mut io::sink());
+    }
+}
+
+pub struct Server {
+    listener: TcpListener,
+    shared: Arc<Shared>,
+    workers: usize,
+    timeout: Duration,
+}
+
+impl Server {
+    // Use port 0 to have the operating system pick a free port, then
+    // local_addr to find out which
+    pub fn bind(addr: impl ToSocketAddrs, store: Store, menu: Menu) -> io::Result<Server> {
+        Ok(Server {
+            listener: TcpListener::bind(addr)?,
+            shared: Arc::new(Shared {
+                store: Mutex::new(store),
+                menu,
+                allergens: AllergenGuide::house(),
+            }),
+            workers: WORKERS,
+            timeout: READ_TIMEOUT,
+        })
+    }
+
+    // How many connections can be handled at the same time
+    pub fn workers(mut self, count: usize) -> Server {
+        assert!(count > 0, "the server needs at least one worker");
+        self.workers = count;
+        self
+    }
+
+    // How long to wait for each part of a request before giving up on it
+    pub fn read_timeout(mut self, timeout: Duration) -> Server {
+        assert!(!timeout.is_zero(), "a read timeout has to be longer than nothing");
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    // The store, for as long as the guard is held. Requests wait until it's
+    // let go.
+    pub fn store(&self) -> MutexGuard<'_, Store> {
+        self.shared.store.lock().expect("a request panicked while changing the store")
+    }
+
+    // Handles requests until the program is stopped
+    pub fn run(&self) {
+        self.run_for(usize::MAX);
+    }
+
+    // Handles `count` connections, then returns once they've all been
+    // answered. Handy for trying the server out from the same program.
+    pub fn run_for(&self, count: usize) {
+        let workers = Workers::new(self.workers);
+        for _ in 0..count {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    let shared = Arc::clone(&self.shared);
+                    let timeout = self.timeout;
+                    workers.run(move || handle_connection(&shared, stream, timeout));
+                }
+                Err(e) => eprintln!("Couldn't accept a connection: {}", e),
+            }
+        }
+    }
+}
+
+impl Handler<'_> {
+    fn route(&mut self, request: &Request) -> Result<Response, ApiError> {
+        let segments: Vec<&str> = request.path.split('/').filter(|s| !s.is_empty()).collect();
+        let method = request.method.as_str();
+
+        match (segments.as_slice(), method) {
+            (["waitlist"], "GET") => self.waitlist(),
+            (["waitlist"], "POST") => self.add_party(request.json()?),
+            (["waitlist", party], "DELETE") => {
+                self.store.remove_party(PartyId(id(party, "party")?))?;
+                Ok(Response::no_content())
+            }
+            (["seatings"], "GET") => {
+                let seatings = self.store.state().seatings().iter().map(seating_json).collect();
+                Ok(Response::ok(Value::Array(seatings)))
+            }
+            (["seatings"], "POST") => {
+                let At { at } = request.json()?;
+                Ok(Response::created(seating_json(&self.store.seat(at)?)))
+            }
+            (["seatings", party, "clear"], "POST") => {
+                let At { at } = request.json()?;
+                self.store.clear_table(PartyId(id(party, "party")?), at)?;
+                Ok(Response::no_content())
+            }
+            (["orders"], "GET") => {
+                let orders = self.store.state().orders().iter().map(order_json).collect();
+                Ok(Response::ok(Value::Array(orders)))
+            }
+            (["orders"], "POST") => self.open_order(request.json()?),
+            (["orders", order], "GET") => Ok(Response::ok(self.order(order)?)),
+            (["orders", order, "items"], "POST") => {
+                let new: NewItem = request.json()?;
+                let order = OrderId(id(order, "order")?);
+                let party = self.store.state().orders().get(order)?.party;
+                let item = self.menu_item(party, new)?;
+                self.store.add_item(order, item)?;
+                Ok(Response::ok(order_json(self.store.state().orders().get(order)?)))
+            }
+            (["orders", order, "items", index], "DELETE") => {
+                let order = OrderId(id(order, "order")?);
+                let index = index
+                    .parse()
+                    .map_err(|_| ApiError::not_found(format!("\"{}\" is not an item number", index)))?;
+                self.store.remove_item(order, index)?;
+                Ok(Response::ok(order_json(self.store.state().orders().get(order)?)))
+            }
+            (["orders", order, "status"], "POST") => {
+                let StatusChange { to, at } = request.json()?;
+                let order = OrderId(id(order, "order")?);
+                self.store.move_order(order, to, at)?;
+                Ok(Response::ok(order_json(self.store.state().orders().get(order)?)))
+            }
+            (["orders", order, "bill"], "GET") => {
+                let bill = self.store.state().bill(OrderId(id(order, "order")?))?;
+                Ok(Response::ok(bill_json(&bill)))
+            }
+            (["orders", order, "bill", "tip"], "PUT") => {
+                let Tip { tip } = request.json()?;
+                let order = OrderId(id(order, "order")?);
+                self.store.set_tip(order, tip)?;
+                Ok(Response::ok(bill_json(&self.store.state().bill(order)?)))
+            }
+            (["orders", order, "bill", "vouchers"], "POST") => {
+                let Voucher { code } = request.json()?;
+                let order = OrderId(id(order, "order")?);
+                self.store.redeem_voucher(order, &code)?;
+                Ok(Response::ok(bill_json(&self.store.state().bill(order)?)))
+            }
+            (["orders", order, "bill", "loyalty"], "PUT") => {
+                let Loyalty { tier } = request.json()?;
+                let order = OrderId(id(order, "order")?);
+                self.store.set_loyalty(order, &tier)?;
+                Ok(Response::ok(bill_json(&self.store.state().bill(order)?)))
+            }
+            (["orders", order, "bill", "payments"], "POST") => {
+                let Payment { kind, amount, at } = request.json()?;
+                let order = OrderId(id(order, "order")?);
+                let receipt = self.store.pay(order, Tender::new(kind, amount, at))?;
+                Ok(Response::created(json!({
+                    "applied": receipt.applied.to_string(),
+                    "change": receipt.change.to_string(),
+                    "balance": receipt.balance.to_string(),
+                })))
+            }
+            (["orders", order, "bill", "split"], "GET") => {
+                let guests = request
+                    .query("guests")
+                    .ok_or_else(|| ApiError::bad_request("say how many guests to split between, e.g. ?guests=3"))?;
+                let guests: usize = guests
+                    .parse()
+                    .map_err(|_| ApiError::bad_request(format!("\"{}\" is not a number of guests", guests)))?;
+                let bill = self.store.state().bill(OrderId(id(order, "order")?))?;
+                let shares = bill.split_evenly(guests)?;
+                Ok(Response::ok(Value::Array(shares.iter().map(share_json).collect())))
+            }
+            (["waitlist"] | ["seatings"] | ["orders"], _)
+            | (["waitlist", _] | ["seatings", _, "clear"] | ["orders", _], _)
+            | (["orders", _, "items"] | ["orders", _, "items", _] | ["orders", _, "status"], _)
+            | (["orders", _, "bill"] | ["orders", _, "bill", "tip" | "vouchers" | "loyalty"], _)
+            | (["orders", _, "bill", "payments" | "split"], _) => Err(ApiError::new(
+                405,
+                format!("{} can't be used with {}", method, request.path),
+            )),
+            _ => Err(ApiError::not_found(format!("there's nothing at {}", request.path))),
+        }
+    }
+
+    fn waitlist(&self) -> Result<Response, ApiError> {
+        let waitlist = self.store.state().waitlist();
+        let entries = waitlist
+            .iter()
+            .map(|entry| {
+                json!({
+                    "id": entry.id.0,
+                    "name": entry.party.name,
+                    "size": entry.party.size,
+                    "arrived": entry.party.arrived.to_string(),
+                    "priority": entry.party.priority,
+                    "wait_minutes": waitlist.estimated_wait(entry.id),
+                })
+            })
+            .collect();
+        Ok(Response::ok(Value::Array(entries)))
+    }
+
+    fn add_party(&mut self, new: NewParty) -> Result<Response, ApiError> {
+        if new.name.trim().is_empty() || new.size == 0 {
+            return Err(ApiError::bad_request("a party needs a name and at least one guest"));
+        }
+        let mut party = Party::new(&new.name, new.size, new.at).priority(new.priority);
+        party.phone = new.phone;
+        party.notes = new.notes;
+        party.allergies = new.allergies;
+        let id = self.store.add_party(party)?;
+        let wait = self.store.state().waitlist().estimated_wait(id);
+        Ok(Response::created(json!({ "id": id.0, "wait_minutes": wait })))
+    }
+
+    // Builds the item, refusing anything the party is allergic to unless
+    // the guest has confirmed it
+    fn menu_item(&self, party: PartyId, new: NewItem) -> Result<OrderItem, ApiError> {
+        let modifiers: Vec<&str> = new.modifiers.iter().map(|m| m.as_str()).collect();
+        let item = OrderItem::from_menu(self.menu, &new.menu_id, &modifiers, new.quantity)?;
+        let seating = self.store.state().seatings().iter().find(|s| s.id == party);
+        if let Some(seating) = seating.filter(|_| !new.confirm_allergy) {
+            self.allergens.check(&seating.party, std::slice::from_ref(&item))?;
+        }
+        Ok(item)
+    }
+
+    fn open_order(&mut self, new: NewOrder) -> Result<Response, ApiError> {
+        let items = new
+            .items
+            .into_iter()
+            .map(|item| self.menu_item(PartyId(new.party), item))
+            .collect::<Result<Vec<_>, _>>()?;
+        let id = self.store.open_order(PartyId(new.party), items, new.at)?;
+        Ok(Response::created(order_json(self.store.state().orders().get(id)?)))
+    }
+
+    fn order(&self, order: &str) -> Result<Value, ApiError> {
+        let order = self.store.state().orders().get(OrderId(id(order, "order")?))?;
+        Ok(order_json(order))
+    }
+}
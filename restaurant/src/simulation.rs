@@ -0,0 +1,586 @@
+// A discrete-event simulation of an evening's service, for asking "what if"
+// questions offline: what if we had another table for four, or a second
+// cook on the grill?
+//
+// Rather than ticking through every minute, the simulation keeps a queue of
+// things that are going to happen (a party arriving, an order being taken,
+// food coming out of the kitchen...) and jumps straight from one to the
+// next. Handling one event usually schedules more, and the evening is over
+// when there's nothing left in the queue.
+//
+// The real hosting and serving code does the seating and order keeping, so
+// the simulation follows the same rules as the restaurant does. The kitchen
+// is the exception: the real one cooks in real time on its own threads, so
+// here each station is modelled as a number of cooks who each work through
+// one item at a time, using the same routing and prep times.
+//
+// Parties arrive at random, but from a seeded generator, so the same config
+// always gives the same evening. The arrivals are all worked out before the
+// evening starts, from their own generator, so two configs with the same
+// seed see exactly the same guests walk in and can be compared fairly.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::fmt;
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+use crate::back_of_house::kitchen::{Routing, Station};
+use crate::back_of_house::menu::Menu;
+use crate::clock::Time;
+use crate::front_of_house::hosting::{
+    self, Floor, Party, PartyId, ReservationBook, Seating, TableId, TableTurnover, Waitlist,
+};
+use crate::front_of_house::serving::{self, OrderBook, OrderId, OrderItem};
+
+// Minutes between a party sitting down and a server getting to them, and
+// how long taking their order keeps the server busy
+const SETTLE_MINUTES: u32 = 2;
+const ORDER_MINUTES: u32 = 3;
+
+// How often the kitchen backlog is measured
+const SAMPLE_MINUTES: u32 = 5;
+
+#[derive(Debug, Clone)]
+pub struct SimulationConfig {
+    pub seed: u64,
+    pub opens: Time,
+    // No new parties arrive after this, but everyone already in is served
+    pub last_arrival: Time,
+    pub arrivals_per_hour: f64,
+    pub tables: Vec<u32>,
+    // Pairs of tables (numbered from 0, in the order listed) which can be
+    // pushed together
+    pub joins: Vec<(usize, usize)>,
+    pub servers: u32,
+    pub cooks: Vec<(Station, u32)>,
+    pub routing: Routing,
+    pub menu: Menu,
+    // How long a party will wait for a table before giving up and leaving
+    pub patience_minutes: u32,
+    // How long a party stays once their food arrives, on average
+    pub dining_minutes: u32,
+}
+
+impl SimulationConfig {
+    // A small dinner service on the house menu, which the other methods can
+    // then change one thing at a time
+    pub fn new(seed: u64) -> SimulationConfig {
+        SimulationConfig {
+            seed,
+            opens: Time::hm(18, 0),
+            last_arrival: Time::hm(21, 30),
+            arrivals_per_hour: 12.0,
+            tables: vec![2, 2, 2, 4, 4, 6],
+            joins: vec![(0, 1), (1, 2)],
+            servers: 2,
+            cooks: Station::ALL.iter().map(|&station| (station, 1)).collect(),
+            routing: Routing::house(),
+            menu: Menu::house(),
+            patience_minutes: 30,
+            dining_minutes: 45,
+        }
+    }
+
+    pub fn hours(mut self, opens: Time, last_arrival: Time) -> SimulationConfig {
+        self.opens = opens;
+        self.last_arrival = last_arrival;
+        self
+    }
+
+    // As with Guess, a nonsensical setting is a bug in the caller, so these
+    // panic rather than returning an error
+    pub fn arrivals_per_hour(mut self, rate: f64) -> SimulationConfig {
+        check_rate(rate);
+        self.arrivals_per_hour = rate;
+        self
+    }
+
+    // Joins are pairs of indexes into `capacities`, for tables that can be
+    // pushed together
+    pub fn tables(mut self, capacities: &[u32], joins: &[(usize, usize)]) -> SimulationConfig {
+        check_joins(capacities, joins);
+        self.tables = capacities.to_vec();
+        self.joins = joins.to_vec();
+        self
+    }
+
+    pub fn servers(mut self, servers: u32) -> SimulationConfig {
+        check_servers(servers);
+        self.servers = servers;
+        self
+    }
+
+    pub fn cooks(mut self, station: Station, cooks: u32) -> SimulationConfig {
+        check_cooks(station, cooks);
+        self.cooks.retain(|&(s, _)| s != station);
+        self.cooks.push((station, cooks));
+        self
+    }
+
+    pub fn patience(mut self, minutes: u32) -> SimulationConfig {
+        self.patience_minutes = minutes;
+        self
+    }
+
+    pub fn dining(mut self, minutes: u32) -> SimulationConfig {
+        self.dining_minutes = minutes;
+        self
+    }
+
+    // The fields are public, so they can be set without going through the
+    // methods above. This makes the same checks again before a simulation
+    // starts, along with any the methods can't make on their own.
+    fn check(&self) {
+        check_rate(self.arrivals_per_hour);
+        check_joins(&self.tables, &self.joins);
+        check_servers(self.servers);
+        for &(station, cooks) in &self.cooks {
+            check_cooks(station, cooks);
+        }
+        for station in Station::ALL {
+            if !self.cooks.iter().any(|&(s, _)| s == station) {
+                check_cooks(station, 0);
+            }
+        }
+        if let Some(item) = self.menu.items().find(|item| self.routing.lookup(&item.id).is_none()) {
+            panic!("{} is on the menu, but no station knows how to cook it.", item.name);
+        }
+    }
+}
+
+fn check_rate(rate: f64) {
+    if !(rate.is_finite() && rate > 0.0) {
+        panic!("Arrivals per hour must be a positive number, got {rate}.");
+    }
+}
+
+fn check_joins(capacities: &[u32], joins: &[(usize, usize)]) {
+    for &(a, b) in joins {
+        if a.max(b) >= capacities.len() {
+            panic!(
+                "Tables {a} and {b} can't be joined, as there are only {} tables.",
+                capacities.len()
+            );
+        }
+    }
+}
+
+fn check_servers(servers: u32) {
+    if servers == 0 {
+        panic!("There must be at least one server.");
+    }
+}
+
+fn check_cooks(station: Station, cooks: u32) {
+    if cooks == 0 {
+        panic!("Every station needs at least one cook, but {station:?} was given none.");
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SimulationReport {
+    pub arrivals: u32,
+    pub parties_seated: u32,
+    pub covers_served: u32,
+    // Parties who gave up waiting for a table, and how many people that was
+    pub walkouts: u32,
+    pub walkout_covers: u32,
+    // Minutes from arriving to being seated, for parties who were seated
+    pub average_wait: f64,
+    pub longest_wait: u32,
+    // The share of table time (from opening until the last party left)
+    // that tables spent with someone sat at them, from 0 to 1
+    pub table_utilization: f64,
+    // Minutes from an order reaching the kitchen until it was all ready
+    pub average_ticket_minutes: f64,
+    // Items waiting for a cook, measured every few minutes
+    pub average_backlog: f64,
+    pub peak_backlog: usize,
+    pub closed_at: Time,
+}
+
+impl fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(
+            f,
+            "{} parties arrived, {} seated ({} covers), {} walked out ({} covers)",
+            self.arrivals, self.parties_seated, self.covers_served, self.walkouts, self.walkout_covers
+        )?;
+        writeln!(
+            f,
+            "Wait for a table: {:.1} minutes on average, {} at most",
+            self.average_wait, self.longest_wait
+        )?;
+        writeln!(f, "Tables in use {:.0}% of the evening", self.table_utilization * 100.0)?;
+        writeln!(
+            f,
+            "Kitchen: {:.1} minutes per ticket, {:.1} items waiting on average, {} at most",
+            self.average_ticket_minutes, self.average_backlog, self.peak_backlog
+        )?;
+        write!(f, "Last party left at {}", self.closed_at)
+    }
+}
+
+// Variants only hold ids, so events can be kept in the (ordered) queue
+// directly. Events at the same minute happen in the order they were added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Event {
+    // A party of this size walks in
+    Arrival(u32),
+    GiveUp(PartyId),
+    TakeOrder(PartyId),
+    FoodReady(PartyId),
+    Leave(PartyId),
+    Sample,
+}
+
+fn mean(values: &[u32]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.iter().map(|&v| f64::from(v)).sum::<f64>() / values.len() as f64
+}
+
+struct Service<'a> {
+    config: &'a SimulationConfig,
+    // Used for what guests order and how long they stay
+    rng: StdRng,
+    queue: BinaryHeap<Reverse<(u32, u64, Event)>>,
+    next_sequence: u64,
+
+    waitlist: Waitlist,
+    reservations: ReservationBook,
+    floor: Floor,
+    orders: OrderBook,
+    seated: HashMap<PartyId, (Seating, Option<OrderId>)>,
+
+    // When each server, and each cook at each station, is next free
+    servers: Vec<u32>,
+    cooks: HashMap<Station, Vec<u32>>,
+    // When each item sent to the kitchen is due to be started
+    item_starts: Vec<u32>,
+
+    arrivals: u32,
+    covers_served: u32,
+    walkouts: u32,
+    walkout_covers: u32,
+    waits: Vec<u32>,
+    ticket_minutes: Vec<u32>,
+    backlog: Vec<usize>,
+    table_minutes: u32,
+    last_event: u32,
+}
+
+impl<'a> Service<'a> {
+    fn new(config: &'a SimulationConfig) -> Service<'a> {
+        let mut floor = Floor::new();
+        let ids: Vec<TableId> = config.tables.iter().map(|&c| floor.add_table(c)).collect();
+        for &(a, b) in &config.joins {
+            let (a, b) = (ids[a], ids[b]);
+            floor.join(a, b).expect("both tables were just added");
+        }
+        let turnover = TableTurnover::new(config.tables.len() as u32, config.dining_minutes + 15);
+
+        Service {
+            config,
+            rng: StdRng::seed_from_u64(config.seed.wrapping_add(1)),
+            queue: BinaryHeap::new(),
+            next_sequence: 0,
+            waitlist: Waitlist::new(turnover),
+            reservations: ReservationBook::new(),
+            floor,
+            orders: OrderBook::new(),
+            seated: HashMap::new(),
+            servers: vec![0; config.servers as usize],
+            cooks: config
+                .cooks
+                .iter()
+                .map(|&(station, cooks)| (station, vec![0; cooks as usize]))
+                .collect(),
+            item_starts: Vec::new(),
+            arrivals: 0,
+            covers_served: 0,
+            walkouts: 0,
+            walkout_covers: 0,
+            waits: Vec::new(),
+            ticket_minutes: Vec::new(),
+            backlog: Vec::new(),
+            table_minutes: 0,
+            last_event: config.opens.minutes(),
+        }
+    }
+
+    fn schedule(&mut self, at: u32, event: Event) {
+        self.queue.push(Reverse((at, self.next_sequence, event)));
+        self.next_sequence += 1;
+    }
+
+    // Arrivals are a Poisson process, so the gaps between them follow an
+    // exponential distribution with the right average. Most parties are
+    // couples, with the odd big group.
+    fn schedule_arrivals(&mut self) {
+        let mut rng = StdRng::seed_from_u64(self.config.seed);
+        let mean_gap = 60.0 / self.config.arrivals_per_hour;
+        let mut now = self.config.opens.minutes();
+        loop {
+            let u: f64 = rng.gen();
+            now += (-(1.0 - u).ln() * mean_gap).round() as u32;
+            if now > self.config.last_arrival.minutes() {
+                break;
+            }
+            let size = match rng.gen_range(0..100) {
+                0..=9 => 1,
+                10..=49 => 2,
+                50..=64 => 3,
+                65..=84 => 4,
+                85..=89 => 5,
+                _ => 6,
+            };
+            self.schedule(now, Event::Arrival(size));
+        }
+    }
+
+    // Each guest picks something at random, along with one option from
+    // every group that needs one
+    fn pick_items(&mut self, guests: u32) -> Vec<OrderItem> {
+        let dishes: Vec<_> = self.config.menu.items().collect();
+        let mut items = Vec::new();
+        for _ in 0..guests {
+            let dish = dishes[self.rng.gen_range(0..dishes.len())];
+            let choices: Vec<&str> = dish
+                .modifiers
+                .iter()
+                .filter(|g| g.required)
+                .map(|g| g.options[self.rng.gen_range(0..g.options.len())].name.as_str())
+                .collect();
+            items.push(
+                OrderItem::from_menu(&self.config.menu, &dish.id, &choices, 1)
+                    .expect("the choices were taken from the menu"),
+            );
+        }
+        items
+    }
+
+    fn seat_everyone(&mut self, now: u32) {
+        while let Ok(seating) = hosting::seat_at_table(
+            &mut self.waitlist,
+            &mut self.reservations,
+            &mut self.floor,
+            Time::from_minutes(now),
+        ) {
+            self.waits.push(Time::from_minutes(now).minutes_since(seating.party.arrived));
+
+            // Whichever server is free first comes over once they've settled
+            let (server, &free) = self
+                .servers
+                .iter()
+                .enumerate()
+                .min_by_key(|&(_, free)| *free)
+                .expect("there is always at least one server");
+            let done = (now + SETTLE_MINUTES).max(free) + ORDER_MINUTES;
+            self.servers[server] = done;
+            self.schedule(done, Event::TakeOrder(seating.id));
+            self.seated.insert(seating.id, (seating, None));
+        }
+    }
+
+    // Hands each item to the cook at its station who will be free first,
+    // and works out when the last of them will be ready
+    fn cook(&mut self, items: &[OrderItem], now: u32) -> u32 {
+        let mut ready = now;
+        for item in items {
+            let (station, prep) = self
+                .config
+                .routing
+                .lookup(&item.menu_id)
+                .expect("every item on the menu has a station");
+            let cooks = self
+                .cooks
+                .get_mut(&station)
+                .expect("every station has a cook");
+            let cook = (0..cooks.len())
+                .min_by_key(|&i| cooks[i])
+                .expect("every station has a cook");
+            let start = cooks[cook].max(now);
+            cooks[cook] = start + prep * item.quantity;
+            self.item_starts.push(start);
+            ready = ready.max(cooks[cook]);
+        }
+        ready
+    }
+
+    fn handle(&mut self, now: u32, event: Event) {
+        match event {
+            Event::Arrival(size) => {
+                self.arrivals += 1;
+                let name = format!("Party {}", self.arrivals);
+                let party = Party::new(&name, size, Time::from_minutes(now));
+                let id = hosting::add_to_waitlist(&mut self.waitlist, party);
+                self.schedule(now + self.config.patience_minutes, Event::GiveUp(id));
+                self.seat_everyone(now);
+            }
+            Event::GiveUp(id) => {
+                if let Some(party) = self.waitlist.remove(id) {
+                    self.walkouts += 1;
+                    self.walkout_covers += party.size;
+                }
+            }
+            Event::TakeOrder(id) => {
+                let guests = self.seated[&id].0.party.size;
+                let items = self.pick_items(guests);
+                let at = Time::from_minutes(now);
+                let order_id = serving::take_order(&mut self.orders, id, items, at)
+                    .expect("a new order can always be taken");
+                let order = self.orders.get_mut(order_id).expect("the order was just taken");
+                order.send_to_kitchen(at).expect("every guest ordered something");
+                order.start_cooking(at).expect("the order was just sent");
+                let items = order.items().to_vec();
+
+                let ready = self.cook(&items, now);
+                self.ticket_minutes.push(ready - now);
+                if let Some(seated) = self.seated.get_mut(&id) {
+                    seated.1 = Some(order_id);
+                }
+                self.schedule(ready, Event::FoodReady(id));
+            }
+            Event::FoodReady(id) => {
+                let order_id = self.seated[&id].1.expect("food is only ready once ordered");
+                let at = Time::from_minutes(now);
+                let order = self.orders.get_mut(order_id).expect("the order exists");
+                order.mark_ready(at).expect("the order was cooking");
+                serving::serve_order(&mut self.orders, order_id, at).expect("the order is ready");
+
+                let spread = self.rng.gen_range(0.75..1.25);
+                let stay = (f64::from(self.config.dining_minutes) * spread).round() as u32;
+                self.schedule(now + stay, Event::Leave(id));
+            }
+            Event::Leave(id) => {
+                let (seating, order_id) = self.seated.remove(&id).expect("only seated parties leave");
+                let at = Time::from_minutes(now);
+                if let Some(order_id) = order_id {
+                    let order = self.orders.get_mut(order_id).expect("the order exists");
+                    order.pay(at).expect("the order was served");
+                }
+                self.table_minutes += at.minutes_since(seating.seated_at) * seating.tables.len() as u32;
+                self.covers_served += seating.party.size;
+                hosting::clear_table(&mut self.waitlist, &mut self.floor, &seating, at);
+                self.seat_everyone(now);
+            }
+            Event::Sample => {
+                self.item_starts.retain(|&start| start > now);
+                self.backlog.push(self.item_starts.len());
+                if !self.queue.is_empty() {
+                    self.schedule(now + SAMPLE_MINUTES, Event::Sample);
+                }
+            }
+        }
+    }
+
+    fn run(mut self) -> SimulationReport {
+        let opens = self.config.opens.minutes();
+        self.schedule(opens, Event::Sample);
+        self.schedule_arrivals();
+
+        while let Some(Reverse((now, _, event))) = self.queue.pop() {
+            if event != Event::Sample {
+                self.last_event = now;
+            }
+            self.handle(now, event);
+        }
+
+        let open_minutes = self.last_event.saturating_sub(opens).max(1);
+        let table_time = open_minutes * self.config.tables.len().max(1) as u32;
+        SimulationReport {
+            arrivals: self.arrivals,
+            parties_seated: self.waits.len() as u32,
+            covers_served: self.covers_served,
+            walkouts: self.walkouts,
+            walkout_covers: self.walkout_covers,
+            average_wait: mean(&self.waits),
+            longest_wait: self.waits.iter().copied().max().unwrap_or(0),
+            table_utilization: f64::from(self.table_minutes) / f64::from(table_time),
+            average_ticket_minutes: mean(&self.ticket_minutes),
+            average_backlog: if self.backlog.is_empty() {
+                0.0
+            } else {
+                self.backlog.iter().sum::<usize>() as f64 / self.backlog.len() as f64
+            },
+            peak_backlog: self.backlog.iter().copied().max().unwrap_or(0),
+            closed_at: Time::from_minutes(self.last_event),
+        }
+    }
+}
+
+// Runs a whole evening with the given config. Panics if the config makes no
+// sense, in the same way as the SimulationConfig methods do.
+pub fn simulate(config: &SimulationConfig) -> SimulationReport {
+    config.check();
+    Service::new(config).run()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_same_seed_gives_the_same_evening() {
+        let config = SimulationConfig::new(7).tables(&[4, 4], &[(0, 1)]);
+        let report = simulate(&config);
+        assert!(report.parties_seated > 0);
+        assert_eq!(simulate(&config), report);
+
+        // A different seed sees different guests
+        assert_ne!(simulate(&SimulationConfig { seed: 8, ..config }), report);
+    }
+
+    #[test]
+    fn more_tables_turn_fewer_people_away() {
+        let busy = SimulationConfig::new(11).arrivals_per_hour(30.0).tables(&[2, 2, 4], &[(0, 1)]);
+        let before = simulate(&busy);
+        let after = simulate(&busy.clone().tables(&[2, 2, 4, 4, 6, 6], &[(0, 1)]));
+        assert_eq!(after.arrivals, before.arrivals);
+        assert!(before.walkouts > 0);
+        assert!(after.walkouts < before.walkouts);
+        assert!(after.covers_served > before.covers_served);
+        assert!(after.average_wait <= before.average_wait);
+    }
+
+    #[test]
+    fn more_cooks_keep_the_kitchen_moving() {
+        let busy = SimulationConfig::new(11).arrivals_per_hour(30.0);
+        let before = simulate(&busy);
+        let mut staffed = busy.clone();
+        for station in Station::ALL {
+            staffed = staffed.cooks(station, 3);
+        }
+        let after = simulate(&staffed);
+        assert_eq!(after.arrivals, before.arrivals);
+        assert!(after.walkouts <= before.walkouts);
+        assert!(after.average_ticket_minutes < before.average_ticket_minutes);
+        assert!(after.peak_backlog <= before.peak_backlog);
+    }
+
+    #[test]
+    #[should_panic(expected = "There must be at least one server.")]
+    fn a_config_changed_by_hand_still_needs_a_server() {
+        let mut config = SimulationConfig::new(7);
+        config.servers = 0;
+        simulate(&config);
+    }
+
+    #[test]
+    #[should_panic(expected = "Every station needs at least one cook, but Fryer was given none.")]
+    fn a_config_changed_by_hand_still_needs_every_station_staffed() {
+        let mut config = SimulationConfig::new(7);
+        config.cooks.retain(|&(station, _)| station != Station::Fryer);
+        simulate(&config);
+    }
+
+    #[test]
+    #[should_panic(expected = "Tables 1 and 2 can't be joined, as there are only 2 tables.")]
+    fn joins_must_name_tables_that_exist() {
+        SimulationConfig::new(7).tables(&[4, 4], &[(1, 2)]);
+    }
+}
@@ -0,0 +1,998 @@
+// Saving the restaurant's state, so that it survives the program stopping
+// (or crashing) part way through an evening.
+//
+// Everything that changes the waitlist, reservations, orders or payments is
+// written down as a Record, one JSON object per line, at the end of a log
+// file. The file is never rewritten in place: to load it, we start from an
+// empty restaurant and replay every record through the same hosting and
+// serving code that made it in the first place. This is sometimes called
+// event sourcing, and it means the log doubles as an audit trail.
+//
+// The first line of the file is a header giving the schema version the
+// records were written with. When the shape of a record changes, a
+// migration is added to MIGRATIONS to upgrade older records, and logs
+// written by an older version are upgraded (into a new file, which then
+// replaces the old one) the next time they're opened.
+//
+// Each record is written with a single write and flushed to disk before we
+// carry on. If the program dies part way through a write, the file ends with
+// a partial line without a newline. That line never took effect, so when
+// the log is next opened it is cut off and the rest is loaded as normal. Any
+// other line that can't be read means the file has been damaged, and is
+// reported rather than skipped.
+//
+// Only one Store can have a log open at a time. Each program replays the
+// log once, when it opens it, and from then on only knows about its own
+// changes, so two of them appending to the same file would each miss what
+// the other wrote. Opening a log takes an exclusive lock on a file next to
+// it (restaurant.log.lock for restaurant.log), which is let go when the
+// Store is dropped or the program exits.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::clock::Time;
+use crate::front_of_house::billing::{
+    Bill, BillingConfig, BillingError, Charges, TaxRule, Tender, TenderReceipt,
+};
+use crate::front_of_house::hosting::{
+    self, Floor, Party, PartyId, ReservationBook, ReservationError, ReservationId, Seating,
+    SeatingError, TableTurnover, Waitlist,
+};
+use crate::front_of_house::pricing::PricingRules;
+use crate::front_of_house::serving::{self, OrderBook, OrderError, OrderId, OrderItem, OrderStatus};
+use crate::money::Money;
+
+// Upgrades a record from one schema version to the next. MIGRATIONS[0]
+// takes a record from version 1 to 2, MIGRATIONS[1] from 2 to 3 and so on.
+type Migration = fn(&mut Value);
+
+const MIGRATIONS: &[Migration] = &[
+    parties_have_allergies,
+    unchanged,
+    payments_have_charges,
+    bookings_have_times,
+];
+
+// Version 2: parties are asked about allergies, so every party has a list of
+// them. Nobody was asked before, so older parties get an empty list.
+fn parties_have_allergies(record: &mut Value) {
+    let kind = record["type"].as_str();
+    if kind != Some("party_joined") && kind != Some("booked") {
+        return;
+    }
+    if let Some(party) = record["party"].as_object_mut() {
+        party.entry("allergies").or_insert_with(|| Value::Array(Vec::new()));
+    }
+}
+
+// Version 3: vouchers and loyalty cards are recorded against bills. Older
+// records don't change, but the new kinds of record mean an older program
+// has to be told the log is too new for it, rather than that it's damaged.
+fn unchanged(_: &mut Value) {}
+
+// Version 4: payments save the charges the bill was worked out to have when
+// it was paid. Older payments didn't, so they're left to be worked out with
+// whatever the pricing rules are when the log is read.
+fn payments_have_charges(record: &mut Value) {
+    if record["type"].as_str() == Some("paid") {
+        if let Some(paid) = record.as_object_mut() {
+            paid.entry("charges").or_insert(Value::Null);
+        }
+    }
+}
+
+// Version 5: bookings save when they were made or changed, so that they can't
+// be for a time which has already gone. Older bookings weren't checked.
+fn bookings_have_times(record: &mut Value) {
+    let kind = record["type"].as_str();
+    if kind == Some("booked") || kind == Some("booking_changed") {
+        if let Some(booking) = record.as_object_mut() {
+            booking.entry("at").or_insert(Value::Null);
+        }
+    }
+}
+
+pub const CURRENT_SCHEMA: u32 = MIGRATIONS.len() as u32 + 1;
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Header {
+    schema: u32,
+}
+
+// One change to the restaurant. Records are stored with a "type" field
+// naming the variant, e.g. {"type":"party_joined","party":{...}}
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Record {
+    PartyJoined { party: Party },
+    PartyLeft { party: PartyId },
+    NoShow { party: PartyId },
+    Seated { at: Time },
+    TableCleared { party: PartyId, at: Time },
+    // `at` is when the booking was made or changed. Bookings saved before it
+    // was kept have none, and aren't checked against it.
+    Booked { party: Party, start: Time, duration: u32, at: Option<Time> },
+    BookingChanged { reservation: ReservationId, size: u32, start: Time, duration: u32, at: Option<Time> },
+    BookingCancelled { reservation: ReservationId },
+    CheckedIn { reservation: ReservationId, at: Time },
+    OrderOpened { party: PartyId, items: Vec<OrderItem>, at: Time },
+    ItemAdded { order: OrderId, item: OrderItem },
+    ItemRemoved { order: OrderId, index: usize },
+    OrderMoved { order: OrderId, to: OrderStatus, at: Time },
+    TipSet { order: OrderId, tip: Money },
+    VoucherRedeemed { order: OrderId, code: String },
+    LoyaltyShown { order: OrderId, tier: String },
+    // `charges` are the bill's discounts, taxes and service charge when it
+    // was paid, so that changing pricing.json doesn't change a bill that's
+    // already been paid. Payments saved before these were kept have none.
+    Paid { order: OrderId, tender: Tender, charges: Option<Charges> },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StoreError {
+    Io(String),
+    // A line other than a half-written last one couldn't be read or replayed
+    Corrupt { line: usize, message: String },
+    // The log was written by a newer version of the program
+    UnsupportedSchema { found: u32, supported: u32 },
+    // Another program (the CLI or the server) has the log open
+    Locked(String),
+    UnknownParty(PartyId),
+    // The party isn't on the waitlist (they may have been seated already)
+    NotWaiting(PartyId),
+    Seating(SeatingError),
+    Reservation(ReservationError),
+    Order(OrderError),
+    Billing(BillingError),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoreError::Io(message) => write!(f, "could not read or write the log: {}", message),
+            StoreError::Corrupt { line, message } => {
+                write!(f, "line {} of the log is damaged: {}", line, message)
+            }
+            StoreError::UnsupportedSchema { found, supported } => write!(
+                f,
+                "the log uses schema version {}, but only versions up to {} are understood",
+                found, supported
+            ),
+            StoreError::Locked(path) => write!(
+                f,
+                "{} is already open in another program (the CLI and the server can't share a log)",
+                path
+            ),
+            StoreError::UnknownParty(id) => write!(f, "party {} isn't sat at a table", id.0),
+            StoreError::NotWaiting(id) => write!(f, "party {} isn't on the waitlist", id.0),
+            StoreError::Seating(e) => write!(f, "{}", e),
+            StoreError::Reservation(e) => write!(f, "{}", e),
+            StoreError::Order(e) => write!(f, "{}", e),
+            StoreError::Billing(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for StoreError {}
+
+impl From<SeatingError> for StoreError {
+    fn from(e: SeatingError) -> StoreError {
+        StoreError::Seating(e)
+    }
+}
+
+impl From<ReservationError> for StoreError {
+    fn from(e: ReservationError) -> StoreError {
+        StoreError::Reservation(e)
+    }
+}
+
+impl From<OrderError> for StoreError {
+    fn from(e: OrderError) -> StoreError {
+        StoreError::Order(e)
+    }
+}
+
+impl From<BillingError> for StoreError {
+    fn from(e: BillingError) -> StoreError {
+        StoreError::Billing(e)
+    }
+}
+
+fn io_error(path: &Path, e: io::Error) -> StoreError {
+    StoreError::Io(format!("{}: {}", path.display(), e))
+}
+
+// What applying a record produced, for the Store methods to hand back
+enum Applied {
+    Nothing,
+    Party(PartyId),
+    Seating(Seating),
+    Reservation(ReservationId),
+    Order(OrderId),
+    Receipt(TenderReceipt),
+}
+
+// Everything the log keeps track of. The floor plan and billing rules are
+// set up by the program, rather than stored, so they're given to State::new.
+#[derive(Debug, Clone)]
+pub struct State {
+    floor: Floor,
+    waitlist: Waitlist,
+    reservations: ReservationBook,
+    seatings: Vec<Seating>,
+    orders: OrderBook,
+    billing: BillingConfig,
+    // Bills which have had a tip, voucher, loyalty card or payment recorded
+    // against them. They're worked out again whenever their order changes.
+    bills: BTreeMap<OrderId, Bill>,
+}
+
+impl State {
+    pub fn new(floor: Floor, turnover: TableTurnover, billing: BillingConfig) -> State {
+        State {
+            floor,
+            waitlist: Waitlist::new(turnover),
+            reservations: ReservationBook::new(),
+            seatings: Vec::new(),
+            orders: OrderBook::new(),
+            billing,
+            bills: BTreeMap::new(),
+        }
+    }
+
+    // The restaurant's own floor plan and billing rules: three tables for
+    // two (the first three can be pushed together), two for four and one for
+    // six, with 8% sales tax, a 12.5% service charge and the discounts in
+    // pricing.json
+    pub fn house() -> State {
+        let mut floor = Floor::new();
+        let tables: Vec<_> = [2, 2, 2, 4, 4, 6].iter().map(|&c| floor.add_table(c)).collect();
+        floor.join(tables[0], tables[1]).expect("both tables were just added");
+        floor.join(tables[1], tables[2]).expect("both tables were just added");
+        let billing = BillingConfig::new()
+            .tax(TaxRule::new("Sales tax", 800))
+            .service_charge(1250)
+            .pricing(PricingRules::house());
+        State::new(floor, TableTurnover::new(tables.len() as u32, 60), billing)
+    }
+
+    pub fn floor(&self) -> &Floor {
+        &self.floor
+    }
+
+    pub fn waitlist(&self) -> &Waitlist {
+        &self.waitlist
+    }
+
+    pub fn reservations(&self) -> &ReservationBook {
+        &self.reservations
+    }
+
+    // Parties sat at a table right now
+    pub fn seatings(&self) -> &[Seating] {
+        &self.seatings
+    }
+
+    pub fn orders(&self) -> &OrderBook {
+        &self.orders
+    }
+
+    pub fn billing(&self) -> &BillingConfig {
+        &self.billing
+    }
+
+    // The bill for an order, including any tip and payments so far
+    pub fn bill(&self, order: OrderId) -> Result<Bill, StoreError> {
+        match self.bills.get(&order) {
+            Some(bill) => Ok(bill.clone()),
+            None => Ok(Bill::for_order(self.orders.get(order)?, &self.billing)),
+        }
+    }
+
+    fn bill_mut(&mut self, order: OrderId) -> Result<&mut Bill, StoreError> {
+        if !self.bills.contains_key(&order) {
+            let bill = Bill::for_order(self.orders.get(order)?, &self.billing);
+            self.bills.insert(order, bill);
+        }
+        Ok(self.bills.get_mut(&order).expect("the bill was just added"))
+    }
+
+    // Works an order's bill out again after its items change, keeping the
+    // tip, vouchers and loyalty card. Orders can't change once they've been
+    // served, so there are never any payments to carry over.
+    fn refresh_bill(&mut self, order: OrderId) -> Result<(), StoreError> {
+        let Some(old) = self.bills.get(&order) else {
+            return Ok(());
+        };
+        let mut bill = Bill::for_order(self.orders.get(order)?, &self.billing);
+        bill.set_tip(old.tip())?;
+        for code in old.vouchers() {
+            bill.redeem_voucher(code)?;
+        }
+        if let Some(tier) = old.loyalty() {
+            bill.set_loyalty(tier)?;
+        }
+        self.bills.insert(order, bill);
+        Ok(())
+    }
+
+    // Makes the change a record describes. This is the only place state is
+    // changed, whether the record is new or being replayed from the log.
+    fn apply(&mut self, record: &Record) -> Result<Applied, StoreError> {
+        match record {
+            Record::PartyJoined { party } => {
+                Ok(Applied::Party(hosting::add_to_waitlist(&mut self.waitlist, party.clone())))
+            }
+            Record::PartyLeft { party } => {
+                self.waitlist.remove(*party).ok_or(StoreError::NotWaiting(*party))?;
+                Ok(Applied::Nothing)
+            }
+            Record::NoShow { party } => {
+                self.waitlist.mark_no_show(*party).ok_or(StoreError::NotWaiting(*party))?;
+                Ok(Applied::Nothing)
+            }
+            Record::Seated { at } => {
+                let seating = hosting::seat_at_table(
+                    &mut self.waitlist,
+                    &mut self.reservations,
+                    &mut self.floor,
+                    *at,
+                )?;
+                self.seatings.push(seating.clone());
+                Ok(Applied::Seating(seating))
+            }
+            Record::TableCleared { party, at } => {
+                let index = self
+                    .seatings
+                    .iter()
+                    .position(|s| s.id == *party)
+                    .ok_or(StoreError::UnknownParty(*party))?;
+                let seating = self.seatings.remove(index);
+                hosting::clear_table(&mut self.waitlist, &mut self.floor, &seating, *at);
+                Ok(Applied::Nothing)
+            }
+            Record::Booked { party, start, duration, at } => {
+                let now = at.unwrap_or(*start);
+                let id = self.reservations.book(&self.floor, party.clone(), *start, *duration, now)?;
+                Ok(Applied::Reservation(id))
+            }
+            Record::BookingChanged { reservation, size, start, duration, at } => {
+                let now = at.unwrap_or(*start);
+                self.reservations
+                    .modify(&self.floor, *reservation, *size, *start, *duration, now)?;
+                Ok(Applied::Nothing)
+            }
+            Record::BookingCancelled { reservation } => {
+                self.reservations.cancel(*reservation)?;
+                Ok(Applied::Nothing)
+            }
+            Record::CheckedIn { reservation, at } => {
+                let party = self.reservations.check_in(&mut self.waitlist, *reservation, *at)?;
+                Ok(Applied::Party(party))
+            }
+            Record::OrderOpened { party, items, at } => {
+                // Only parties sat at a table can order
+                if !self.seatings.iter().any(|s| s.id == *party) {
+                    return Err(StoreError::UnknownParty(*party));
+                }
+                let id = serving::take_order(&mut self.orders, *party, items.clone(), *at)?;
+                Ok(Applied::Order(id))
+            }
+            Record::ItemAdded { order, item } => {
+                self.orders.get_mut(*order)?.add_item(item.clone())?;
+                self.refresh_bill(*order)?;
+                Ok(Applied::Nothing)
+            }
+            Record::ItemRemoved { order, index } => {
+                self.orders.get_mut(*order)?.remove_item(*index)?;
+                self.refresh_bill(*order)?;
+                Ok(Applied::Nothing)
+            }
+            Record::OrderMoved { order, to, at } => {
+                let order = self.orders.get_mut(*order)?;
+                match to {
+                    OrderStatus::Placed => {
+                        return Err(StoreError::Order(OrderError::IllegalTransition {
+                            order: order.id,
+                            from: order.status(),
+                            to: *to,
+                        }))
+                    }
+                    OrderStatus::SentToKitchen => order.send_to_kitchen(*at)?,
+                    OrderStatus::Cooking => order.start_cooking(*at)?,
+                    OrderStatus::Ready => order.mark_ready(*at)?,
+                    OrderStatus::Served => order.serve(*at)?,
+                    OrderStatus::Paid => order.pay(*at)?,
+                    OrderStatus::Cancelled => order.cancel(*at)?,
+                    OrderStatus::Voided => order.void(*at)?,
+                }
+                Ok(Applied::Nothing)
+            }
+            Record::TipSet { order, tip } => {
+                self.bill_mut(*order)?.set_tip(*tip)?;
+                Ok(Applied::Nothing)
+            }
+            Record::VoucherRedeemed { order, code } => {
+                self.bill_mut(*order)?.redeem_voucher(code)?;
+                Ok(Applied::Nothing)
+            }
+            Record::LoyaltyShown { order, tier } => {
+                self.bill_mut(*order)?.set_loyalty(tier)?;
+                Ok(Applied::Nothing)
+            }
+            Record::Paid { order, tender, charges } => {
+                self.bill_mut(*order)?;
+                let bill = self.bills.get_mut(order).expect("bill_mut adds the bill");
+                if let Some(charges) = charges {
+                    bill.restore_charges(charges.clone());
+                }
+                let receipt = serving::take_payment(&mut self.orders, bill, *tender)?;
+                Ok(Applied::Receipt(receipt))
+            }
+        }
+    }
+}
+
+// Reads the log at `path` into `state`, returning how many bytes of a
+// half-written last line were found (and should be cut off), and whether
+// the records had to be migrated from an older schema
+fn replay(path: &Path, bytes: &[u8], state: &mut State) -> Result<(Vec<Record>, usize, bool), StoreError> {
+    let mut lines = Vec::new();
+    let mut start = 0;
+    while let Some(length) = bytes[start..].iter().position(|&b| b == b'\n') {
+        lines.push(&bytes[start..start + length]);
+        start += length + 1;
+    }
+    let torn = bytes.len() - start;
+
+    let corrupt = |line: usize, message: String| StoreError::Corrupt { line, message };
+    let Some((header, lines)) = lines.split_first() else {
+        return Ok((Vec::new(), torn, false));
+    };
+    let header: Header = serde_json::from_slice(header).map_err(|e| corrupt(1, e.to_string()))?;
+    if header.schema == 0 || header.schema > CURRENT_SCHEMA {
+        return Err(StoreError::UnsupportedSchema {
+            found: header.schema,
+            supported: CURRENT_SCHEMA,
+        });
+    }
+
+    let mut records = Vec::with_capacity(lines.len());
+    for (i, line) in lines.iter().enumerate() {
+        let number = i + 2;
+        let mut value: Value =
+            serde_json::from_slice(line).map_err(|e| corrupt(number, e.to_string()))?;
+        for migrate in &MIGRATIONS[header.schema as usize - 1..] {
+            migrate(&mut value);
+        }
+        let record: Record =
+            serde_json::from_value(value).map_err(|e| corrupt(number, e.to_string()))?;
+        state
+            .apply(&record)
+            .map_err(|e| corrupt(number, format!("{} ({})", e, path.display())))?;
+        records.push(record);
+    }
+    Ok((records, torn, header.schema < CURRENT_SCHEMA))
+}
+
+fn header_line() -> String {
+    let header = serde_json::to_string(&Header { schema: CURRENT_SCHEMA })
+        .expect("the header is always valid JSON");
+    header + "\n"
+}
+
+// Writes a whole log to a new file, then swaps it in place of the old one,
+// so there's never a moment where neither file is complete
+fn rewrite(path: &Path, records: &[Record]) -> Result<(), StoreError> {
+    let temporary = path.with_extension("rewrite");
+    let mut text = header_line();
+    for record in records {
+        text += &serde_json::to_string(record).map_err(|e| StoreError::Io(e.to_string()))?;
+        text.push('\n');
+    }
+    let mut file = File::create(&temporary).map_err(|e| io_error(&temporary, e))?;
+    file.write_all(text.as_bytes()).map_err(|e| io_error(&temporary, e))?;
+    file.sync_all().map_err(|e| io_error(&temporary, e))?;
+    fs::rename(&temporary, path).map_err(|e| io_error(path, e))
+}
+
+// Takes the lock that keeps a second Store from opening the same log
+fn lock(path: &Path) -> Result<File, StoreError> {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".lock");
+    let lock_path = PathBuf::from(name);
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(&lock_path)
+        .map_err(|e| io_error(&lock_path, e))?;
+    match file.try_lock() {
+        Ok(()) => Ok(file),
+        Err(fs::TryLockError::WouldBlock) => Err(StoreError::Locked(path.display().to_string())),
+        Err(fs::TryLockError::Error(e)) => Err(io_error(&lock_path, e)),
+    }
+}
+
+pub struct Store {
+    path: PathBuf,
+    file: File,
+    // Held for as long as the Store is open; see lock
+    _lock: File,
+    state: State,
+    recovered_bytes: usize,
+}
+
+impl Store {
+    // Opens the log at `path`, creating it if it doesn't exist yet, and
+    // replays it on top of `state` (which should be freshly made)
+    pub fn open(path: &Path, mut state: State) -> Result<Store, StoreError> {
+        let lock = lock(path)?;
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(io_error(path, e)),
+        };
+        let (records, torn, migrated) = replay(path, &bytes, &mut state)?;
+
+        if bytes.len() == torn || migrated {
+            // A new log, one which crashed before its header was written, or
+            // one written with an older schema
+            rewrite(path, &records)?;
+        } else if torn > 0 {
+            let file = OpenOptions::new().write(true).open(path).map_err(|e| io_error(path, e))?;
+            file.set_len((bytes.len() - torn) as u64)
+                .and_then(|_| file.sync_all())
+                .map_err(|e| io_error(path, e))?;
+        }
+
+        let file = OpenOptions::new().append(true).open(path).map_err(|e| io_error(path, e))?;
+        Ok(Store {
+            path: path.to_path_buf(),
+            file,
+            _lock: lock,
+            state,
+            recovered_bytes: torn,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn state(&self) -> &State {
+        &self.state
+    }
+
+    // How many bytes of a half-written record were cut off the end of the
+    // log when it was opened. Zero unless the last run crashed mid-write.
+    pub fn recovered_bytes(&self) -> usize {
+        self.recovered_bytes
+    }
+
+    // Checks and makes a change, then writes it to the log. If writing fails
+    // the change has still been made in memory, so the store should be
+    // reopened to get back in step with the file.
+    fn record(&mut self, record: Record) -> Result<Applied, StoreError> {
+        let applied = self.state.apply(&record)?;
+        let mut line = serde_json::to_string(&record).map_err(|e| StoreError::Io(e.to_string()))?;
+        line.push('\n');
+        self.file
+            .write_all(line.as_bytes())
+            .and_then(|_| self.file.sync_data())
+            .map_err(|e| io_error(&self.path, e))?;
+        Ok(applied)
+    }
+
+    pub fn add_party(&mut self, party: Party) -> Result<PartyId, StoreError> {
+        match self.record(Record::PartyJoined { party })? {
+            Applied::Party(id) => Ok(id),
+            _ => unreachable!("adding a party always gives a party id"),
+        }
+    }
+
+    pub fn remove_party(&mut self, party: PartyId) -> Result<(), StoreError> {
+        self.record(Record::PartyLeft { party }).map(|_| ())
+    }
+
+    pub fn no_show(&mut self, party: PartyId) -> Result<(), StoreError> {
+        self.record(Record::NoShow { party }).map(|_| ())
+    }
+
+    pub fn seat(&mut self, at: Time) -> Result<Seating, StoreError> {
+        match self.record(Record::Seated { at })? {
+            Applied::Seating(seating) => Ok(seating),
+            _ => unreachable!("seating a party always gives a seating"),
+        }
+    }
+
+    pub fn clear_table(&mut self, party: PartyId, at: Time) -> Result<(), StoreError> {
+        self.record(Record::TableCleared { party, at }).map(|_| ())
+    }
+
+    pub fn book(
+        &mut self,
+        party: Party,
+        start: Time,
+        duration: u32,
+        at: Time,
+    ) -> Result<ReservationId, StoreError> {
+        match self.record(Record::Booked { party, start, duration, at: Some(at) })? {
+            Applied::Reservation(id) => Ok(id),
+            _ => unreachable!("booking always gives a reservation id"),
+        }
+    }
+
+    pub fn change_booking(
+        &mut self,
+        reservation: ReservationId,
+        size: u32,
+        start: Time,
+        duration: u32,
+        at: Time,
+    ) -> Result<(), StoreError> {
+        self.record(Record::BookingChanged { reservation, size, start, duration, at: Some(at) })
+            .map(|_| ())
+    }
+
+    pub fn cancel_booking(&mut self, reservation: ReservationId) -> Result<(), StoreError> {
+        self.record(Record::BookingCancelled { reservation }).map(|_| ())
+    }
+
+    pub fn check_in(&mut self, reservation: ReservationId, at: Time) -> Result<PartyId, StoreError> {
+        match self.record(Record::CheckedIn { reservation, at })? {
+            Applied::Party(id) => Ok(id),
+            _ => unreachable!("checking in always gives a party id"),
+        }
+    }
+
+    pub fn open_order(
+        &mut self,
+        party: PartyId,
+        items: Vec<OrderItem>,
+        at: Time,
+    ) -> Result<OrderId, StoreError> {
+        match self.record(Record::OrderOpened { party, items, at })? {
+            Applied::Order(id) => Ok(id),
+            _ => unreachable!("opening an order always gives an order id"),
+        }
+    }
+
+    pub fn add_item(&mut self, order: OrderId, item: OrderItem) -> Result<(), StoreError> {
+        self.record(Record::ItemAdded { order, item }).map(|_| ())
+    }
+
+    pub fn remove_item(&mut self, order: OrderId, index: usize) -> Result<(), StoreError> {
+        self.record(Record::ItemRemoved { order, index }).map(|_| ())
+    }
+
+    // Moves an order on to its next status (or cancels or voids it)
+    pub fn move_order(&mut self, order: OrderId, to: OrderStatus, at: Time) -> Result<(), StoreError> {
+        self.record(Record::OrderMoved { order, to, at }).map(|_| ())
+    }
+
+    pub fn set_tip(&mut self, order: OrderId, tip: Money) -> Result<(), StoreError> {
+        self.record(Record::TipSet { order, tip }).map(|_| ())
+    }
+
+    pub fn redeem_voucher(&mut self, order: OrderId, code: &str) -> Result<(), StoreError> {
+        let code = String::from(code);
+        self.record(Record::VoucherRedeemed { order, code }).map(|_| ())
+    }
+
+    pub fn set_loyalty(&mut self, order: OrderId, tier: &str) -> Result<(), StoreError> {
+        let tier = String::from(tier);
+        self.record(Record::LoyaltyShown { order, tier }).map(|_| ())
+    }
+
+    pub fn pay(&mut self, order: OrderId, tender: Tender) -> Result<TenderReceipt, StoreError> {
+        let charges = Some(self.state.bill(order)?.charges());
+        match self.record(Record::Paid { order, tender, charges })? {
+            Applied::Receipt(receipt) => Ok(receipt),
+            _ => unreachable!("paying always gives a receipt"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::back_of_house::menu::Menu;
+    use crate::front_of_house::billing::TenderKind;
+
+    // A log file in the temporary directory, named after the test so tests
+    // running at the same time don't share one
+    fn log_path(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("restaurant-{}-{}.log", name, std::process::id()));
+        remove_log(&path);
+        path
+    }
+
+    fn remove_log(path: &Path) {
+        let _ = fs::remove_file(path);
+        let _ = fs::remove_file(format!("{}.lock", path.display()));
+    }
+
+    // Opens an order of two soups for a party, and serves it
+    fn served_order(store: &mut Store) -> OrderId {
+        let party = store.add_party(Party::new("Okafor", 2, Time::hm(12, 0))).unwrap();
+        store.seat(Time::hm(12, 5)).unwrap();
+        let soup = OrderItem::from_menu(&Menu::house(), "soup", &[], 2).unwrap();
+        let order = store.open_order(party, vec![soup], Time::hm(12, 10)).unwrap();
+        for (to, at) in [
+            (OrderStatus::SentToKitchen, Time::hm(12, 11)),
+            (OrderStatus::Cooking, Time::hm(12, 12)),
+            (OrderStatus::Ready, Time::hm(12, 20)),
+            (OrderStatus::Served, Time::hm(12, 22)),
+        ] {
+            store.move_order(order, to, at).unwrap();
+        }
+        order
+    }
+
+    // The house rules, but with the friends and family voucher worth 50%
+    // rather than 15%, as if pricing.json had been edited
+    fn repriced_house() -> State {
+        let text = include_str!("../pricing.json").replace(
+            "\"code\": \"FRIENDS\",\n      \"percent\": \"15\"",
+            "\"code\": \"FRIENDS\",\n      \"percent\": \"50\"",
+        );
+        let mut state = State::house();
+        state.billing = state.billing.clone().pricing(PricingRules::from_json(&text).unwrap());
+        state
+    }
+
+    #[test]
+    fn a_half_written_record_is_cut_off() {
+        let path = log_path("torn");
+        let mut store = Store::open(&path, State::house()).unwrap();
+        store.add_party(Party::new("Lee", 2, Time::hm(18, 0))).unwrap();
+        store.add_party(Party::new("Diaz", 4, Time::hm(18, 5))).unwrap();
+        drop(store);
+
+        // Crash part way through writing the second party
+        let bytes = fs::read(&path).unwrap();
+        let whole = bytes.len() - 1 - bytes[..bytes.len() - 1].iter().rev().position(|&b| b == b'\n').unwrap();
+        fs::write(&path, &bytes[..bytes.len() - 20]).unwrap();
+
+        let mut store = Store::open(&path, State::house()).unwrap();
+        assert_eq!(store.recovered_bytes(), bytes.len() - 20 - whole);
+        assert_eq!(store.state().waitlist().len(), 1);
+        assert_eq!(fs::read(&path).unwrap(), &bytes[..whole]);
+
+        // The log carries on from the last whole record
+        store.add_party(Party::new("Diaz", 4, Time::hm(18, 5))).unwrap();
+        drop(store);
+        let store = Store::open(&path, State::house()).unwrap();
+        assert_eq!(store.recovered_bytes(), 0);
+        assert_eq!(store.state().waitlist().len(), 2);
+        drop(store);
+        remove_log(&path);
+    }
+
+    #[test]
+    fn a_crash_before_the_header_leaves_an_empty_log() {
+        let path = log_path("torn-header");
+        fs::write(&path, "{\"sche").unwrap();
+
+        let store = Store::open(&path, State::house()).unwrap();
+        assert_eq!(store.recovered_bytes(), 6);
+        assert!(store.state().waitlist().is_empty());
+        assert_eq!(fs::read_to_string(&path).unwrap(), header_line());
+        drop(store);
+        remove_log(&path);
+    }
+
+    #[test]
+    fn damage_before_the_last_line_is_reported() {
+        let path = log_path("damaged");
+        let mut store = Store::open(&path, State::house()).unwrap();
+        store.add_party(Party::new("Lee", 2, Time::hm(18, 0))).unwrap();
+        store.add_party(Party::new("Diaz", 4, Time::hm(18, 5))).unwrap();
+        drop(store);
+
+        let text = fs::read_to_string(&path).unwrap().replacen("Lee", "Lee\n", 1);
+        fs::write(&path, text).unwrap();
+        match Store::open(&path, State::house()) {
+            Err(StoreError::Corrupt { line, .. }) => assert_eq!(line, 2),
+            other => panic!("expected a damaged log, got {:?}", other.map(|_| ())),
+        }
+        remove_log(&path);
+    }
+
+    #[test]
+    fn bills_keep_up_with_their_order() {
+        let path = log_path("bill-refresh");
+        let mut store = Store::open(&path, State::house()).unwrap();
+        let party = store.add_party(Party::new("Okafor", 2, Time::hm(12, 0))).unwrap();
+        store.seat(Time::hm(12, 5)).unwrap();
+        let order = store.open_order(party, Vec::new(), Time::hm(12, 10)).unwrap();
+        store.set_tip(order, Money::from_cents(300)).unwrap();
+        store.redeem_voucher(order, "FRIENDS").unwrap();
+
+        let soup = OrderItem::from_menu(&Menu::house(), "soup", &[], 2).unwrap();
+        store.add_item(order, soup).unwrap();
+        let bill = store.state().bill(order).unwrap();
+        assert_eq!(bill.lines().len(), 1);
+        assert_eq!(bill.tip(), Money::from_cents(300));
+        assert_eq!(bill.vouchers(), ["FRIENDS"]);
+        assert_eq!(bill.discounts().len(), 1);
+
+        store.remove_item(order, 0).unwrap();
+        assert_eq!(store.state().bill(order).unwrap().subtotal(), Money::ZERO);
+        drop(store);
+        remove_log(&path);
+    }
+
+    #[test]
+    fn negative_tips_are_refused() {
+        let path = log_path("negative-tip");
+        let mut store = Store::open(&path, State::house()).unwrap();
+        let order = served_order(&mut store);
+        let records = fs::read_to_string(&path).unwrap().lines().count();
+        let error = store.set_tip(order, Money::from_cents(-100)).unwrap_err();
+        assert_eq!(error.to_string(), "a tip of -1.00 can't be negative");
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), records);
+        drop(store);
+
+        // Nor are they taken from a log that has been edited by hand
+        let tip = format!("{{\"type\":\"tip_set\",\"order\":{},\"tip\":-100}}\n", order.0);
+        fs::write(&path, fs::read_to_string(&path).unwrap() + &tip).unwrap();
+        match Store::open(&path, State::house()) {
+            Err(StoreError::Corrupt { line, .. }) => assert_eq!(line, records + 1),
+            other => panic!("expected a damaged log, got {:?}", other.map(|_| ())),
+        }
+        remove_log(&path);
+    }
+
+    #[test]
+    fn paid_bills_cant_be_tipped() {
+        let path = log_path("tip-after-paying");
+        let mut store = Store::open(&path, State::house()).unwrap();
+        let order = served_order(&mut store);
+        let balance = store.state().bill(order).unwrap().balance();
+        store.pay(order, Tender::new(TenderKind::Card, balance, Time::hm(12, 40))).unwrap();
+        let records = fs::read_to_string(&path).unwrap().lines().count();
+
+        let error = store.set_tip(order, Money::from_cents(2000)).unwrap_err();
+        assert_eq!(error.to_string(), "the bill has already been paid");
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), records);
+        let bill = store.state().bill(order).unwrap();
+        assert_eq!(bill.tip(), Money::ZERO);
+        assert_eq!(bill.balance(), Money::ZERO);
+        drop(store);
+        remove_log(&path);
+    }
+
+    #[test]
+    fn paid_bills_keep_the_prices_they_were_paid_at() {
+        let path = log_path("repriced");
+        let mut store = Store::open(&path, State::house()).unwrap();
+        let order = served_order(&mut store);
+        store.redeem_voucher(order, "FRIENDS").unwrap();
+        let paid = store.state().bill(order).unwrap();
+        store
+            .pay(order, Tender::new(TenderKind::Card, paid.total(), Time::hm(12, 40)))
+            .unwrap();
+        drop(store);
+
+        // A bigger discount would leave the card payment more than was owed
+        assert_ne!(repriced_house().billing(), State::house().billing());
+        let store = Store::open(&path, repriced_house()).unwrap();
+        let bill = store.state().bill(order).unwrap();
+        assert_eq!(bill.total(), paid.total());
+        assert_eq!(bill.discounts(), paid.discounts());
+        assert!(bill.is_settled());
+        assert_eq!(store.state().orders().get(order).unwrap().status(), OrderStatus::Paid);
+        drop(store);
+        remove_log(&path);
+    }
+
+    #[test]
+    fn version_3_payments_are_priced_with_the_current_rules() {
+        let path = log_path("payments-migration");
+        let mut store = Store::open(&path, State::house()).unwrap();
+        let order = served_order(&mut store);
+        let total = store.state().bill(order).unwrap().total();
+        store.pay(order, Tender::new(TenderKind::Cash, total, Time::hm(12, 40))).unwrap();
+        drop(store);
+
+        // Write the log out as version 3 would have, without the charges
+        let text = fs::read_to_string(&path).unwrap();
+        let mut lines: Vec<String> = text.lines().map(String::from).collect();
+        lines[0] = String::from("{\"schema\":3}");
+        let mut paid: Value = serde_json::from_str(lines.last().unwrap()).unwrap();
+        paid.as_object_mut().unwrap().remove("charges");
+        *lines.last_mut().unwrap() = paid.to_string();
+        fs::write(&path, lines.join("\n") + "\n").unwrap();
+
+        let store = Store::open(&path, State::house()).unwrap();
+        assert!(store.state().bill(order).unwrap().is_settled());
+        drop(store);
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with(&header_line()));
+        assert!(text.lines().last().unwrap().contains("\"charges\":null"));
+        remove_log(&path);
+    }
+
+    #[test]
+    fn version_1_parties_get_no_allergies() {
+        let path = log_path("allergies-migration");
+        fs::write(
+            &path,
+            concat!(
+                "{\"schema\":1}\n",
+                "{\"type\":\"party_joined\",\"party\":{\"name\":\"Lee\",\"size\":2,",
+                "\"arrived\":1080,\"phone\":null,\"notes\":null,\"priority\":\"Normal\"}}\n",
+            ),
+        )
+        .unwrap();
+
+        let store = Store::open(&path, State::house()).unwrap();
+        let entry = store.state().waitlist().next().unwrap();
+        assert_eq!(entry.party.name, "Lee");
+        assert!(entry.party.allergies.is_empty());
+        drop(store);
+
+        // The log is rewritten with the current schema, so the migration
+        // isn't needed the next time it's opened
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with(&header_line()));
+        assert!(text.contains("\"allergies\":[]"));
+        remove_log(&path);
+    }
+
+    #[test]
+    fn version_4_bookings_arent_checked_against_the_time() {
+        let path = log_path("bookings-migration");
+        fs::write(
+            &path,
+            concat!(
+                "{\"schema\":4}\n",
+                "{\"type\":\"booked\",\"party\":{\"name\":\"Lee\",\"size\":2,\"arrived\":1080,",
+                "\"phone\":null,\"notes\":null,\"priority\":\"Normal\",\"allergies\":[]},",
+                "\"start\":1140,\"duration\":90}\n",
+            ),
+        )
+        .unwrap();
+
+        let mut store = Store::open(&path, State::house()).unwrap();
+        assert_eq!(store.state().reservations().iter().count(), 1);
+
+        // New bookings are, though, and a refused one isn't saved
+        let party = Party::new("Kim", 2, Time::hm(19, 0));
+        assert!(store.book(party, Time::hm(18, 0), 90, Time::hm(19, 0)).is_err());
+        drop(store);
+
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with(&header_line()));
+        assert_eq!(text.lines().count(), 2);
+        assert!(text.contains("\"at\":null"));
+        remove_log(&path);
+    }
+
+    #[test]
+    fn logs_from_a_newer_version_are_refused() {
+        let path = log_path("newer-schema");
+        let header = format!("{{\"schema\":{}}}\n", CURRENT_SCHEMA + 1);
+        fs::write(&path, header + "{\"type\":\"something_new\"}\n").unwrap();
+
+        let error = Store::open(&path, State::house()).err().unwrap();
+        assert_eq!(
+            error,
+            StoreError::UnsupportedSchema {
+                found: CURRENT_SCHEMA + 1,
+                supported: CURRENT_SCHEMA,
+            }
+        );
+        remove_log(&path);
+    }
+}
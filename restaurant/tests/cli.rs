@@ -0,0 +1,60 @@
+// Runs the command line tool the way staff would, one command at a time
+// against the same log. Cargo builds the binary before running these tests
+// and tells us where it is through CARGO_BIN_EXE_<name>.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+fn restaurant(log: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_restaurant"))
+        .arg("--log")
+        .arg(log)
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn stdout(output: &Output) -> String {
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+// The word that comes after `before`, such as the id in "as party 3,"
+fn id_from(text: &str, before: &str) -> String {
+    let (_, rest) = text.split_once(before).unwrap();
+    rest.split([' ', ',']).next().unwrap().to_string()
+}
+
+fn log_path(name: &str) -> PathBuf {
+    let log = std::env::temp_dir().join(format!("restaurant-cli-{}-{}.log", name, std::process::id()));
+    let _ = fs::remove_file(&log);
+    log
+}
+
+#[test]
+fn allergies_hold_up_an_item_until_the_guest_confirms() {
+    let log = log_path("allergies");
+    let added = stdout(&restaurant(&log, &["--at", "19:00", "waitlist", "add", "Lee", "2", "--allergies", "milk"]));
+    let party = id_from(&added, "as party ");
+    stdout(&restaurant(&log, &["--at", "19:05", "seat"]));
+    let opened = stdout(&restaurant(&log, &["--at", "19:10", "order", "new", &party]));
+    let order = id_from(&opened, "Opened order ");
+
+    let refused = restaurant(&log, &["order", "add-item", &order, "soup"]);
+    assert_eq!(refused.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(refused.stderr).unwrap(),
+        "Error: Soup of the day contains milk, which Lee's party is allergic to. \
+         Change the item, or confirm with the guest first.\n"
+    );
+
+    let added = stdout(&restaurant(&log, &["order", "add-item", &order, "soup", "--confirm-allergy"]));
+    assert_eq!(added, "Added 1 x Soup of the day at 5.25 each\n");
+    // Nothing they're allergic to needs confirming
+    let added = stdout(&restaurant(&log, &["order", "add-item", &order, "salad", "Vinaigrette"]));
+    assert_eq!(added, "Added 1 x Garden salad at 6.00 each\n");
+
+    let _ = fs::remove_file(&log);
+    let _ = fs::remove_file(format!("{}.lock", log.display()));
+}
@@ -0,0 +1,233 @@
+// Drives the HTTP server over real connections, the way a tablet at the host
+// stand would. Each test starts its own server on a port the operating
+// system picks, with its own log, and tells it how many connections to
+// expect so it can be joined at the end.
+
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+
+use restaurant::menu::Menu;
+use restaurant::server::{Server, MAX_BODY, MAX_HEADERS};
+use restaurant::storage::{State, Store};
+
+struct TestServer {
+    addr: SocketAddr,
+    handle: thread::JoinHandle<()>,
+    log: PathBuf,
+}
+
+impl TestServer {
+    // Starts a server which handles `connections` connections, then stops
+    fn start(name: &str, menu: Menu, connections: usize) -> TestServer {
+        let log = std::env::temp_dir().join(format!("restaurant-server-{}-{}.log", name, std::process::id()));
+        let _ = fs::remove_file(&log);
+        let store = Store::open(&log, State::house()).unwrap();
+        let server = Server::bind("127.0.0.1:0", store, menu)
+            .unwrap()
+            .read_timeout(Duration::from_millis(500));
+        let addr = server.local_addr().unwrap();
+        let handle = thread::spawn(move || server.run_for(connections));
+        TestServer { addr, handle, log }
+    }
+
+    // Sends a whole request and reads the status and JSON body of the reply
+    fn request(&self, method: &str, path: &str, body: &str) -> (u16, Value) {
+        let mut stream = TcpStream::connect(self.addr).unwrap();
+        let request = format!(
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            body.len(),
+            body
+        );
+        stream.write_all(request.as_bytes()).unwrap();
+        response(stream)
+    }
+
+    fn stop(self) {
+        self.handle.join().unwrap();
+        let _ = fs::remove_file(&self.log);
+        let _ = fs::remove_file(format!("{}.lock", self.log.display()));
+    }
+}
+
+fn response(mut stream: TcpStream) -> (u16, Value) {
+    let mut text = String::new();
+    stream.read_to_string(&mut text).unwrap();
+    let status = text[9..12].parse().unwrap();
+    let (_, body) = text.split_once("\r\n\r\n").unwrap();
+    let body = if body.is_empty() { Value::Null } else { serde_json::from_str(body).unwrap() };
+    (status, body)
+}
+
+#[test]
+fn parties_join_and_leave_the_waitlist() {
+    let server = TestServer::start("waitlist", Menu::house(), 5);
+
+    let (status, body) = server.request("POST", "/waitlist", r#"{"name": "Patel", "size": 4, "at": "19:00"}"#);
+    assert_eq!(status, 201);
+    let id = body["id"].as_u64().unwrap();
+
+    let (status, body) = server.request("GET", "/waitlist", "");
+    assert_eq!(status, 200);
+    assert_eq!(body[0]["name"], "Patel");
+    assert_eq!(body[0]["size"], 4);
+
+    let (status, body) = server.request("DELETE", "/waitlist/99", "");
+    assert_eq!(status, 404);
+    assert_eq!(body["error"], "party 99 isn't on the waitlist");
+
+    let (status, body) = server.request("DELETE", &format!("/waitlist/{}", id), "");
+    assert_eq!((status, body), (204, Value::Null));
+
+    let (_, body) = server.request("GET", "/waitlist", "");
+    assert_eq!(body, Value::Array(Vec::new()));
+    server.stop();
+}
+
+#[test]
+fn an_order_is_billed_and_paid() {
+    let server = TestServer::start("order", Menu::house(), 10);
+
+    server.request("POST", "/waitlist", r#"{"name": "Okafor", "size": 2, "at": "12:00"}"#);
+    let (status, body) = server.request("POST", "/seatings", r#"{"at": "12:05"}"#);
+    assert_eq!(status, 201);
+    let party = body["party"].as_u64().unwrap();
+
+    let order = format!(r#"{{"party": {}, "at": "12:10", "items": [{{"menu_id": "soup", "quantity": 2}}]}}"#, party);
+    let (status, body) = server.request("POST", "/orders", &order);
+    assert_eq!(status, 201);
+    assert_eq!(body["items"][0]["quantity"], 2);
+    let path = format!("/orders/{}", body["id"]);
+
+    for (to, at) in [("SentToKitchen", "12:11"), ("Cooking", "12:12"), ("Ready", "12:20"), ("Served", "12:22")] {
+        let body = format!(r#"{{"to": "{}", "at": "{}"}}"#, to, at);
+        let (status, _) = server.request("POST", &format!("{}/status", path), &body);
+        assert_eq!(status, 200);
+    }
+
+    let (status, bill) = server.request("GET", &format!("{}/bill", path), "");
+    assert_eq!(status, 200);
+    let payment = format!(r#"{{"kind": "Card", "amount": {}, "at": "12:40"}}"#, bill["total"]);
+    let (status, receipt) = server.request("POST", &format!("{}/bill/payments", path), &payment);
+    assert_eq!(status, 201);
+    assert_eq!(receipt["balance"], "0.00");
+
+    // Once it's paid, more can't be taken
+    let (status, _) = server.request("POST", &format!("{}/bill/payments", path), &payment);
+    assert_eq!(status, 409);
+    server.stop();
+}
+
+#[test]
+fn allergies_hold_up_an_order_until_the_guest_confirms() {
+    let server = TestServer::start("allergies", Menu::house(), 8);
+
+    let party = r#"{"name": "Lee", "size": 2, "at": "19:00", "allergies": ["Milk"]}"#;
+    server.request("POST", "/waitlist", party);
+    let (_, seating) = server.request("POST", "/seatings", r#"{"at": "19:05"}"#);
+
+    let soup = format!(r#"{{"party": {}, "at": "19:10", "items": [{{"menu_id": "soup"}}]}}"#, seating["party"]);
+    let (status, body) = server.request("POST", "/orders", &soup);
+    assert_eq!(status, 409);
+    assert_eq!(
+        body["error"],
+        "Soup of the day contains milk, which Lee's party is allergic to. Change the item, or confirm with the guest first."
+    );
+
+    let confirmed = soup.replace(r#""soup""#, r#""soup", "confirm_allergy": true"#);
+    let (status, order) = server.request("POST", "/orders", &confirmed);
+    assert_eq!(status, 201);
+    let items = format!("/orders/{}/items", order["id"]);
+
+    // The same goes for items added later
+    let ranch = r#"{"menu_id": "salad", "modifiers": ["Ranch"]}"#;
+    let (status, _) = server.request("POST", &items, ranch);
+    assert_eq!(status, 409);
+    let (status, _) = server.request("POST", &items, r#"{"menu_id": "salad", "modifiers": ["Vinaigrette"]}"#);
+    assert_eq!(status, 200);
+    let confirmed = r#"{"menu_id": "salad", "modifiers": ["Ranch"], "confirm_allergy": true}"#;
+    let (status, _) = server.request("POST", &items, confirmed);
+    assert_eq!(status, 200);
+
+    let (_, order) = server.request("GET", &format!("/orders/{}", order["id"]), "");
+    assert_eq!(order["items"].as_array().unwrap().len(), 3);
+    server.stop();
+}
+
+#[test]
+fn mistakes_get_the_right_status() {
+    let mut menu = Menu::house();
+    menu.set_available("soup", false).unwrap();
+    let server = TestServer::start("errors", menu, 10);
+
+    server.request("POST", "/waitlist", r#"{"name": "Okafor", "size": 2, "at": "12:00"}"#);
+    let (_, seating) = server.request("POST", "/seatings", r#"{"at": "12:05"}"#);
+    let order = format!(r#"{{"party": {}, "at": "12:10"}}"#, seating["party"]);
+    let (status, body) = server.request("POST", "/orders", &order);
+    assert_eq!(status, 201);
+    let order = body["id"].clone();
+
+    let (status, _) = server.request("POST", "/waitlist", r#"{"name": "Okafor"}"#);
+    assert_eq!(status, 400);
+    let (status, _) = server.request("GET", "/kitchen", "");
+    assert_eq!(status, 404);
+    let (status, _) = server.request("PUT", "/waitlist", "");
+    assert_eq!(status, 405);
+    let (status, _) = server.request("GET", &format!("/orders/{}/bill/unknown", order), "");
+    assert_eq!(status, 404);
+    let (status, body) = server.request("POST", &format!("/orders/{}/items", order), r#"{"menu_id": "soup"}"#);
+    assert_eq!(status, 409);
+    assert_eq!(body["error"], "Soup of the day is not available right now");
+    let (status, body) = server.request("PUT", &format!("/orders/{}/bill/tip", order), r#"{"tip": "-2.00"}"#);
+    assert_eq!(status, 400);
+    assert_eq!(body["error"], "a tip of -2.00 can't be negative");
+
+    let huge = format!("{{\"name\": \"{}\"}}", "x".repeat(MAX_BODY));
+    let (status, _) = server.request("POST", "/waitlist", &huge);
+    assert_eq!(status, 413);
+    server.stop();
+}
+
+#[test]
+fn headers_are_capped() {
+    let server = TestServer::start("headers", Menu::house(), 1);
+    let mut stream = TcpStream::connect(server.addr).unwrap();
+    let request = format!("GET /waitlist HTTP/1.1\r\nX-Padding: {}\r\n\r\n", "x".repeat(MAX_HEADERS));
+    // The server may stop reading before it's all sent
+    let _ = stream.write_all(request.as_bytes());
+    let (status, _) = response(stream);
+    assert_eq!(status, 431);
+    server.stop();
+}
+
+#[test]
+fn a_slow_client_doesnt_hold_up_anyone_else() {
+    let server = TestServer::start("slow", Menu::house(), 3);
+    let started = Instant::now();
+
+    // One client connects and says nothing, and another promises a body it
+    // never sends
+    let idle = TcpStream::connect(server.addr).unwrap();
+    let mut short = TcpStream::connect(server.addr).unwrap();
+    short
+        .write_all(b"POST /waitlist HTTP/1.1\r\nContent-Length: 100\r\n\r\n{\"name\"")
+        .unwrap();
+
+    let (status, _) = server.request("GET", "/waitlist", "");
+    assert_eq!(status, 200);
+    assert!(started.elapsed() < Duration::from_millis(500));
+
+    // Both are given up on once the read timeout passes
+    let (status, _) = response(short);
+    assert_eq!(status, 408);
+    let (status, _) = response(idle);
+    assert_eq!(status, 408);
+    server.stop();
+}
@@ -5,8 +5,6 @@
 // was adopted to avoid confusion when editing multiple files called mod.rs
 pub mod hosting;
 
-pub mod serving {
-    fn take_order() {}
-    fn serve_order() {}
-    fn take_payment() {}
-}
\ No newline at end of file
+// Serving isn't split out into its own file, so it can come straight from
+// the restaurant crate
+pub use restaurant::serving;
@@ -1,2 +1,4 @@
-pub fn add_to_waitlist() {}
-pub fn seat_at_table() {}
\ No newline at end of file
+// Just like front_of_house, hosting has a file of its own. Rather than
+// copying the waitlist and floor plan from the restaurant crate, it
+// re-exports them, so both crates seat parties the same way
+pub use restaurant::pub_hosting::*;
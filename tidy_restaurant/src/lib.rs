@@ -5,8 +5,28 @@
 // filesystem
 mod front_of_house;
 
-pub use crate::front_of_house::hosting;
+// Types shared by the whole crate come from the restaurant crate too
+pub use restaurant::clock;
+
+pub use crate::front_of_house::{hosting, serving};
 
 pub fn eat_at_restaurant() {
-    hosting::add_to_waitlist();
+    let mut waitlist = hosting::Waitlist::new(hosting::TableTurnover::new(12, 45));
+    let id = hosting::add_to_waitlist(
+        &mut waitlist,
+        hosting::Party::new("Patel", 4, clock::Time::hm(19, 0)),
+    );
+    println!(
+        "Party {:?} should wait about {} minutes",
+        id,
+        waitlist.estimated_wait(id).unwrap_or_default()
+    );
+
+    let mut floor = hosting::Floor::new();
+    floor.add_table(4);
+    let mut reservations = hosting::ReservationBook::new();
+    match hosting::seat_at_table(&mut waitlist, &mut reservations, &mut floor, clock::Time::hm(19, 15)) {
+        Ok(seating) => println!("Seated {} at {:?}", seating.party.name, seating.tables),
+        Err(e) => println!("Couldn't seat anyone: {}", e),
+    }
 }
\ No newline at end of file